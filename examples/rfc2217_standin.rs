@@ -0,0 +1,299 @@
+//! A minimal RFC 2217 server for trying out `rfc2217://` ports without
+//! lab hardware:
+//!
+//!     cargo run --example rfc2217_standin -- 127.0.0.1:2217 [max-baud]
+//!     octolog -p rfc2217://127.0.0.1:2217:115200:8E1:board
+//!
+//! It accepts the COM-PORT option, acknowledges every setting (capping the
+//! baudrate at `max-baud` when given), prints what it was asked to apply,
+//! sends a line every half second and echoes back each line it receives.
+//! Every outgoing line carries a 0xFF byte so IAC escaping is exercised.
+
+use octolog::sources::rfc2217::{
+    DO, IAC, OPT_COM_PORT, SERVER_OFFSET, SET_BAUDRATE, TelnetDecoder, TelnetMessage, WILL,
+    escape_iac, subnegotiation,
+};
+
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+#[tokio::main]
+async fn main() -> std::io::Result<()> {
+    let mut args = std::env::args().skip(1);
+    let addr = args.next().unwrap_or_else(|| "127.0.0.1:2217".to_string());
+    let max_baud: Option<u32> = args.next().and_then(|s| s.parse().ok());
+
+    let listener = TcpListener::bind(&addr).await?;
+    eprintln!("rfc2217 stand-in listening on {addr}");
+
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        eprintln!("client connected: {peer}");
+        tokio::spawn(async move {
+            if let Err(e) = serve(stream, max_baud).await {
+                eprintln!("client {peer}: {e}");
+            }
+            eprintln!("client disconnected: {peer}");
+        });
+    }
+}
+
+async fn serve(mut stream: TcpStream, max_baud: Option<u32>) -> std::io::Result<()> {
+    let mut decoder = TelnetDecoder::default();
+    let mut tick = tokio::time::interval(Duration::from_millis(500));
+    let mut buf = [0u8; 1024];
+    let mut line = Vec::new();
+    let mut count = 0u64;
+
+    loop {
+        tokio::select! {
+            _ = tick.tick() => {
+                count += 1;
+                let text = [format!("tick {count} ").as_bytes(), &[IAC], b"\r\n"].concat();
+                stream.write_all(&escape_iac(&text)).await?;
+            }
+            res = stream.read(&mut buf) => {
+                let n = res?;
+                if n == 0 {
+                    return Ok(());
+                }
+
+                let mut data = Vec::new();
+                let mut messages = Vec::new();
+                decoder.feed(&buf[..n], &mut data, &mut messages);
+
+                for msg in messages {
+                    match msg {
+                        TelnetMessage::Negotiate(WILL, OPT_COM_PORT) => {
+                            stream.write_all(&[IAC, DO, OPT_COM_PORT]).await?;
+                        }
+                        TelnetMessage::Negotiate(cmd, opt) => {
+                            eprintln!("negotiation {cmd} {opt} ignored");
+                        }
+                        TelnetMessage::Sub(payload) => {
+                            let [OPT_COM_PORT, code, value @ ..] = payload.as_slice() else {
+                                continue;
+                            };
+                            let value = applied(*code, value, max_baud);
+                            eprintln!("set {code}: {value:?}");
+                            stream
+                                .write_all(&subnegotiation(code + SERVER_OFFSET, &value))
+                                .await?;
+                        }
+                    }
+                }
+
+                for &b in &data {
+                    if b == b'\r' || b == b'\n' {
+                        if !line.is_empty() {
+                            let echo = [b"echo: ", line.as_slice(), b"\r\n"].concat();
+                            stream.write_all(&escape_iac(&echo)).await?;
+                            line.clear();
+                        }
+                    } else {
+                        line.push(b);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The value the stand-in reports as applied for a setting.
+fn applied(code: u8, value: &[u8], max_baud: Option<u32>) -> Vec<u8> {
+    match (code, value, max_baud) {
+        (SET_BAUDRATE, &[a, b, c, d], Some(max)) => u32::from_be_bytes([a, b, c, d])
+            .min(max)
+            .to_be_bytes()
+            .to_vec(),
+        _ => value.to_vec(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use octolog::core::{
+        AnsiPolicy, AppEvent, FlowControl, LineEnding, LineFraming, LinePolicy, PortDefaults,
+        PortSpec,
+    };
+    use octolog::runtime::shutdown_channel;
+    use octolog::sources::rfc2217::{SET_DATASIZE, SET_PARITY, SET_STOPSIZE};
+    use octolog::sources::{EventSource, Rfc2217Source, SourceContext};
+    use tokio::sync::mpsc;
+    use tokio::time::timeout;
+
+    const WAIT: Duration = Duration::from_secs(5);
+
+    /// Runs the stand-in on an ephemeral port and returns its address.
+    async fn standin(max_baud: Option<u32>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(serve(stream, max_baud));
+            }
+        });
+        addr
+    }
+
+    /// Reads until `done` holds for the data or messages decoded so far.
+    async fn read_until(
+        stream: &mut TcpStream,
+        decoder: &mut TelnetDecoder,
+        data: &mut Vec<u8>,
+        messages: &mut Vec<TelnetMessage>,
+        done: impl Fn(&[u8], &[TelnetMessage]) -> bool,
+    ) {
+        let mut buf = [0u8; 1024];
+        while !done(data, messages) {
+            let n = timeout(WAIT, stream.read(&mut buf))
+                .await
+                .expect("stand-in went quiet")
+                .unwrap();
+            assert!(n > 0, "stand-in closed the connection");
+            decoder.feed(&buf[..n], data, messages);
+        }
+    }
+
+    #[tokio::test]
+    async fn negotiates_acknowledges_settings_and_escapes_iac() {
+        let addr = standin(Some(57_600)).await;
+        let mut stream = TcpStream::connect(&addr).await.unwrap();
+        let mut decoder = TelnetDecoder::default();
+        let (mut data, mut messages) = (Vec::new(), Vec::new());
+
+        stream.write_all(&[IAC, WILL, OPT_COM_PORT]).await.unwrap();
+        read_until(
+            &mut stream,
+            &mut decoder,
+            &mut data,
+            &mut messages,
+            |_, m| !m.is_empty(),
+        )
+        .await;
+        assert!(matches!(
+            messages.as_slice(),
+            [TelnetMessage::Negotiate(DO, OPT_COM_PORT)]
+        ));
+
+        messages.clear();
+        let settings = [
+            (SET_BAUDRATE, 115_200u32.to_be_bytes().to_vec()),
+            (SET_DATASIZE, vec![7]),
+            (SET_PARITY, vec![3]),
+            (SET_STOPSIZE, vec![2]),
+        ];
+        for (code, value) in &settings {
+            stream
+                .write_all(&subnegotiation(*code, value))
+                .await
+                .unwrap();
+        }
+        read_until(
+            &mut stream,
+            &mut decoder,
+            &mut data,
+            &mut messages,
+            |_, m| m.len() == settings.len(),
+        )
+        .await;
+        let replies = messages
+            .iter()
+            .map(|m| match m {
+                TelnetMessage::Sub(payload) => payload.clone(),
+                other => panic!("unexpected {other:?}"),
+            })
+            .collect::<Vec<_>>();
+        let capped = [
+            &[OPT_COM_PORT, SET_BAUDRATE + SERVER_OFFSET][..],
+            &57_600u32.to_be_bytes(),
+        ];
+        assert_eq!(replies[0], capped.concat(), "baudrate is capped");
+        assert_eq!(replies[1], [OPT_COM_PORT, SET_DATASIZE + SERVER_OFFSET, 7]);
+        assert_eq!(replies[2], [OPT_COM_PORT, SET_PARITY + SERVER_OFFSET, 3]);
+        assert_eq!(replies[3], [OPT_COM_PORT, SET_STOPSIZE + SERVER_OFFSET, 2]);
+
+        // A 0xFF byte each way: doubled on the wire, single in the data.
+        stream.write_all(&escape_iac(b"a\xffb\r\n")).await.unwrap();
+        let echo = b"echo: a\xffb\r\n";
+        read_until(
+            &mut stream,
+            &mut decoder,
+            &mut data,
+            &mut messages,
+            |d, _| d.windows(echo.len()).any(|w| w == echo),
+        )
+        .await;
+        read_until(
+            &mut stream,
+            &mut decoder,
+            &mut data,
+            &mut messages,
+            |d, _| d.windows(3).any(|w| w == b" \xff\r"),
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn client_reports_capped_baudrate_and_reads_lines() {
+        let addr = standin(Some(57_600)).await;
+        let spec = format!("rfc2217://{addr}:115200:7E1:Board")
+            .parse::<PortSpec>()
+            .unwrap()
+            .resolve(&PortDefaults {
+                baud: 115_200,
+                line_ending: LineEnding::default(),
+                framing: "8N1".parse().unwrap(),
+                flow_control: FlowControl::None,
+                dtr: LinePolicy::Keep,
+                rts: LinePolicy::Keep,
+                line_framing: LineFraming::default(),
+                min_level: None,
+                ansi: AnsiPolicy::default(),
+                device_ts: None,
+            });
+
+        let (tx, mut rx) = mpsc::channel(64);
+        let (commands_tx, commands) = mpsc::channel(4);
+        let (shutdown, handle) = shutdown_channel();
+        let task = tokio::spawn(Box::new(Rfc2217Source::new(spec)).run(SourceContext {
+            tx,
+            commands,
+            capture: None,
+            defmt: None,
+            shutdown,
+            reconnect_delay: Duration::from_secs(1),
+        }));
+        let (mut capped, mut tick, mut echo) = (false, false, false);
+        while !(capped && tick && echo) {
+            let event = timeout(WAIT, rx.recv())
+                .await
+                .expect("no events from the client")
+                .unwrap();
+            match event {
+                AppEvent::System { message, .. } if message.contains("server set") => {
+                    assert!(
+                        message.ends_with("server set baudrate to 57600 instead of 115200"),
+                        "{message}"
+                    );
+                    capped = true;
+                }
+                // The stand-in's lines end in a 0xFF byte.
+                AppEvent::LogLine { raw, .. } if raw.starts_with("tick ") => {
+                    assert!(raw.ends_with('\u{fffd}'), "{raw:?}");
+                    if !tick {
+                        commands_tx.send("hello".to_string()).await.unwrap();
+                    }
+                    tick = true;
+                }
+                AppEvent::LogLine { raw, .. } if raw == "echo: hello" => echo = true,
+                _ => {}
+            }
+        }
+
+        handle.trigger();
+        timeout(WAIT, task).await.unwrap().unwrap();
+    }
+}
@@ -0,0 +1,75 @@
+use crate::config::Config;
+use crate::core::{AppError, AppResult};
+use crate::sources::serial::scan::{self, PortInfo};
+
+pub fn run(cfg: &Config) -> AppResult<()> {
+    let ports = scan::list_available_ports(cfg.list_filter.clone(), cfg.list_all)?;
+
+    if cfg.list_json {
+        let json =
+            serde_json::to_string_pretty(&ports).map_err(|e| AppError::Runtime(e.to_string()))?;
+        println!("{json}");
+        return Ok(());
+    }
+
+    if ports.is_empty() {
+        println!("No serial ports found.");
+        return Ok(());
+    }
+
+    print_table(&ports);
+    Ok(())
+}
+
+fn print_table(ports: &[PortInfo]) {
+    let header = [
+        "PORT",
+        "TYPE",
+        "VID:PID",
+        "SERIAL",
+        "MANUFACTURER",
+        "PRODUCT",
+        "BY-ID",
+    ];
+    let rows = ports.iter().map(row).collect::<Vec<_>>();
+
+    let mut widths = header.map(str::len);
+    for r in &rows {
+        for (w, cell) in widths.iter_mut().zip(r) {
+            *w = (*w).max(cell.chars().count());
+        }
+    }
+
+    print_row(&header.map(str::to_string), &widths);
+    for r in &rows {
+        print_row(r, &widths);
+    }
+}
+
+fn row(p: &PortInfo) -> [String; 7] {
+    let dash = |v: &Option<String>| v.clone().unwrap_or_else(|| "-".to_string());
+    let ids = match (p.vid, p.pid) {
+        (Some(vid), Some(pid)) => format!("{vid:04x}:{pid:04x}"),
+        _ => "-".to_string(),
+    };
+
+    [
+        p.path.clone(),
+        p.kind.as_str().to_string(),
+        ids,
+        dash(&p.serial),
+        dash(&p.manufacturer),
+        dash(&p.product),
+        dash(&p.by_id),
+    ]
+}
+
+fn print_row(cells: &[String; 7], widths: &[usize; 7]) {
+    let line = cells
+        .iter()
+        .zip(widths)
+        .map(|(c, w)| format!("{c:<w$}"))
+        .collect::<Vec<_>>()
+        .join("  ");
+    println!("{}", line.trim_end());
+}
@@ -1,11 +1,20 @@
+mod list;
+
 use crate::cli::CliArgs;
 use crate::config::Config;
-use crate::core::{AppError, AppResult};
+use crate::core::{AppError, AppResult, OutputFormat, Transport};
 use crate::processing::LogProcessor;
-use crate::runtime::engine::LineFilter;
-use crate::runtime::{Engine, shutdown_channel};
-use crate::sinks::{FileSink, StdoutSink, spawn_fanout, spawn_sink_worker};
-use crate::sources::serial;
+use crate::runtime::commands::spawn_stdin_commands;
+use crate::runtime::{CommandRouter, Engine, shutdown_channel};
+use crate::sinks::rotate::RotationPolicy;
+use crate::sinks::{
+    EventSink, FileSink, JsonlSink, PerPortSink, RenderOptions, Route, StdoutSink, TuiSink,
+    spawn_fanout, spawn_sink_worker,
+};
+use crate::sources::{self, CaptureWriter, EventSource, ReplaySource, StdinSource, serial};
+use std::collections::HashMap;
+use std::io::IsTerminal;
+use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::sync::mpsc;
 
@@ -13,57 +22,210 @@ pub async fn run(args: CliArgs) -> AppResult<()> {
     let cfg = Config::try_from(args)?;
 
     if cfg.list {
-        let ports = serial::scan::list_available_ports(None)?;
-        if ports.is_empty() {
-            println!("No serial ports found.");
-            return Ok(());
-        }
-        for p in ports {
-            println!("{p}");
-        }
-        return Ok(());
+        return list::run(&cfg);
     }
 
     let (shutdown, shutdown_handle) = shutdown_channel();
 
-    let mut sink_txs = Vec::new();
-    let mut sink_handles = Vec::new();
+    let commands = CommandRouter::new();
+
+    // With nothing live to keep up with, a replay or piped input delivers
+    // every line, and ends the session once done unless the TUI is up for
+    // inspection.
+    let reads_stdin = cfg.ports.iter().any(|p| p.transport == Transport::Stdin);
+    let replay_only = cfg.replay.is_some() && cfg.ports.is_empty() && cfg.discover.is_none();
+    let stdin_only =
+        reads_stdin && cfg.ports.len() == 1 && cfg.replay.is_none() && cfg.discover.is_none();
+    let bounded = replay_only || stdin_only;
 
-    let stdout_sink = Arc::new(StdoutSink::new().with_highlights(cfg.highlight.clone()));
-    let (stdout_tx, stdout_h) = spawn_sink_worker(stdout_sink, cfg.runtime.event_bus_capacity);
-    sink_txs.push(stdout_tx);
-    sink_handles.push(stdout_h);
+    // Sinks report their own problems as system events, so the bus comes
+    // first.
+    let (tx, rx) = mpsc::channel(cfg.runtime.event_bus_capacity);
+
+    let mut routes = Vec::new();
+    let mut sink_handles = Vec::new();
+    let render = RenderOptions {
+        keep_ansi: cfg.keep_ansi,
+    };
 
     if let Some(path) = cfg.output {
-        let file_sink = Arc::new(FileSink::new(path).map_err(|e| AppError::Config(e.to_string()))?);
+        let file_sink = file_sink(path, cfg.output_format, &cfg.rotation, render)?;
         let (file_tx, file_h) = spawn_sink_worker(file_sink, cfg.runtime.event_bus_capacity);
-        sink_txs.push(file_tx);
+        routes.push(Route::new(file_tx).with_filter(cfg.output_filter.clone()));
         sink_handles.push(file_h);
     }
 
+    if let Some(template) = cfg.port_output {
+        let port_sink = Arc::new(
+            PerPortSink::new(template, cfg.output_format, cfg.rotation.clone(), render)
+                .with_events(tx.clone()),
+        );
+        let (port_tx, port_h) = spawn_sink_worker(port_sink, cfg.runtime.event_bus_capacity);
+        routes.push(Route::new(port_tx).with_filter(cfg.output_filter.clone()));
+        sink_handles.push(port_h);
+    }
+
+    if let Some(path) = cfg.json_output {
+        let json_sink = file_sink(path, OutputFormat::Jsonl, &cfg.rotation, render)?;
+        let (json_tx, json_h) = spawn_sink_worker(json_sink, cfg.runtime.event_bus_capacity);
+        routes.push(Route::new(json_tx).with_filter(cfg.json_filter.clone()));
+        sink_handles.push(json_h);
+    }
+
+    for route in &cfg.routes {
+        let sink = file_sink(route.path.clone(), route.format, &cfg.rotation, render)?;
+        let (tx, h) = spawn_sink_worker(sink, cfg.runtime.event_bus_capacity);
+        routes.push(Route::new(tx).with_filter(route.filter.clone()));
+        sink_handles.push(h);
+    }
+
+    // Parse each firmware ELF once, up front, so a bad path stops the
+    // session instead of leaving a port silently undecoded.
+    let mut tables = HashMap::new();
+    let configured = cfg.ports.iter().chain(&cfg.replay_ports);
+    for path in configured.clone().filter_map(|p| p.defmt.as_deref()) {
+        if !tables.contains_key(path) {
+            let table = sources::defmt::load(path)
+                .map_err(|e| AppError::Config(format!("defmt: {}: {e}", path.display())))?;
+            tables.insert(path.to_path_buf(), table);
+        }
+    }
+
+    let (capture, capture_task) = match cfg.capture.as_deref() {
+        Some(path) => {
+            let (w, task) = CaptureWriter::spawn(path, tx.clone())
+                .map_err(|e| AppError::Config(format!("capture: {}: {e}", path.display())))?;
+            (Some(w), Some(task))
+        }
+        None => (None, None),
+    };
+
+    // The terminal is only taken over once every fallible setup step is done.
+    let mut ui_task = None;
+
+    if cfg.tui {
+        let sources = cfg.ports.iter().map(|p| p.source_id()).collect();
+        let tui_sink = Arc::new(TuiSink::new(sources).with_commands(commands.clone()));
+        let terminal = ratatui::try_init().map_err(|e| AppError::Runtime(e.to_string()))?;
+        ui_task = Some(tui_sink.spawn_ui(terminal, shutdown.clone(), shutdown_handle.clone()));
+        let (tui_tx, tui_h) = spawn_sink_worker(tui_sink, cfg.runtime.event_bus_capacity);
+        routes.push(
+            Route::new(tui_tx)
+                .with_filter(cfg.terminal_filter.clone())
+                .with_system(true),
+        );
+        sink_handles.push(tui_h);
+    } else {
+        let stdout_sink = Arc::new(StdoutSink::new().with_highlights(cfg.highlight.clone()));
+        let (stdout_tx, stdout_h) = spawn_sink_worker(stdout_sink, cfg.runtime.event_bus_capacity);
+        routes.push(
+            Route::new(stdout_tx)
+                .with_filter(cfg.terminal_filter.clone())
+                .with_system(true),
+        );
+        sink_handles.push(stdout_h);
+    }
+
     let (processed_tx, processed_rx) = mpsc::channel(cfg.runtime.event_bus_capacity);
 
-    let fanout_task = spawn_fanout(processed_rx, sink_txs);
+    let fanout_task = spawn_fanout(processed_rx, routes, bounded);
 
-    let processor = LogProcessor::new();
-    let filter = LineFilter::new(cfg.filter.clone(), cfg.exclude.clone());
-    let engine = Engine::new(processor, processed_tx, shutdown.clone()).with_filter(filter);
+    let mut processor = LogProcessor::new()
+        .with_level_patterns(cfg.level_patterns.clone())
+        .with_ansi(cfg.port_defaults.ansi)
+        .with_device_ts(cfg.port_defaults.device_ts.as_ref());
+    for port in configured {
+        let source = port.source_id();
+        // A command's stderr is its own stream but follows the port's settings.
+        if let Transport::Command { .. } = port.transport {
+            let stderr = sources::process::stderr_id(&source);
+            processor = processor
+                .with_port_ansi(stderr.clone(), port.ansi)
+                .with_port_device_ts(stderr.clone(), port.device_ts.as_ref());
+        }
+        processor = processor
+            .with_port_ansi(source.clone(), port.ansi)
+            .with_port_device_ts(source.clone(), port.device_ts.as_ref());
+    }
+    let engine = Engine::new(processor, processed_tx, shutdown.clone())
+        .with_clock_drift(cfg.clock_drift)
+        .with_backpressure(bounded);
 
-    let (tx, rx) = mpsc::channel(cfg.runtime.event_bus_capacity);
     let engine_task = tokio::spawn(engine.run(rx));
 
-    let source_tasks = serial::SerialSource::new(cfg.ports, tx, shutdown.clone()).spawn();
+    if !cfg.tui && !reads_stdin && std::io::stdin().is_terminal() {
+        spawn_stdin_commands(commands.clone(), tx.clone());
+    }
 
-    tokio::signal::ctrl_c()
-        .await
-        .map_err(|e| AppError::Runtime(e.to_string()))?;
+    let claimed = cfg.ports.clone();
+    let mut source_tasks = cfg
+        .ports
+        .into_iter()
+        .map(|spec| {
+            let defmt = spec.defmt.as_ref().and_then(|p| tables.get(p)).cloned();
+            let source: Box<dyn EventSource> = if stdin_only && !cfg.tui {
+                Box::new(StdinSource::new(spec).exit_when_done(shutdown_handle.clone()))
+            } else {
+                sources::from_spec(spec)
+            };
+            sources::spawn_source(
+                source,
+                tx.clone(),
+                &commands,
+                capture.clone(),
+                defmt,
+                shutdown.clone(),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    if let Some(path) = cfg.replay {
+        let mut replay = ReplaySource::new(
+            path,
+            cfg.replay_speed,
+            cfg.port_defaults.clone(),
+            cfg.replay_ports,
+            tx.clone(),
+            shutdown.clone(),
+        )
+        .with_defmt(tables);
+        if replay_only && !cfg.tui {
+            replay = replay.exit_when_done(shutdown_handle.clone());
+        }
+        source_tasks.push(replay.spawn());
+    }
+
+    if let Some(rule) = cfg.discover {
+        let watcher =
+            serial::HotplugWatcher::new(rule, cfg.port_defaults, claimed, tx, shutdown.clone())
+                .with_commands(commands)
+                .with_capture(capture.clone());
+        source_tasks.push(watcher.spawn());
+    }
+
+    let mut stop = shutdown.clone();
+    tokio::select! {
+        res = tokio::signal::ctrl_c() => {
+            res.map_err(|e| AppError::Runtime(e.to_string()))?;
+        }
+        _ = stop.changed() => {}
+    }
 
     shutdown_handle.trigger();
 
+    if let Some(t) = ui_task {
+        let _ = t.await;
+    }
+
     for t in source_tasks {
         let _ = t.await;
     }
 
+    if let (Some(capture), Some(task)) = (capture, capture_task) {
+        capture.close().await;
+        let _ = task.await;
+    }
+
     engine_task
         .await
         .map_err(|e| AppError::Runtime(e.to_string()))??;
@@ -76,3 +238,21 @@ pub async fn run(args: CliArgs) -> AppResult<()> {
 
     Ok(())
 }
+
+/// A text or JSON Lines output file.
+fn file_sink(
+    path: PathBuf,
+    format: OutputFormat,
+    rotation: &RotationPolicy,
+    render: RenderOptions,
+) -> AppResult<Arc<dyn EventSink>> {
+    let invalid = |e: std::io::Error| AppError::Config(e.to_string());
+    Ok(match format {
+        OutputFormat::Text => {
+            Arc::new(FileSink::new(path, rotation.clone(), render).map_err(invalid)?)
+        }
+        OutputFormat::Jsonl => {
+            Arc::new(JsonlSink::new(path, rotation.clone(), render).map_err(invalid)?)
+        }
+    })
+}
@@ -1,3 +1,6 @@
+use crate::core::{AnsiPolicy, LineEnding, LogLevel, OutputFormat, TimestampFormat};
+use crate::sinks::rotate::{Compression, RotateEvery, parse_size};
+use crate::sources::ReplaySpeed;
 use clap::Parser;
 use std::path::PathBuf;
 
@@ -6,43 +9,208 @@ use std::path::PathBuf;
     name = "octolog",
     version,
     about = "Multi-serial-port log monitor (CLI/TUI)",
-    after_help = "Examples:\n  octolog --list\n  octolog -p /dev/ttyACM0:115200:Sensor -p /dev/ttyACM1:TFM\n  octolog -p /dev/ttyUSB0 --baud 9600\n"
+    after_help = "Examples:\n  octolog --list\n  octolog --list --all --json\n  octolog -p /dev/ttyACM0:115200:Sensor -p /dev/ttyACM1:TFM\n  octolog -p /dev/ttyUSB0 --baud 9600\n  octolog --tui -p /dev/ttyACM0:GPS -p /dev/ttyACM1:Sensor\n  octolog --profile bench-a\n  octolog --auto --tui\n  octolog --replay night.cap --replay-speed max -o night.log\n"
 )]
 pub struct CliArgs {
     /// Print available ports and exit
     #[arg(long)]
     pub list: bool,
 
-    /// Serial ports to monitor
+    /// With --list: only show ports whose path matches this regex
+    #[arg(long = "list-filter", value_name = "REGEX", requires = "list")]
+    pub list_filter: Option<String>,
+
+    /// With --list: include every port, not just USB/ACM/COM devices
+    #[arg(long, requires = "list")]
+    pub all: bool,
+
+    /// With --list: print JSON instead of a table
+    #[arg(long, requires = "list")]
+    pub json: bool,
+
+    /// Config file to use instead of ./octolog.toml and ~/.config/octolog/octolog.toml
+    #[arg(short = 'c', long = "config", value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// Named profile from the config file to apply
+    #[arg(short = 'P', long = "profile", value_name = "NAME")]
+    pub profile: Option<String>,
+
+    /// Full-screen terminal UI with one pane per port
+    #[arg(long, overrides_with = "no_tui")]
+    pub tui: bool,
+
+    /// Print to stdout even if the config file enables the TUI
+    #[arg(long = "no-tui", overrides_with = "tui")]
+    pub no_tui: bool,
+
+    /// Serial ports or network sources to monitor
+    ///
+    /// Format: target[:baudrate][:framing][:alias][:hex][:key=value...]
+    ///
+    /// Target: a device path, usb:VID:PID, by-id:NAME, or serial=/manufacturer=/product=
+    ///
+    /// Network: tcp://host:port, tcp-listen://[host:]port, udp://[host:]port or
+    /// unix:///path, followed by [:alias][:key=value...]; rfc2217://host:port takes
+    /// the same fields as a serial port
+    ///
+    /// Local: cmd:COMMAND (stdout and stderr as two streams, restart=on to rerun
+    /// it when it exits) or file:PATH (followed like tail -F), taken whole up to
+    /// a '::' before [alias][:key=value...] or quoted ('COMMAND':alias); or
+    /// - / stdin (piped input), then [:alias]
+    ///
+    /// Options: flow=none|sw|hw, dtr=on|off|keep, rts=on|off|keep, eol=cr|lf|crlf
+    ///
+    /// Line framing: delim=any|lf|crlf|cr|0xNN|TEXT, idle=250ms, max_line=BYTES,
+    /// keep_empty=on|off
+    ///
+    /// hex shows the raw byte stream as hex dumps, one per read or, with idle=,
+    /// per burst between gaps
+    ///
+    /// Binary frames (shown as hex dumps instead of lines):
+    /// frame=cobs|slip|len8|len16le|len16be|len32le|len32be, header=BYTES,
+    /// crc=crc8|crc16|crc16-modbus|crc32
+    ///
+    /// defmt=ELF decodes defmt logs using the firmware's ELF file
+    ///
+    /// min_level=trace|debug|info|warn|error overrides --min-level for the port
+    ///
+    /// ansi=pass|strip|sanitize overrides --ansi for the port
     ///
-    /// Format: path[:baudrate][:alias]
+    /// device_ts=zephyr|esp-idf|kernel overrides --device-ts for the port
     ///
     /// Examples:
     ///   -p /dev/ttyACM0:115200:Sensor
     ///   -p /dev/ttyACM1:TFM
+    ///   -p /dev/ttyUSB0:9600:7E1:Modem:flow=hw:dtr=off
+    ///   -p usb:0483:5740:serial=A1B2:GPS
+    ///   -p tcp://10.0.0.5:2000:Bench
+    ///   -p rfc2217://lab-1:2217:921600:8N1:Target:flow=hw
+    ///   -p /dev/ttyUSB1:921600:Telemetry:frame=cobs:crc=crc16
+    ///   -p /dev/ttyUSB2:9600:NewBoard:hex:idle=20ms
+    ///   -p /dev/ttyACM2:115200:App:defmt=target/thumbv7em-none-eabihf/release/app
+    ///   -p /dev/ttyUSB3:GPS:min_level=warn
+    ///   -p /dev/ttyUSB4:Shell:ansi=strip
+    ///   -p /dev/ttyACM3:Zephyr:device_ts=zephyr
+    ///   -p 'cmd:./companion --verbose::Host:restart=on'
     #[arg(short = 'p', long = "port", value_name = "PORT", num_args = 1..)]
     pub port: Vec<String>,
 
-    /// Default baudrate (used when a port does not specify one)
-    #[arg(short = 'b', long, value_name = "BAUD", default_value_t = 115200)]
-    pub baud: u32,
+    /// Attach to serial devices as they are plugged in, and detach on removal
+    ///
+    /// Matches the same devices as --list unless the config file has a
+    /// [discover] rule.
+    #[arg(long)]
+    pub auto: bool,
+
+    /// Record the raw bytes read from every port, with timestamps, to this file
+    #[arg(long = "capture", value_name = "PATH")]
+    pub capture: Option<PathBuf>,
+
+    /// Play back a file written by --capture as if its ports were connected
+    #[arg(long = "replay", value_name = "PATH")]
+    pub replay: Option<PathBuf>,
+
+    /// Playback speed for --replay: 1 (real time), a factor such as 10x, or max [default: 1]
+    #[arg(long = "replay-speed", value_name = "SPEED", requires = "replay")]
+    pub replay_speed: Option<ReplaySpeed>,
+
+    /// Default baudrate (used when a port does not specify one) [default: 115200]
+    #[arg(short = 'b', long, value_name = "BAUD")]
+    pub baud: Option<u32>,
+
+    /// Line ending appended to commands sent to a port (cr, lf, crlf) [default: lf]
+    #[arg(long = "line-ending", value_name = "EOL")]
+    pub line_ending: Option<LineEnding>,
 
     /// Write rendered output to a file
+    ///
+    /// {date}, {time} and {datetime} in the path expand to the UTC time the
+    /// file is opened, e.g. logs/{date}/bench.log
     #[arg(short = 'o', long = "output", value_name = "PATH")]
     pub output: Option<PathBuf>,
 
-    /// Highlight patterns (can be repeated)
+    /// Format of the --output file: text or jsonl [default: text]
+    #[arg(long = "output-format", value_name = "FORMAT")]
+    pub output_format: Option<OutputFormat>,
+
+    /// Also write each port to its own file, created when its first line arrives
+    ///
+    /// The path must contain {alias} or {port}; {date}, {time} and {datetime}
+    /// expand as for --output. Uses --output-format and the rotation options.
+    ///
+    /// Example:
+    ///   --port-output 'logs/{date}/{alias}.log'
+    #[arg(long = "port-output", value_name = "TEMPLATE")]
+    pub port_output: Option<PathBuf>,
+
+    /// Also write every event as JSON Lines to this file
+    #[arg(long = "json-output", value_name = "PATH")]
+    pub json_output: Option<PathBuf>,
+
+    /// Start a new output file once the current one reaches this size (e.g. 100M, 1G)
+    #[arg(long = "rotate-size", value_name = "SIZE", value_parser = parse_size)]
+    pub rotate_size: Option<u64>,
+
+    /// Start a new output file every hour or day (hourly, daily)
+    #[arg(long = "rotate-every", value_name = "PERIOD")]
+    pub rotate_every: Option<RotateEvery>,
+
+    /// Number of rotated files to keep; older ones are deleted
+    #[arg(long = "rotate-keep", value_name = "N")]
+    pub rotate_keep: Option<usize>,
+
+    /// Compress rotated files (none, gzip, zstd) [default: none]
+    #[arg(long = "rotate-compress", value_name = "CODEC")]
+    pub rotate_compress: Option<Compression>,
+
+    /// Append to existing output files instead of truncating them
+    #[arg(long, overrides_with = "no_append")]
+    pub append: bool,
+
+    /// Truncate output files even if the config file sets append
+    #[arg(long = "no-append", overrides_with = "append")]
+    pub no_append: bool,
+
+    /// Keep the escape sequences devices send in output files
+    ///
+    /// By default --output, --port-output, --json-output and --route files
+    /// get plain text.
+    #[arg(long = "keep-ansi", overrides_with = "no_keep_ansi")]
+    pub keep_ansi: bool,
+
+    /// Write plain text to output files even if the config file sets keep_ansi
+    #[arg(long = "no-keep-ansi", overrides_with = "keep_ansi")]
+    pub no_keep_ansi: bool,
+
+    /// Highlight matching text (can be repeated)
+    ///
+    /// TEXT[=STYLE] or /REGEX/[i][=STYLE]. STYLE is a comma-separated list
+    /// of a color (red, bright-cyan, #ff8800, 208), on-COLOR for the
+    /// background, and bold, dim, italic, underline, reverse; the default
+    /// is yellow,bold. With capture groups only the groups are highlighted.
+    /// Where rules overlap the first one wins.
     ///
     /// Examples:
-    ///   --highlight ERROR --highlight WARN
-    #[arg(long = "highlight", value_name = "PATTERN", num_args = 1..)]
+    ///   --highlight ERROR=red,bold --highlight WARN
+    ///   --highlight '/\d+\.\d+\.\d+\.\d+/=cyan'
+    ///   --highlight '/rssi=(-?\d+)/=black,on-yellow'
+    #[arg(long = "highlight", value_name = "RULE", num_args = 1..)]
     pub highlight: Vec<String>,
 
-    /// Only keep log lines containing this substring
+    /// Only keep log lines matching this expression
     ///
-    /// Example:
+    /// Terms: text, "quoted text"[i], /regex/[i], src:NAME, src:/regex/,
+    /// level:LEVEL, level>=LEVEL (also >, <=, <), kind:line|frame|echo|sys,
+    /// combined with && (and), || (or), ! (not) and parentheses
+    ///
+    /// System messages only pass a filter with a kind: term, except on the
+    /// terminal, which always shows them.
+    ///
+    /// Examples:
     ///   --filter "AT+"
-    #[arg(long = "filter", value_name = "TEXT")]
+    ///   --filter 'src:GPS && /fix lost/i || level>=error'
+    #[arg(long = "filter", value_name = "EXPR")]
     pub filter: Option<String>,
 
     /// Drop log lines containing these substrings (can be repeated)
@@ -51,4 +219,81 @@ pub struct CliArgs {
     ///   --exclude "DEBUG" --exclude "heartbeat"
     #[arg(long = "exclude", value_name = "TEXT", num_args = 1..)]
     pub exclude: Vec<String>,
+
+    /// Only show lines matching this expression on the terminal (stdout or TUI)
+    ///
+    /// Applies on top of --filter and --exclude, like the other sink filters.
+    ///
+    /// Example:
+    ///   --terminal-filter 'level>=error || src:Sensor'
+    #[arg(long = "terminal-filter", value_name = "EXPR")]
+    pub terminal_filter: Option<String>,
+
+    /// Only write lines matching this expression to --output and --port-output
+    #[arg(long = "output-filter", value_name = "EXPR")]
+    pub output_filter: Option<String>,
+
+    /// Only write lines matching this expression to --json-output
+    #[arg(long = "json-filter", value_name = "EXPR")]
+    pub json_filter: Option<String>,
+
+    /// Also write lines matching a filter to their own file (can be repeated)
+    ///
+    /// Files ending in .jsonl or .json get JSON Lines, others the text format.
+    ///
+    /// Example:
+    ///   --route 'logs/panics.log=/panic|assert|HardFault/'
+    #[arg(long = "route", value_name = "PATH=EXPR", num_args = 1..)]
+    pub route: Vec<String>,
+
+    /// Only show lines at or above this level (trace, debug, info, warn, error)
+    ///
+    /// Applies to stdout or the TUI; output files keep every line. Lines whose
+    /// level is not recognized are still shown; add --terminal-filter
+    /// 'level>=LEVEL' to hide them as well. Levels come from
+    /// the source (defmt) or are recognized in the text: Zephyr, ESP-IDF,
+    /// logcat, syslog priorities and [LEVEL] tags, plus --level-pattern.
+    #[arg(long = "min-level", value_name = "LEVEL")]
+    pub min_level: Option<LogLevel>,
+
+    /// Regex whose `level` group holds a line's level (can be repeated)
+    ///
+    /// Tried before the built-in formats.
+    ///
+    /// Example:
+    ///   --level-pattern '^\d+ (?<level>[A-Z]+):'
+    #[arg(long = "level-pattern", value_name = "REGEX", num_args = 1..)]
+    pub level_pattern: Vec<String>,
+
+    /// What to do with ANSI escape sequences in device lines [default: sanitize]
+    ///
+    /// pass leaves lines untouched, strip removes every sequence, sanitize
+    /// keeps colors, drops cursor movement and other sequences, and resets
+    /// the colors at the end of each line.
+    #[arg(long = "ansi", value_name = "POLICY")]
+    pub ansi: Option<AnsiPolicy>,
+
+    /// Read the device's own timestamp from each line and show it next to
+    /// the host time
+    ///
+    /// zephyr ([00:00:01.234,567]), esp-idf (I (1234) tag:, milliseconds),
+    /// kernel ([    1.234567]), or /REGEX/UNIT where the regex's ts group,
+    /// or first group, holds a number of s, ms, us or ns.
+    ///
+    /// Examples:
+    ///   --device-ts zephyr
+    ///   --device-ts '/^(\d+) /ms'
+    #[arg(long = "device-ts", value_name = "FORMAT")]
+    pub device_ts: Option<TimestampFormat>,
+
+    /// Estimate how fast each device clock runs against the host clock
+    ///
+    /// Reported in ppm for ports with --device-ts, first after a minute of
+    /// device time and then every five minutes.
+    #[arg(long = "clock-drift", overrides_with = "no_clock_drift")]
+    pub clock_drift: bool,
+
+    /// Skip the clock drift estimate even if the config file enables it
+    #[arg(long = "no-clock-drift", overrides_with = "clock_drift")]
+    pub no_clock_drift: bool,
 }
@@ -0,0 +1,737 @@
+use crate::core::port_spec::parse_usb_id;
+use crate::core::{
+    AnsiPolicy, AppError, AppResult, Delimiter, FlowControl, Framing, LineEnding, LinePolicy,
+    LogLevel, OutputFormat, PortSpec, PortSpecParseError, TimestampFormat, Transport, UsbMatch,
+};
+use crate::processing::{Filter, LevelPattern};
+
+use crate::sinks::highlight::{HighlightRule, Style};
+use crate::sinks::rotate::{self, Compression, RotateEvery};
+
+use super::{DiscoveryConfig, RouteConfig};
+
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+use toml::Spanned;
+
+pub const FILE_NAME: &str = "octolog.toml";
+
+/// Settings as written in `octolog.toml`, either at the top level or inside
+/// a `[profiles.<name>]` table.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawSettings {
+    tui: Option<bool>,
+    baud: Option<u32>,
+    line_ending: Option<Spanned<String>>,
+    framing: Option<Spanned<String>>,
+    flow_control: Option<Spanned<String>>,
+    dtr: Option<Spanned<String>>,
+    rts: Option<Spanned<String>>,
+    delimiter: Option<Spanned<String>>,
+    idle_flush_ms: Option<Spanned<u64>>,
+    max_line: Option<Spanned<usize>>,
+    keep_empty: Option<bool>,
+    /// Spec strings or tables, told apart before a table is read so its
+    /// errors are its own.
+    ports: Option<Vec<Spanned<toml::Value>>>,
+    discover: Option<Spanned<RawDiscover>>,
+    capture: Option<PathBuf>,
+    output: Option<PathBuf>,
+    output_format: Option<Spanned<String>>,
+    port_output: Option<PathBuf>,
+    json_output: Option<PathBuf>,
+    rotation: Option<Spanned<RawRotation>>,
+    append: Option<bool>,
+    keep_ansi: Option<bool>,
+    highlight: Option<Vec<Spanned<RawHighlight>>>,
+    filter: Option<Spanned<String>>,
+    exclude: Option<Vec<String>>,
+    terminal_filter: Option<Spanned<String>>,
+    output_filter: Option<Spanned<String>>,
+    json_filter: Option<Spanned<String>>,
+    routes: Option<Vec<Spanned<RawRoute>>>,
+    min_level: Option<Spanned<String>>,
+    level_patterns: Option<Vec<Spanned<RawLevelPattern>>>,
+    ansi: Option<Spanned<String>>,
+    device_ts: Option<Spanned<String>>,
+    clock_drift: Option<bool>,
+    #[serde(default)]
+    profiles: BTreeMap<String, Spanned<RawSettings>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawPortTable {
+    path: Option<String>,
+    usb: Option<String>,
+    serial: Option<String>,
+    manufacturer: Option<String>,
+    product: Option<String>,
+    baud: Option<u32>,
+    alias: Option<String>,
+    line_ending: Option<String>,
+    framing: Option<String>,
+    flow_control: Option<String>,
+    dtr: Option<String>,
+    rts: Option<String>,
+    delimiter: Option<String>,
+    idle_flush_ms: Option<u64>,
+    max_line: Option<usize>,
+    keep_empty: Option<bool>,
+    frame: Option<String>,
+    header: Option<usize>,
+    crc: Option<String>,
+    hex: Option<bool>,
+    defmt: Option<PathBuf>,
+    min_level: Option<String>,
+    ansi: Option<String>,
+    device_ts: Option<String>,
+    restart: Option<bool>,
+}
+
+/// An entry of `highlight`: `TEXT[=STYLE]` or `/REGEX/[i][=STYLE]` as on
+/// the command line, or a table.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum RawHighlight {
+    Spec(String),
+    Rule(RawHighlightRule),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawHighlightRule {
+    regex: Option<String>,
+    text: Option<String>,
+    ignore_case: Option<bool>,
+    fg: Option<String>,
+    bg: Option<String>,
+    style: Option<String>,
+}
+
+/// An entry of `level_patterns`: a regex with a `level` group, or a regex
+/// and the level it implies.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum RawLevelPattern {
+    Regex(String),
+    Rule { regex: String, level: String },
+}
+
+/// `[discover]`: which hotplugged devices to attach to.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawDiscover {
+    regex: Option<String>,
+    glob: Option<String>,
+    usb: Option<String>,
+    serial: Option<String>,
+    manufacturer: Option<String>,
+    product: Option<String>,
+    interval_ms: Option<u64>,
+}
+
+/// An entry of `routes`: an extra output file and the lines it takes.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawRoute {
+    path: PathBuf,
+    filter: Option<String>,
+    format: Option<String>,
+}
+
+/// `[rotation]`: when output files roll over and what is kept.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawRotation {
+    size: Option<String>,
+    every: Option<String>,
+    keep: Option<usize>,
+    compress: Option<String>,
+}
+
+/// The USB selector keys shared by port tables and `[discover]`.
+struct UsbFields<'a> {
+    usb: &'a Option<String>,
+    serial: &'a Option<String>,
+    manufacturer: &'a Option<String>,
+    product: &'a Option<String>,
+}
+
+impl RawPortTable {
+    fn usb_fields(&self) -> UsbFields<'_> {
+        UsbFields {
+            usb: &self.usb,
+            serial: &self.serial,
+            manufacturer: &self.manufacturer,
+            product: &self.product,
+        }
+    }
+}
+
+impl RawDiscover {
+    fn usb_fields(&self) -> UsbFields<'_> {
+        UsbFields {
+            usb: &self.usb,
+            serial: &self.serial,
+            manufacturer: &self.manufacturer,
+            product: &self.product,
+        }
+    }
+}
+
+/// Validated values from one or more config files. `None` means unset, so
+/// the next layer (or the CLI) decides.
+#[derive(Debug, Clone, Default)]
+pub struct FileLayer {
+    pub tui: Option<bool>,
+    pub baud: Option<u32>,
+    pub line_ending: Option<LineEnding>,
+    pub framing: Option<Framing>,
+    pub flow_control: Option<FlowControl>,
+    pub dtr: Option<LinePolicy>,
+    pub rts: Option<LinePolicy>,
+    pub delimiter: Option<Delimiter>,
+    pub idle_flush: Option<Duration>,
+    pub max_line: Option<usize>,
+    pub keep_empty: Option<bool>,
+    pub ports: Option<Vec<PortSpec>>,
+    pub discover: Option<DiscoveryConfig>,
+    pub capture: Option<PathBuf>,
+    pub output: Option<PathBuf>,
+    pub output_format: Option<OutputFormat>,
+    pub port_output: Option<PathBuf>,
+    pub json_output: Option<PathBuf>,
+    pub rotate_size: Option<u64>,
+    pub rotate_every: Option<RotateEvery>,
+    pub rotate_keep: Option<usize>,
+    pub rotate_compress: Option<Compression>,
+    pub append: Option<bool>,
+    pub keep_ansi: Option<bool>,
+    pub highlight: Option<Vec<HighlightRule>>,
+    pub filter: Option<Filter>,
+    pub exclude: Option<Vec<String>>,
+    pub terminal_filter: Option<Filter>,
+    pub output_filter: Option<Filter>,
+    pub json_filter: Option<Filter>,
+    pub routes: Option<Vec<RouteConfig>>,
+    pub min_level: Option<LogLevel>,
+    pub level_patterns: Option<Vec<LevelPattern>>,
+    pub ansi: Option<AnsiPolicy>,
+    pub device_ts: Option<TimestampFormat>,
+    pub clock_drift: Option<bool>,
+}
+
+impl FileLayer {
+    /// Overrides every field that `other` sets.
+    fn overlay(&mut self, other: FileLayer) {
+        set(&mut self.tui, other.tui);
+        set(&mut self.baud, other.baud);
+        set(&mut self.line_ending, other.line_ending);
+        set(&mut self.framing, other.framing);
+        set(&mut self.flow_control, other.flow_control);
+        set(&mut self.dtr, other.dtr);
+        set(&mut self.rts, other.rts);
+        set(&mut self.delimiter, other.delimiter);
+        set(&mut self.idle_flush, other.idle_flush);
+        set(&mut self.max_line, other.max_line);
+        set(&mut self.keep_empty, other.keep_empty);
+        set(&mut self.ports, other.ports);
+        set(&mut self.discover, other.discover);
+        set(&mut self.capture, other.capture);
+        set(&mut self.output, other.output);
+        set(&mut self.output_format, other.output_format);
+        set(&mut self.port_output, other.port_output);
+        set(&mut self.json_output, other.json_output);
+        set(&mut self.rotate_size, other.rotate_size);
+        set(&mut self.rotate_every, other.rotate_every);
+        set(&mut self.rotate_keep, other.rotate_keep);
+        set(&mut self.rotate_compress, other.rotate_compress);
+        set(&mut self.append, other.append);
+        set(&mut self.keep_ansi, other.keep_ansi);
+        set(&mut self.highlight, other.highlight);
+        set(&mut self.filter, other.filter);
+        set(&mut self.exclude, other.exclude);
+        set(&mut self.terminal_filter, other.terminal_filter);
+        set(&mut self.output_filter, other.output_filter);
+        set(&mut self.json_filter, other.json_filter);
+        set(&mut self.routes, other.routes);
+        set(&mut self.min_level, other.min_level);
+        set(&mut self.level_patterns, other.level_patterns);
+        set(&mut self.ansi, other.ansi);
+        set(&mut self.device_ts, other.device_ts);
+        set(&mut self.clock_drift, other.clock_drift);
+    }
+}
+
+fn set<T>(dst: &mut Option<T>, src: Option<T>) {
+    if src.is_some() {
+        *dst = src;
+    }
+}
+
+/// Config files in increasing order of precedence: the user-wide file, then
+/// the project-local one. An explicit `--config` path replaces both.
+pub fn discover(explicit: Option<&Path>) -> AppResult<Vec<PathBuf>> {
+    if let Some(path) = explicit {
+        if !path.is_file() {
+            return Err(AppError::Config(format!(
+                "config file not found: {}",
+                path.display()
+            )));
+        }
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    let user_dir = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".config")));
+
+    let candidates = user_dir
+        .map(|d| d.join("octolog").join(FILE_NAME))
+        .into_iter()
+        .chain(std::iter::once(PathBuf::from(FILE_NAME)));
+
+    Ok(candidates.filter(|p| p.is_file()).collect())
+}
+
+/// Reads `paths` in order and flattens them, applying `profile` last.
+pub fn load(paths: &[PathBuf], profile: Option<&str>) -> AppResult<FileLayer> {
+    let mut merged = FileLayer::default();
+    let mut profiles = BTreeMap::new();
+
+    for path in paths {
+        let src = std::fs::read_to_string(path)
+            .map_err(|e| AppError::Config(format!("{}: {e}", path.display())))?;
+        let ctx = FileContext { path, src: &src };
+
+        let raw: RawSettings =
+            toml::from_str(&src).map_err(|e| AppError::Config(ctx.describe_toml(&e)))?;
+
+        for (name, p) in raw.profiles.iter() {
+            if !p.get_ref().profiles.is_empty() {
+                return Err(ctx.error(p.span().start, "profiles cannot be nested"));
+            }
+            profiles.insert(name.clone(), ctx.layer(p.get_ref())?);
+        }
+
+        merged.overlay(ctx.layer(&raw)?);
+    }
+
+    if let Some(name) = profile {
+        let layer = profiles.remove(name).ok_or_else(|| {
+            let known = profiles.keys().cloned().collect::<Vec<_>>();
+            AppError::Config(if known.is_empty() {
+                format!("unknown profile '{name}' (no profiles defined)")
+            } else {
+                format!("unknown profile '{name}' (available: {})", known.join(", "))
+            })
+        })?;
+        merged.overlay(layer);
+    }
+
+    Ok(merged)
+}
+
+struct FileContext<'a> {
+    path: &'a Path,
+    src: &'a str,
+}
+
+impl FileContext<'_> {
+    fn layer(&self, raw: &RawSettings) -> AppResult<FileLayer> {
+        let ports = raw
+            .ports
+            .as_ref()
+            .map(|ports| {
+                ports
+                    .iter()
+                    .map(|p| self.port(p.get_ref(), p.span().start))
+                    .collect::<AppResult<Vec<_>>>()
+            })
+            .transpose()?;
+
+        let discover = raw
+            .discover
+            .as_ref()
+            .map(|d| self.discover(d.get_ref(), d.span().start))
+            .transpose()?;
+
+        let highlight = raw
+            .highlight
+            .as_ref()
+            .map(|rules| {
+                rules
+                    .iter()
+                    .map(|r| self.highlight(r.get_ref(), r.span().start))
+                    .collect::<AppResult<Vec<_>>>()
+            })
+            .transpose()?;
+
+        let level_patterns = raw
+            .level_patterns
+            .as_ref()
+            .map(|patterns| {
+                patterns
+                    .iter()
+                    .map(|p| self.level_pattern(p.get_ref(), p.span().start))
+                    .collect::<AppResult<Vec<_>>>()
+            })
+            .transpose()?;
+
+        let routes = raw
+            .routes
+            .as_ref()
+            .map(|routes| {
+                routes
+                    .iter()
+                    .map(|r| self.route(r.get_ref(), r.span().start))
+                    .collect::<AppResult<Vec<_>>>()
+            })
+            .transpose()?;
+
+        let rotation = raw.rotation.as_ref().map(|r| (r.get_ref(), r.span().start));
+        let rotate_size = rotation
+            .and_then(|(r, offset)| at(&r.size, offset))
+            .map(|(v, offset)| rotate::parse_size(v).map_err(|e| self.error(offset, &e)))
+            .transpose()?;
+
+        Ok(FileLayer {
+            tui: raw.tui,
+            baud: raw.baud,
+            line_ending: self.parse_opt(spanned(&raw.line_ending))?,
+            framing: self.parse_opt(spanned(&raw.framing))?,
+            flow_control: self.parse_opt(spanned(&raw.flow_control))?,
+            dtr: self.parse_opt(spanned(&raw.dtr))?,
+            rts: self.parse_opt(spanned(&raw.rts))?,
+            delimiter: self.parse_opt(spanned(&raw.delimiter))?,
+            idle_flush: raw
+                .idle_flush_ms
+                .as_ref()
+                .map(|v| self.positive("idle_flush_ms", *v.get_ref(), v.span().start))
+                .transpose()?
+                .map(Duration::from_millis),
+            max_line: raw
+                .max_line
+                .as_ref()
+                .map(|v| self.positive("max_line", *v.get_ref(), v.span().start))
+                .transpose()?,
+            keep_empty: raw.keep_empty,
+            ports,
+            discover,
+            capture: raw.capture.clone(),
+            output: raw.output.clone(),
+            output_format: self.parse_opt(spanned(&raw.output_format))?,
+            port_output: raw.port_output.clone(),
+            json_output: raw.json_output.clone(),
+            rotate_size,
+            rotate_every: self.parse_opt(rotation.and_then(|(r, o)| at(&r.every, o)))?,
+            rotate_keep: rotation.and_then(|(r, _)| r.keep),
+            rotate_compress: self.parse_opt(rotation.and_then(|(r, o)| at(&r.compress, o)))?,
+            append: raw.append,
+            keep_ansi: raw.keep_ansi,
+            highlight,
+            filter: self.filter(spanned(&raw.filter))?,
+            exclude: raw.exclude.clone(),
+            terminal_filter: self.filter(spanned(&raw.terminal_filter))?,
+            output_filter: self.filter(spanned(&raw.output_filter))?,
+            json_filter: self.filter(spanned(&raw.json_filter))?,
+            routes,
+            min_level: self.parse_opt(spanned(&raw.min_level))?,
+            level_patterns,
+            ansi: self.parse_opt(spanned(&raw.ansi))?,
+            device_ts: self.parse_opt(spanned(&raw.device_ts))?,
+            clock_drift: raw.clock_drift,
+        })
+    }
+
+    /// Compiles a filter expression; a blank one means no filter.
+    fn filter(&self, raw: Option<(&str, usize)>) -> AppResult<Option<Filter>> {
+        raw.filter(|(v, _)| !v.trim().is_empty())
+            .map(|(v, offset)| {
+                Filter::parse(v)
+                    .map_err(|e| self.error(offset, &format!("invalid filter '{v}': {e}")))
+            })
+            .transpose()
+    }
+
+    fn route(&self, raw: &RawRoute, offset: usize) -> AppResult<RouteConfig> {
+        Ok(RouteConfig {
+            format: self
+                .parse_opt(at(&raw.format, offset))?
+                .unwrap_or_else(|| OutputFormat::for_path(&raw.path)),
+            path: raw.path.clone(),
+            filter: self.filter(at(&raw.filter, offset))?,
+        })
+    }
+
+    fn highlight(&self, raw: &RawHighlight, offset: usize) -> AppResult<HighlightRule> {
+        let invalid = |e: String| self.error(offset, &e);
+        let t = match raw {
+            RawHighlight::Spec(s) => return s.parse().map_err(invalid),
+            RawHighlight::Rule(t) => t,
+        };
+
+        let mut style = Style::default();
+        if let Some(fg) = &t.fg {
+            style.fg = Some(fg.parse().map_err(invalid)?);
+        }
+        if let Some(bg) = &t.bg {
+            style.bg = Some(bg.parse().map_err(invalid)?);
+        }
+        if let Some(words) = &t.style {
+            style.apply(words).map_err(invalid)?;
+        }
+        let style = (style != Style::default()).then_some(style);
+
+        let pattern = match (&t.regex, &t.text) {
+            (Some(re), None) => re.clone(),
+            (None, Some(text)) if !text.is_empty() => regex::escape(text),
+            _ => return Err(self.error(offset, "highlight: set either regex or text")),
+        };
+        HighlightRule::regex(&pattern, t.ignore_case.unwrap_or(false), style).map_err(invalid)
+    }
+
+    fn level_pattern(&self, raw: &RawLevelPattern, offset: usize) -> AppResult<LevelPattern> {
+        let (regex, level) = match raw {
+            RawLevelPattern::Regex(regex) => (regex, None),
+            RawLevelPattern::Rule { regex, level } => {
+                (regex, self.parse_opt(Some((level.as_str(), offset)))?)
+            }
+        };
+        LevelPattern::new(regex, level).map_err(|e| self.error(offset, &e))
+    }
+
+    fn port(&self, raw: &toml::Value, offset: usize) -> AppResult<PortSpec> {
+        match raw {
+            toml::Value::String(s) => s
+                .parse::<PortSpec>()
+                .map_err(|e| self.error(offset, &format!("invalid port format: {e}"))),
+            toml::Value::Table(_) => {
+                let t = raw
+                    .clone()
+                    .try_into::<RawPortTable>()
+                    .map_err(|e| self.error(offset, &format!("port: {}", e.message())))?;
+                let invalid = |e: PortSpecParseError| {
+                    self.error(offset, &format!("invalid port format: {e}"))
+                };
+
+                let usb = self.usb_match(t.usb_fields(), offset)?;
+                let path = match (&usb, t.path.as_deref().map(str::trim)) {
+                    (Some(_), Some(_)) => {
+                        return Err(self.error(
+                            offset,
+                            "invalid port format: give either a path or USB selectors",
+                        ));
+                    }
+                    (Some(usb), None) => usb.to_string(),
+                    (None, Some(p)) if !p.is_empty() => p.to_string(),
+                    (None, _) => {
+                        return Err(self.error(offset, "invalid port format: missing port path"));
+                    }
+                };
+
+                // `path` may also be a network URL such as tcp://host:port,
+                // a `cmd:`/`file:` source taken whole, colons included, or
+                // stdin.
+                let local = match path.as_str() {
+                    "-" | "stdin" => Some(Ok(Transport::Stdin)),
+                    _ => path
+                        .split_once(':')
+                        .and_then(|(kind, target)| Transport::local(kind, target)),
+                };
+                let (path, mut transport) = if let Some(local) = local {
+                    let transport = local.map_err(invalid)?;
+                    (transport.to_string(), transport)
+                } else if path.contains("://") {
+                    let (transport, rest) = Transport::parse_url(&path).map_err(invalid)?;
+                    if !rest.is_empty() {
+                        return Err(invalid(PortSpecParseError::UnexpectedField {
+                            value: rest.to_string(),
+                        }));
+                    }
+                    (transport.to_string(), transport)
+                } else {
+                    (path, Transport::Serial)
+                };
+
+                if let Some(on) = t.restart {
+                    match &mut transport {
+                        Transport::Command { restart, .. } => *restart = on,
+                        _ => {
+                            return Err(invalid(PortSpecParseError::CommandOnlyOption {
+                                key: "restart".to_string(),
+                            }));
+                        }
+                    }
+                }
+
+                let spec = PortSpec {
+                    path,
+                    transport,
+                    usb,
+                    baud: t.baud,
+                    alias: t.alias.clone(),
+                    line_ending: self.parse_opt(at(&t.line_ending, offset))?,
+                    framing: self.parse_opt(at(&t.framing, offset))?,
+                    flow_control: self.parse_opt(at(&t.flow_control, offset))?,
+                    dtr: self.parse_opt(at(&t.dtr, offset))?,
+                    rts: self.parse_opt(at(&t.rts, offset))?,
+                    delimiter: self.parse_opt(at(&t.delimiter, offset))?,
+                    idle_flush: t
+                        .idle_flush_ms
+                        .map(|v| self.positive("idle_flush_ms", v, offset))
+                        .transpose()?
+                        .map(Duration::from_millis),
+                    max_line: t
+                        .max_line
+                        .map(|v| self.positive("max_line", v, offset))
+                        .transpose()?,
+                    keep_empty: t.keep_empty,
+                    packets: self.parse_opt(at(&t.frame, offset))?,
+                    header: t.header,
+                    crc: self.parse_opt(at(&t.crc, offset))?,
+                    hex: t.hex,
+                    defmt: t.defmt.clone(),
+                    min_level: self.parse_opt(at(&t.min_level, offset))?,
+                    ansi: self.parse_opt(at(&t.ansi, offset))?,
+                    device_ts: self.parse_opt(at(&t.device_ts, offset))?,
+                };
+                spec.validate().map_err(invalid)?;
+                Ok(spec)
+            }
+            other => Err(self.error(
+                offset,
+                &format!(
+                    "port: expected a port spec string or a table, found {}",
+                    other.type_str()
+                ),
+            )),
+        }
+    }
+
+    fn discover(&self, raw: &RawDiscover, offset: usize) -> AppResult<DiscoveryConfig> {
+        let pattern = match (&raw.regex, &raw.glob) {
+            (Some(_), Some(_)) => {
+                return Err(self.error(offset, "discover: set either regex or glob, not both"));
+            }
+            (Some(re), None) => Some(re.clone()),
+            (None, Some(glob)) => Some(glob_to_regex(glob)),
+            (None, None) => None,
+        };
+        let pattern = pattern
+            .map(|p| Regex::new(&p).map_err(|e| self.error(offset, &format!("discover: {e}"))))
+            .transpose()?;
+
+        let mut cfg = DiscoveryConfig {
+            pattern,
+            usb: self.usb_match(raw.usb_fields(), offset)?,
+            ..DiscoveryConfig::default()
+        };
+        if let Some(ms) = raw.interval_ms {
+            cfg.interval = Duration::from_millis(ms.max(100));
+        }
+        Ok(cfg)
+    }
+
+    fn usb_match(&self, t: UsbFields<'_>, offset: usize) -> AppResult<Option<UsbMatch>> {
+        if t.usb.is_none() && t.serial.is_none() && t.manufacturer.is_none() && t.product.is_none()
+        {
+            return Ok(None);
+        }
+
+        let mut usb = UsbMatch {
+            serial: t.serial.clone(),
+            manufacturer: t.manufacturer.clone(),
+            product: t.product.clone(),
+            ..UsbMatch::default()
+        };
+        if let Some(ids) = &t.usb {
+            let invalid =
+                |e: PortSpecParseError| self.error(offset, &format!("invalid port format: {e}"));
+            let (vid, pid) = ids
+                .split_once(':')
+                .ok_or_else(|| invalid(PortSpecParseError::InvalidUsbId { value: ids.clone() }))?;
+            usb.vid = Some(parse_usb_id(vid).map_err(invalid)?);
+            usb.pid = Some(parse_usb_id(pid).map_err(invalid)?);
+        }
+        Ok(Some(usb))
+    }
+
+    /// Parses an optional string setting, reporting failures at `offset`.
+    fn parse_opt<T>(&self, raw: Option<(&str, usize)>) -> AppResult<Option<T>>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        raw.map(|(v, offset)| {
+            v.parse::<T>()
+                .map_err(|e| self.error(offset, &e.to_string()))
+        })
+        .transpose()
+    }
+
+    fn positive<T: PartialEq + Default>(&self, key: &str, v: T, offset: usize) -> AppResult<T> {
+        if v == T::default() {
+            return Err(self.error(offset, &format!("{key} must be greater than 0")));
+        }
+        Ok(v)
+    }
+
+    fn error(&self, offset: usize, msg: &str) -> AppError {
+        AppError::Config(format!(
+            "{}:{}: {msg}",
+            self.path.display(),
+            line_of(self.src, offset)
+        ))
+    }
+
+    fn describe_toml(&self, e: &toml::de::Error) -> String {
+        match e.span() {
+            Some(span) => format!(
+                "{}:{}: {}",
+                self.path.display(),
+                line_of(self.src, span.start),
+                e.message()
+            ),
+            None => format!("{}: {}", self.path.display(), e.message()),
+        }
+    }
+}
+
+/// Translates a shell-style glob (`*`, `?`) into an anchored regex.
+fn glob_to_regex(glob: &str) -> String {
+    let mut re = String::from("^");
+    for c in glob.chars() {
+        match c {
+            '*' => re.push_str("[^/]*"),
+            '?' => re.push_str("[^/]"),
+            c => re.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    re.push('$');
+    re
+}
+
+fn spanned(v: &Option<Spanned<String>>) -> Option<(&str, usize)> {
+    v.as_ref().map(|v| (v.get_ref().as_str(), v.span().start))
+}
+
+fn at(v: &Option<String>, offset: usize) -> Option<(&str, usize)> {
+    v.as_deref().map(|v| (v, offset))
+}
+
+fn line_of(src: &str, offset: usize) -> usize {
+    let offset = offset.min(src.len());
+    src.as_bytes()[..offset]
+        .iter()
+        .filter(|&&b| b == b'\n')
+        .count()
+        + 1
+}
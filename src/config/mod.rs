@@ -1,24 +1,113 @@
+pub mod file;
+
 use crate::{
     cli::CliArgs,
     core::{
-        AppError, AppResult,
-        port_spec::{PortSpec, ResolvedPortSpec},
+        AppError, AppResult, LogLevel, OutputFormat, UsbMatch,
+        port_spec::{
+            DEFAULT_MAX_LINE, LineFraming, PortDefaults, PortSpec, ResolvedPortSpec, Transport,
+        },
     },
+    processing::{Filter, LevelPattern},
+    sinks::{highlight::HighlightRule, rotate::RotationPolicy},
+    sources::{ReplaySpeed, process::stderr_id},
 };
+use regex::Regex;
 use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+const DEFAULT_BAUD: u32 = 115200;
 
 #[derive(Debug, Clone)]
 pub struct Config {
     pub list: bool,
+    pub list_filter: Option<String>,
+    pub list_all: bool,
+    pub list_json: bool,
+    pub tui: bool,
     pub ports: Vec<ResolvedPortSpec>,
-    pub baud: u32,
+    /// The `-p` and config file ports whose settings a replayed capture's
+    /// sources are framed with; only set for a replay.
+    pub replay_ports: Vec<ResolvedPortSpec>,
+    pub port_defaults: PortDefaults,
+    pub discover: Option<DiscoveryConfig>,
+    pub capture: Option<PathBuf>,
+    pub replay: Option<PathBuf>,
+    pub replay_speed: ReplaySpeed,
     pub output: Option<PathBuf>,
-    pub highlight: Vec<String>,
-    pub filter: Option<String>,
-    pub exclude: Vec<String>,
+    pub output_format: OutputFormat,
+    /// Per-port file template; see `PerPortSink`.
+    pub port_output: Option<PathBuf>,
+    pub json_output: Option<PathBuf>,
+    /// Applies to `output`, `port_output` and `json_output`.
+    pub rotation: RotationPolicy,
+    /// Device escape sequences are kept in output files.
+    pub keep_ansi: bool,
+    pub highlight: Vec<HighlightRule>,
+    /// What the terminal (stdout or TUI) shows. Every sink filter already
+    /// includes `--filter` and the `--exclude` substrings; this one also
+    /// holds the minimum levels.
+    pub terminal_filter: Option<Filter>,
+    /// What `output` and `port_output` record.
+    pub output_filter: Option<Filter>,
+    pub json_filter: Option<Filter>,
+    pub routes: Vec<RouteConfig>,
+    /// Tried before the built-in level formats.
+    pub level_patterns: Vec<LevelPattern>,
+    pub clock_drift: bool,
     pub runtime: RuntimeConfig,
 }
 
+/// Which newly plugged devices the hotplug watcher attaches to.
+///
+/// With neither `pattern` nor `usb` set, the usual USB/ACM/COM devices match.
+#[derive(Debug, Clone)]
+pub struct DiscoveryConfig {
+    pub pattern: Option<Regex>,
+    pub usb: Option<UsbMatch>,
+    pub interval: Duration,
+}
+
+impl Default for DiscoveryConfig {
+    fn default() -> Self {
+        Self {
+            pattern: None,
+            usb: None,
+            interval: Duration::from_secs(1),
+        }
+    }
+}
+
+/// An extra output file that records only the lines matching `filter`.
+#[derive(Debug, Clone)]
+pub struct RouteConfig {
+    pub path: PathBuf,
+    pub format: OutputFormat,
+    pub filter: Option<Filter>,
+}
+
+impl FromStr for RouteConfig {
+    type Err = String;
+
+    /// `PATH=FILTER`; `.jsonl` and `.json` files get JSON Lines.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (path, expr) = s
+            .split_once('=')
+            .ok_or_else(|| format!("invalid route '{s}' (expected PATH=FILTER)"))?;
+        let path = PathBuf::from(path.trim());
+        if path.as_os_str().is_empty() {
+            return Err(format!("invalid route '{s}': missing path"));
+        }
+        let filter = parse_filter(expr)?;
+        Ok(Self {
+            format: OutputFormat::for_path(&path),
+            path,
+            filter,
+        })
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct RuntimeConfig {
     pub event_bus_capacity: usize,
@@ -35,14 +124,23 @@ impl Default for RuntimeConfig {
 impl TryFrom<CliArgs> for Config {
     type Error = AppError;
 
+    /// Builds the session config from the CLI, layered over `octolog.toml`.
+    ///
+    /// Precedence, highest first: CLI flags, the selected profile, the
+    /// project-local file, the user-wide file, built-in defaults. A list given
+    /// on the command line replaces the file's list rather than extending it.
     fn try_from(args: CliArgs) -> AppResult<Self> {
-        if !args.list && args.port.is_empty() {
-            return Err(AppError::Config(
-                "no ports specified (use -p/--port or --list)".to_string(),
-            ));
+        let paths = file::discover(args.config.as_deref())?;
+        if args.profile.is_some() && paths.is_empty() {
+            return Err(AppError::Config(format!(
+                "--profile requires a config file ({} or ~/.config/octolog/{})",
+                file::FILE_NAME,
+                file::FILE_NAME
+            )));
         }
+        let file = file::load(&paths, args.profile.as_deref())?;
 
-        let parsed = args
+        let cli_ports = args
             .port
             .iter()
             .map(|raw| {
@@ -51,20 +149,258 @@ impl TryFrom<CliArgs> for Config {
             })
             .collect::<Result<Vec<_>, _>>()?;
 
+        // A replay runs on its own unless live ports are asked for explicitly.
+        let replaying = args.replay.is_some();
+        let file_ports = file.ports.unwrap_or_default();
+
+        let replay_ports = if replaying {
+            cli_ports.iter().chain(&file_ports).cloned().collect()
+        } else {
+            Vec::new()
+        };
+        let parsed = if !cli_ports.is_empty() {
+            cli_ports
+        } else if replaying {
+            Vec::new()
+        } else {
+            file_ports
+        };
+
+        let discover = match file.discover {
+            Some(d) if !replaying || args.auto => Some(d),
+            _ if args.auto => Some(DiscoveryConfig::default()),
+            _ => None,
+        };
+
+        if !args.list && parsed.is_empty() && discover.is_none() && !replaying {
+            return Err(AppError::Config(
+                "no ports specified (use -p/--port, --auto, --replay, a config file or --list)"
+                    .to_string(),
+            ));
+        }
+
+        if parsed
+            .iter()
+            .filter(|p| p.transport == Transport::Stdin)
+            .count()
+            > 1
+        {
+            return Err(AppError::Config(
+                "stdin can only be read by one port".to_string(),
+            ));
+        }
+
+        let port_output = args.port_output.or(file.port_output);
+        if let Some(t) = &port_output {
+            let t = t.to_string_lossy();
+            if !t.contains("{alias}") && !t.contains("{port}") {
+                return Err(AppError::Config(format!(
+                    "port output path '{t}' must contain {{alias}} or {{port}}"
+                )));
+            }
+        }
+
+        let defaults = PortDefaults {
+            baud: args.baud.or(file.baud).unwrap_or(DEFAULT_BAUD),
+            line_ending: args.line_ending.or(file.line_ending).unwrap_or_default(),
+            framing: file.framing.unwrap_or_default(),
+            flow_control: file.flow_control.unwrap_or_default(),
+            dtr: file.dtr.unwrap_or_default(),
+            rts: file.rts.unwrap_or_default(),
+            line_framing: LineFraming {
+                delimiter: file.delimiter.unwrap_or_default(),
+                idle_flush: file.idle_flush,
+                max_line: file.max_line.unwrap_or(DEFAULT_MAX_LINE),
+                keep_empty: file.keep_empty.unwrap_or(false),
+            },
+            min_level: args.min_level.or(file.min_level),
+            ansi: args.ansi.or(file.ansi).unwrap_or_default(),
+            device_ts: args.device_ts.or(file.device_ts),
+        };
+
+        let global = match args.filter.as_deref() {
+            Some(expr) => parse_filter(expr).map_err(AppError::Config)?,
+            None => file.filter,
+        };
+        let global = prefer_cli(args.exclude, file.exclude)
+            .iter()
+            .map(|s| s.trim())
+            .filter(|s| !s.is_empty())
+            .fold(global, |f, ex| both(f, Some(!Filter::text(ex))));
+
+        let sink_filter = |cli: Option<String>, file: Option<Filter>| -> AppResult<_> {
+            let own = match cli {
+                Some(expr) => parse_filter(&expr).map_err(AppError::Config)?,
+                None => file,
+            };
+            Ok(both(global.clone(), own))
+        };
+        let terminal_filter = sink_filter(args.terminal_filter, file.terminal_filter)?;
+        let output_filter = sink_filter(args.output_filter, file.output_filter)?;
+        let json_filter = sink_filter(args.json_filter, file.json_filter)?;
+
+        let routes = if args.route.is_empty() {
+            file.routes.unwrap_or_default()
+        } else {
+            args.route
+                .iter()
+                .map(|r| r.parse::<RouteConfig>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(AppError::Config)?
+        };
+        let routes = routes
+            .into_iter()
+            .map(|r| RouteConfig {
+                filter: both(global.clone(), r.filter),
+                ..r
+            })
+            .collect();
+
+        let highlight = if args.highlight.is_empty() {
+            file.highlight.unwrap_or_default()
+        } else {
+            args.highlight
+                .iter()
+                .map(|h| h.parse::<HighlightRule>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(AppError::Config)?
+        };
+
+        let level_patterns = if args.level_pattern.is_empty() {
+            file.level_patterns.unwrap_or_default()
+        } else {
+            args.level_pattern
+                .iter()
+                .map(|p| LevelPattern::new(p, None))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(AppError::Config)?
+        };
+
         let ports = parsed
             .into_iter()
-            .map(|p| p.resolve(args.baud))
+            .map(|p| p.resolve(&defaults))
+            .collect::<Vec<_>>();
+        let replay_ports = replay_ports
+            .into_iter()
+            .map(|p| p.resolve(&defaults))
             .collect::<Vec<_>>();
+        let levels = level_filter(defaults.min_level, ports.iter().chain(&replay_ports));
 
         Ok(Self {
             list: args.list,
+            list_filter: args.list_filter,
+            list_all: args.all,
+            list_json: args.json,
+            tui: switch(args.tui, args.no_tui).or(file.tui).unwrap_or(false),
             ports,
-            baud: args.baud,
-            output: args.output,
-            highlight: args.highlight,
-            filter: args.filter,
-            exclude: args.exclude,
+            replay_ports,
+            port_defaults: defaults,
+            discover,
+            capture: args.capture.or(file.capture),
+            replay: args.replay,
+            replay_speed: args.replay_speed.unwrap_or_default(),
+            output: args.output.or(file.output),
+            output_format: args
+                .output_format
+                .or(file.output_format)
+                .unwrap_or_default(),
+            port_output,
+            json_output: args.json_output.or(file.json_output),
+            rotation: RotationPolicy {
+                max_bytes: args.rotate_size.or(file.rotate_size),
+                every: args.rotate_every.or(file.rotate_every),
+                keep: args.rotate_keep.or(file.rotate_keep),
+                compress: args
+                    .rotate_compress
+                    .or(file.rotate_compress)
+                    .unwrap_or_default(),
+                append: switch(args.append, args.no_append)
+                    .or(file.append)
+                    .unwrap_or(false),
+            },
+            keep_ansi: switch(args.keep_ansi, args.no_keep_ansi)
+                .or(file.keep_ansi)
+                .unwrap_or(false),
+            highlight,
+            terminal_filter: both(terminal_filter, levels),
+            output_filter,
+            json_filter,
+            routes,
+            level_patterns,
+            clock_drift: switch(args.clock_drift, args.no_clock_drift)
+                .or(file.clock_drift)
+                .unwrap_or(false),
             runtime: RuntimeConfig::default(),
         })
     }
 }
+
+fn prefer_cli(cli: Vec<String>, file: Option<Vec<String>>) -> Vec<String> {
+    if cli.is_empty() {
+        file.unwrap_or_default()
+    } else {
+        cli
+    }
+}
+
+/// A `--flag`/`--no-flag` pair; `None` when neither was given.
+fn switch(on: bool, off: bool) -> Option<bool> {
+    match (on, off) {
+        (true, _) => Some(true),
+        (_, true) => Some(false),
+        _ => None,
+    }
+}
+
+/// Compiles a filter expression; a blank one means no filter.
+fn parse_filter(expr: &str) -> Result<Option<Filter>, String> {
+    let expr = expr.trim();
+    if expr.is_empty() {
+        return Ok(None);
+    }
+    Filter::parse(expr)
+        .map(Some)
+        .map_err(|e| format!("invalid filter '{expr}': {e}"))
+}
+
+/// `--min-level` and the ports' `min_level` as a filter: each port at its
+/// own minimum, ports that were not configured at the default.
+fn level_filter<'a>(
+    default: Option<LogLevel>,
+    ports: impl Iterator<Item = &'a ResolvedPortSpec>,
+) -> Option<Filter> {
+    let mut filter = None;
+    let mut own: Option<Filter> = None;
+    for port in ports.filter(|p| p.min_level != default) {
+        let id = port.source_id();
+        let mut names = vec![id.label()];
+        // A command's stderr follows the port's settings.
+        if let Transport::Command { .. } = port.transport {
+            names.push(stderr_id(&id).label());
+        }
+        for name in names {
+            let source = Filter::source(&name);
+            if let Some(min) = port.min_level {
+                let at_min = (!source.clone()).or(Filter::min_level(min));
+                filter = both(filter, Some(at_min));
+            }
+            own = Some(match own {
+                Some(own) => own.or(source),
+                None => source,
+            });
+        }
+    }
+    let others = default.map(|min| match own {
+        Some(own) => own.or(Filter::min_level(min)),
+        None => Filter::min_level(min),
+    });
+    both(filter, others)
+}
+
+/// Lines must pass both filters.
+fn both(a: Option<Filter>, b: Option<Filter>) -> Option<Filter> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.and(b)),
+        (a, b) => a.or(b),
+    }
+}
@@ -16,6 +16,9 @@ pub enum AppError {
     #[error("runtime error: {0}")]
     Runtime(String),
 
+    #[error("command error: {0}")]
+    Command(String),
+
     #[error("scan error: {0}")]
     Scan(String),
 }
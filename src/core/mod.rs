@@ -3,5 +3,11 @@ pub mod port_spec;
 pub mod types;
 
 pub use error::{AppError, AppResult};
-pub use port_spec::{PortSpec, PortSpecParseError, ResolvedPortSpec};
-pub use types::{AppEvent, LogLevel, SourceId};
+pub use port_spec::{
+    Crc, Delimiter, FlowControl, Framing, LineFraming, LinePolicy, PacketFraming, PacketKind,
+    Parity, PortDefaults, PortSpec, PortSpecParseError, ResolvedPortSpec, Transport, UsbMatch,
+};
+pub use types::{
+    AnsiPolicy, AppEvent, LineEnding, LogLevel, OutputFormat, PortState, SourceId, TimeUnit,
+    TimestampFormat,
+};
@@ -1,17 +1,230 @@
-use std::{fmt, str::FromStr};
+use crate::core::{AnsiPolicy, LineEnding, LogLevel, SourceId, TimestampFormat};
+use std::{fmt, path::PathBuf, str::FromStr, time::Duration};
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct PortSpec {
     pub path: String,
+    pub transport: Transport,
+    pub usb: Option<UsbMatch>,
     pub baud: Option<u32>,
     pub alias: Option<String>,
+    pub line_ending: Option<LineEnding>,
+    pub framing: Option<Framing>,
+    pub flow_control: Option<FlowControl>,
+    pub dtr: Option<LinePolicy>,
+    pub rts: Option<LinePolicy>,
+    pub delimiter: Option<Delimiter>,
+    pub idle_flush: Option<Duration>,
+    pub max_line: Option<usize>,
+    pub keep_empty: Option<bool>,
+    pub packets: Option<PacketKind>,
+    pub header: Option<usize>,
+    pub crc: Option<Crc>,
+    pub hex: Option<bool>,
+    pub defmt: Option<PathBuf>,
+    pub min_level: Option<LogLevel>,
+    pub ansi: Option<AnsiPolicy>,
+    pub device_ts: Option<TimestampFormat>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ResolvedPortSpec {
+    /// Device path, the selector text when `usb` is set, or the URL of a
+    /// network source.
     pub path: String,
+    pub transport: Transport,
+    pub usb: Option<UsbMatch>,
     pub baud: u32,
     pub alias: Option<String>,
+    pub line_ending: LineEnding,
+    pub framing: Framing,
+    pub flow_control: FlowControl,
+    pub dtr: LinePolicy,
+    pub rts: LinePolicy,
+    pub line_framing: LineFraming,
+    /// Binary framing; replaces line framing when set.
+    pub packet_framing: Option<PacketFraming>,
+    /// Show the raw byte stream as hex dumps instead of lines.
+    pub hex: bool,
+    /// Decode the stream as defmt using this firmware ELF.
+    pub defmt: Option<PathBuf>,
+    /// Lines of a known level below this one are hidden on the terminal.
+    pub min_level: Option<LogLevel>,
+    pub ansi: AnsiPolicy,
+    /// Where the device prints its own timestamp.
+    pub device_ts: Option<TimestampFormat>,
+}
+
+/// Where a port's bytes come from.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Transport {
+    #[default]
+    Serial,
+    /// `tcp://host:port`: connect, and reconnect whenever the link drops.
+    TcpClient { addr: String },
+    /// `tcp-listen://[host:]port`: serve one client at a time.
+    TcpListen { addr: String },
+    /// `udp://[host:]port`: bind and read datagrams.
+    Udp { addr: String },
+    /// `unix:///path`: connect to a Unix-domain stream socket.
+    Unix { path: String },
+    /// `rfc2217://host:port`: a remote serial port behind an RFC 2217
+    /// (Telnet COM-PORT) server; baudrate, framing and control lines apply.
+    Rfc2217 { addr: String },
+    /// `cmd:COMMAND`: run a command; stdout and stderr become two streams
+    /// and commands typed for the port go to its stdin.
+    Command { command: String, restart: bool },
+    /// `file:PATH`: follow a file like `tail -F`.
+    File { path: String },
+    /// `-` or `stdin`: lines piped into Octolog.
+    Stdin,
+}
+
+/// Session-wide settings applied to ports that do not override them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PortDefaults {
+    pub baud: u32,
+    pub line_ending: LineEnding,
+    pub framing: Framing,
+    pub flow_control: FlowControl,
+    pub dtr: LinePolicy,
+    pub rts: LinePolicy,
+    pub line_framing: LineFraming,
+    pub min_level: Option<LogLevel>,
+    pub ansi: AnsiPolicy,
+    pub device_ts: Option<TimestampFormat>,
+}
+
+/// How a port's byte stream is cut into lines.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineFraming {
+    pub delimiter: Delimiter,
+    /// Emit a partial line once no data has arrived for this long.
+    pub idle_flush: Option<Duration>,
+    /// Longer lines are split into pieces of at most this many bytes.
+    pub max_line: usize,
+    pub keep_empty: bool,
+}
+
+impl Default for LineFraming {
+    fn default() -> Self {
+        Self {
+            delimiter: Delimiter::Any,
+            idle_flush: None,
+            max_line: DEFAULT_MAX_LINE,
+            keep_empty: false,
+        }
+    }
+}
+
+pub const DEFAULT_MAX_LINE: usize = 64 * 1024;
+
+/// What ends a line.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Delimiter {
+    /// Any CR or LF; CRLF counts as one terminator.
+    #[default]
+    Any,
+    Lf,
+    CrLf,
+    Cr,
+    /// A custom byte or byte string, e.g. NUL or `\x1e`.
+    Bytes(Vec<u8>),
+}
+
+/// How binary frames are delimited on the wire.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PacketKind {
+    /// Consistent Overhead Byte Stuffing, frames end in 0x00.
+    Cobs,
+    /// RFC 1055 SLIP, frames end in 0xC0.
+    Slip,
+    /// A fixed-size header that starts with the length of the rest of the
+    /// frame, `width` bytes wide.
+    Length { width: u8, big_endian: bool },
+}
+
+/// Checksum appended to each frame, covering everything before it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Crc {
+    #[default]
+    None,
+    /// CRC-8/SMBUS (poly 0x07).
+    Crc8,
+    /// CRC-16/CCITT-FALSE, big-endian.
+    Crc16,
+    /// CRC-16/MODBUS, little-endian.
+    Crc16Modbus,
+    /// CRC-32 (IEEE), little-endian.
+    Crc32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PacketFraming {
+    pub kind: PacketKind,
+    /// Header size for `PacketKind::Length`, at least the length field.
+    pub header: usize,
+    pub crc: Crc,
+}
+
+/// Selects a port by USB descriptors instead of a device path, so the
+/// same board is found whatever order devices were plugged in.
+///
+/// Text fields are compared case-insensitively; `manufacturer` and `product`
+/// match on substrings, `serial` must match exactly.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UsbMatch {
+    pub vid: Option<u16>,
+    pub pid: Option<u16>,
+    pub serial: Option<String>,
+    pub manufacturer: Option<String>,
+    pub product: Option<String>,
+}
+
+/// Directory of stable symlinks that `by-id:NAME` expands into.
+pub const BY_ID_DIR: &str = "/dev/serial/by-id";
+
+/// Character framing, written the usual way (`8N1`, `7E1`, ...).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Framing {
+    pub data_bits: u8,
+    pub parity: Parity,
+    pub stop_bits: u8,
+}
+
+impl Default for Framing {
+    fn default() -> Self {
+        Self {
+            data_bits: 8,
+            parity: Parity::None,
+            stop_bits: 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Parity {
+    None,
+    Odd,
+    Even,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FlowControl {
+    #[default]
+    None,
+    Software,
+    Hardware,
+}
+
+/// What to do with a modem control line (DTR or RTS) when the port opens.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LinePolicy {
+    #[default]
+    On,
+    Off,
+    /// Leave the line as the driver set it.
+    Keep,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -19,6 +232,18 @@ pub enum PortSpecParseError {
     EmptySpec,
     MissingPath,
     InvalidBaud { value: String },
+    InvalidUsbId { value: String },
+    InvalidFraming { value: String },
+    InvalidOption { key: String, value: String },
+    UnknownOption { key: String },
+    UnexpectedField { value: String },
+    UnterminatedQuote { value: String },
+    UnknownScheme { scheme: String },
+    InvalidAddress { value: String },
+    SerialOnlyOption { key: String },
+    CommandOnlyOption { key: String },
+    OptionRequires { key: String, requires: String },
+    ConflictingOptions { key: String, other: String },
 }
 
 impl fmt::Display for PortSpecParseError {
@@ -27,6 +252,38 @@ impl fmt::Display for PortSpecParseError {
             Self::EmptySpec => write!(f, "empty port spec"),
             Self::MissingPath => write!(f, "missing port path"),
             Self::InvalidBaud { value } => write!(f, "invalid baudrate '{}'", value),
+            Self::InvalidUsbId { value } => {
+                write!(f, "invalid USB id '{}' (expected 4 hex digits)", value)
+            }
+            Self::InvalidFraming { value } => {
+                write!(f, "invalid framing '{}' (expected e.g. 8N1, 7E1)", value)
+            }
+            Self::InvalidOption { key, value } => {
+                write!(f, "invalid value '{}' for '{}'", value, key)
+            }
+            Self::UnknownOption { key } => write!(f, "unknown port option '{}'", key),
+            Self::UnexpectedField { value } => write!(f, "unexpected field '{}'", value),
+            Self::UnterminatedQuote { value } => write!(f, "unterminated quote in '{}'", value),
+            Self::UnknownScheme { scheme } => write!(
+                f,
+                "unknown scheme '{}://' (expected tcp, tcp-listen, udp, unix or rfc2217)",
+                scheme
+            ),
+            Self::InvalidAddress { value } => {
+                write!(f, "invalid address '{}' (expected host:port)", value)
+            }
+            Self::SerialOnlyOption { key } => {
+                write!(f, "'{}' only applies to serial ports", key)
+            }
+            Self::CommandOnlyOption { key } => {
+                write!(f, "'{}' only applies to cmd: sources", key)
+            }
+            Self::OptionRequires { key, requires } => {
+                write!(f, "'{}' requires {}", key, requires)
+            }
+            Self::ConflictingOptions { key, other } => {
+                write!(f, "'{}' cannot be combined with '{}'", key, other)
+            }
         }
     }
 }
@@ -34,15 +291,597 @@ impl fmt::Display for PortSpecParseError {
 impl std::error::Error for PortSpecParseError {}
 
 impl PortSpec {
-    pub fn resolve(self, fallback_baud: u32) -> ResolvedPortSpec {
+    pub fn resolve(self, defaults: &PortDefaults) -> ResolvedPortSpec {
         ResolvedPortSpec {
             path: self.path,
-            baud: self.baud.unwrap_or(fallback_baud),
+            transport: self.transport,
+            usb: self.usb,
+            baud: self.baud.unwrap_or(defaults.baud),
             alias: self.alias,
+            line_ending: self.line_ending.unwrap_or(defaults.line_ending),
+            framing: self.framing.unwrap_or(defaults.framing),
+            flow_control: self.flow_control.unwrap_or(defaults.flow_control),
+            dtr: self.dtr.unwrap_or(defaults.dtr),
+            rts: self.rts.unwrap_or(defaults.rts),
+            line_framing: LineFraming {
+                delimiter: self
+                    .delimiter
+                    .unwrap_or_else(|| defaults.line_framing.delimiter.clone()),
+                idle_flush: self.idle_flush.or(defaults.line_framing.idle_flush),
+                max_line: self.max_line.unwrap_or(defaults.line_framing.max_line),
+                keep_empty: self.keep_empty.unwrap_or(defaults.line_framing.keep_empty),
+            },
+            packet_framing: self.packets.map(|kind| PacketFraming {
+                kind,
+                header: match kind {
+                    PacketKind::Length { width, .. } => self.header.unwrap_or(width as usize),
+                    _ => 0,
+                },
+                crc: self.crc.unwrap_or_default(),
+            }),
+            hex: self.hex.unwrap_or(false),
+            defmt: self.defmt,
+            min_level: self.min_level.or(defaults.min_level),
+            ansi: self.ansi.unwrap_or(defaults.ansi),
+            device_ts: self.device_ts.or_else(|| defaults.device_ts.clone()),
+        }
+    }
+
+    /// Rejects options that do not apply to the port.
+    pub fn validate(&self) -> Result<(), PortSpecParseError> {
+        let requires = |key: &str, requires: &str| {
+            Err(PortSpecParseError::OptionRequires {
+                key: key.to_string(),
+                requires: requires.to_string(),
+            })
+        };
+        match (self.packets, self.header) {
+            (Some(PacketKind::Length { width, .. }), Some(header)) if header < width as usize => {
+                return requires(
+                    "header",
+                    &format!("at least {width} bytes for the length field"),
+                );
+            }
+            (Some(PacketKind::Length { .. }), _) | (_, None) => {}
+            (_, Some(_)) => return requires("header", "frame=len8/len16le/..."),
+        }
+        if self.crc.is_some() && self.packets.is_none() {
+            return requires("crc", "frame=cobs, slip or len...");
+        }
+        let conflict = |key: &str, other: &str| {
+            Err(PortSpecParseError::ConflictingOptions {
+                key: key.to_string(),
+                other: other.to_string(),
+            })
+        };
+        if self.hex == Some(true) && self.packets.is_some() {
+            return conflict("hex", "frame");
+        }
+        if self.defmt.is_some() && self.packets.is_some() {
+            return conflict("defmt", "frame");
+        }
+        if self.defmt.is_some() && self.hex == Some(true) {
+            return conflict("defmt", "hex");
+        }
+
+        if self.transport == Transport::Serial {
+            return Ok(());
+        }
+
+        let line_settings = !self.transport.has_line_settings();
+        let serial_only = [
+            ("baud", line_settings && self.baud.is_some()),
+            ("framing", line_settings && self.framing.is_some()),
+            ("flow", line_settings && self.flow_control.is_some()),
+            ("dtr", line_settings && self.dtr.is_some()),
+            ("rts", line_settings && self.rts.is_some()),
+            ("usb", self.usb.is_some()),
+        ];
+        match serial_only.iter().find(|(_, set)| *set) {
+            Some((key, _)) => Err(PortSpecParseError::SerialOnlyOption {
+                key: key.to_string(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Applies one `key=value` option from the port spec.
+    pub fn apply_option(&mut self, key: &str, value: &str) -> Result<(), PortSpecParseError> {
+        let invalid = || PortSpecParseError::InvalidOption {
+            key: key.to_string(),
+            value: value.to_string(),
+        };
+
+        match key {
+            "eol" => self.line_ending = Some(value.parse().map_err(|_| invalid())?),
+            "flow" => self.flow_control = Some(value.parse()?),
+            "dtr" => self.dtr = Some(value.parse().map_err(|_| invalid())?),
+            "rts" => self.rts = Some(value.parse().map_err(|_| invalid())?),
+            "serial" | "manufacturer" | "product" if value.is_empty() => return Err(invalid()),
+            "serial" => self.usb.get_or_insert_default().serial = Some(value.to_string()),
+            "manufacturer" => {
+                self.usb.get_or_insert_default().manufacturer = Some(value.to_string())
+            }
+            "product" => self.usb.get_or_insert_default().product = Some(value.to_string()),
+            "delim" => self.delimiter = Some(value.parse().map_err(|_| invalid())?),
+            "idle" => self.idle_flush = Some(parse_duration(value).map_err(|_| invalid())?),
+            "max_line" => match value.parse::<usize>() {
+                Ok(n) if n > 0 => self.max_line = Some(n),
+                _ => return Err(invalid()),
+            },
+            "keep_empty" => self.keep_empty = Some(parse_switch(value).ok_or_else(invalid)?),
+            "frame" => self.packets = Some(value.parse().map_err(|_| invalid())?),
+            "header" => self.header = Some(value.parse().map_err(|_| invalid())?),
+            "crc" => self.crc = Some(value.parse().map_err(|_| invalid())?),
+            "defmt" if value.is_empty() => return Err(invalid()),
+            "defmt" => self.defmt = Some(PathBuf::from(value)),
+            "min_level" => self.min_level = Some(value.parse().map_err(|_| invalid())?),
+            "ansi" => self.ansi = Some(value.parse().map_err(|_| invalid())?),
+            "device_ts" => self.device_ts = Some(value.parse().map_err(|_| invalid())?),
+            "restart" => match &mut self.transport {
+                Transport::Command { restart, .. } => {
+                    *restart = parse_switch(value).ok_or_else(invalid)?;
+                }
+                _ => {
+                    return Err(PortSpecParseError::CommandOnlyOption {
+                        key: key.to_string(),
+                    });
+                }
+            },
+            _ => {
+                return Err(PortSpecParseError::UnknownOption {
+                    key: key.to_string(),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Transport {
+    /// Parses the `scheme://address` head of a network port spec, returning
+    /// the transport and the fields that follow the address.
+    pub fn parse_url(s: &str) -> Result<(Self, &str), PortSpecParseError> {
+        let (scheme, rest) =
+            s.split_once("://")
+                .ok_or_else(|| PortSpecParseError::InvalidAddress {
+                    value: s.to_string(),
+                })?;
+
+        let transport = match scheme.to_ascii_lowercase().as_str() {
+            "unix" => {
+                let (path, rest) = rest.split_once(':').unwrap_or((rest, ""));
+                if path.is_empty() {
+                    return Err(PortSpecParseError::MissingPath);
+                }
+                return Ok((
+                    Self::Unix {
+                        path: path.to_string(),
+                    },
+                    rest,
+                ));
+            }
+            "tcp" => |addr| Self::TcpClient { addr },
+            "tcp-listen" => |addr| Self::TcpListen { addr },
+            "udp" => |addr| Self::Udp { addr },
+            "rfc2217" => |addr| Self::Rfc2217 { addr },
+            _ => {
+                return Err(PortSpecParseError::UnknownScheme {
+                    scheme: scheme.to_string(),
+                });
+            }
+        };
+
+        // Listeners may leave the host out and bind every interface.
+        let host_required =
+            scheme.eq_ignore_ascii_case("tcp") || scheme.eq_ignore_ascii_case("rfc2217");
+        let (addr, rest) = split_host_port(rest, host_required)?;
+        Ok((transport(addr), rest))
+    }
+
+    /// Parses the `cmd:COMMAND` or `file:PATH` head of a port spec, returning
+    /// the transport and the fields that follow; `None` for other targets.
+    ///
+    /// The command or path runs to the end of the spec, colons included,
+    /// unless fields follow after `::`, or it is quoted: `cmd:'a:b':alias`.
+    pub fn parse_local(s: &str) -> Option<Result<(Self, &str), PortSpecParseError>> {
+        // `-[:alias]` / `stdin[:alias]` have no target.
+        let (kind, rest) = s.split_once(':').unwrap_or((s, ""));
+        if kind == "-" || kind == "stdin" {
+            return Some(Ok((Self::Stdin, rest)));
+        }
+        if kind != "cmd" && kind != "file" {
+            return None;
+        }
+
+        let (target, rest) = match split_local_target(rest) {
+            Ok(split) => split,
+            Err(e) => return Some(Err(e)),
+        };
+        Self::local(kind, target).map(|r| r.map(|t| (t, rest)))
+    }
+
+    /// Builds a `cmd:` or `file:` source from its kind and target; `None`
+    /// for any other kind.
+    pub fn local(kind: &str, target: &str) -> Option<Result<Self, PortSpecParseError>> {
+        let target = target.trim();
+        let transport = match kind {
+            "cmd" => Self::Command {
+                command: target.to_string(),
+                restart: false,
+            },
+            "file" => Self::File {
+                path: target.to_string(),
+            },
+            _ => return None,
+        };
+        if target.is_empty() {
+            return Some(Err(PortSpecParseError::MissingPath));
+        }
+        Some(Ok(transport))
+    }
+
+    /// Whether baudrate, framing, flow control and DTR/RTS can be set.
+    pub fn has_line_settings(&self) -> bool {
+        matches!(self, Self::Serial | Self::Rfc2217 { .. })
+    }
+}
+
+/// Splits the target of a `cmd:` or `file:` source from the fields after it,
+/// see `Transport::parse_local`.
+fn split_local_target(s: &str) -> Result<(&str, &str), PortSpecParseError> {
+    let trimmed = s.trim_start();
+    let Some(quote) = trimmed.chars().next().filter(|c| matches!(c, '\'' | '"')) else {
+        return Ok(s.rsplit_once("::").unwrap_or((s, "")));
+    };
+
+    let body = &trimmed[1..];
+    let end = body
+        .find(quote)
+        .ok_or_else(|| PortSpecParseError::UnterminatedQuote {
+            value: trimmed.to_string(),
+        })?;
+    let after = body[end + 1..].trim_start();
+    let rest = match after.strip_prefix("::").or_else(|| after.strip_prefix(':')) {
+        Some(rest) => rest,
+        None if after.is_empty() => "",
+        None => {
+            return Err(PortSpecParseError::UnexpectedField {
+                value: after.to_string(),
+            });
+        }
+    };
+    Ok((&body[..end], rest))
+}
+
+/// Splits `host:port[:rest]` (or `[v6]:port[:rest]`, or a bare `port`) into
+/// a socket address string and the remaining fields.
+fn split_host_port(s: &str, host_required: bool) -> Result<(String, &str), PortSpecParseError> {
+    let invalid = || PortSpecParseError::InvalidAddress {
+        value: s.to_string(),
+    };
+
+    let (host, after_host) = match s.strip_prefix('[') {
+        Some(v6) => {
+            let (host, after) = v6.split_once(']').ok_or_else(invalid)?;
+            (
+                format!("[{host}]"),
+                after.strip_prefix(':').ok_or_else(invalid)?,
+            )
+        }
+        None => {
+            let (first, after) = s.split_once(':').unwrap_or((s, ""));
+            if first.parse::<u16>().is_ok() && !host_required {
+                ("0.0.0.0".to_string(), s)
+            } else {
+                (first.to_string(), after)
+            }
+        }
+    };
+
+    let (port, rest) = after_host.split_once(':').unwrap_or((after_host, ""));
+    let port = port.trim().parse::<u16>().map_err(|_| invalid())?;
+    if host.is_empty() {
+        return Err(invalid());
+    }
+    Ok((format!("{host}:{port}"), rest))
+}
+
+impl fmt::Display for Transport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Serial => f.write_str("serial"),
+            Self::TcpClient { addr } => write!(f, "tcp://{addr}"),
+            Self::TcpListen { addr } => write!(f, "tcp-listen://{addr}"),
+            Self::Udp { addr } => write!(f, "udp://{addr}"),
+            Self::Unix { path } => write!(f, "unix://{path}"),
+            Self::Rfc2217 { addr } => write!(f, "rfc2217://{addr}"),
+            Self::Command { command, .. } => write!(f, "cmd:{command}"),
+            Self::File { path } => write!(f, "file:{path}"),
+            Self::Stdin => f.write_str("stdin"),
+        }
+    }
+}
+
+impl ResolvedPortSpec {
+    pub fn source_id(&self) -> SourceId {
+        SourceId {
+            port: self.path.clone(),
+            alias: self.alias.clone(),
+        }
+    }
+}
+
+impl UsbMatch {
+    pub fn is_match(
+        &self,
+        vid: u16,
+        pid: u16,
+        serial: Option<&str>,
+        manufacturer: Option<&str>,
+        product: Option<&str>,
+    ) -> bool {
+        let contains = |have: Option<&str>, want: &str| {
+            have.is_some_and(|h| h.to_lowercase().contains(&want.to_lowercase()))
+        };
+
+        self.vid.is_none_or(|v| v == vid)
+            && self.pid.is_none_or(|p| p == pid)
+            && self
+                .serial
+                .as_deref()
+                .is_none_or(|s| serial.is_some_and(|have| have.eq_ignore_ascii_case(s)))
+            && self
+                .manufacturer
+                .as_deref()
+                .is_none_or(|m| contains(manufacturer, m))
+            && self.product.as_deref().is_none_or(|p| contains(product, p))
+    }
+}
+
+impl fmt::Display for UsbMatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if let (Some(vid), Some(pid)) = (self.vid, self.pid) {
+            parts.push(format!("usb:{vid:04x}:{pid:04x}"));
+        }
+        if let Some(s) = &self.serial {
+            parts.push(format!("serial={s}"));
+        }
+        if let Some(m) = &self.manufacturer {
+            parts.push(format!("manufacturer={m}"));
+        }
+        if let Some(p) = &self.product {
+            parts.push(format!("product={p}"));
+        }
+        f.write_str(&parts.join(":"))
+    }
+}
+
+/// `on`/`off` (or `true`/`false`, `1`/`0`).
+fn parse_switch(s: &str) -> Option<bool> {
+    match s.trim().to_ascii_lowercase().as_str() {
+        "on" | "true" | "1" => Some(true),
+        "off" | "false" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+/// Parses a duration such as `250ms`, `2s` or `1.5s`; a bare number is in
+/// milliseconds.
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let t = s.trim().to_ascii_lowercase();
+    let invalid = || format!("invalid duration '{s}' (expected e.g. 250ms or 2s)");
+    let (num, scale) = if let Some(ms) = t.strip_suffix("ms") {
+        (ms, 0.001)
+    } else if let Some(secs) = t.strip_suffix('s') {
+        (secs, 1.0)
+    } else {
+        (t.as_str(), 0.001)
+    };
+    match num.trim().parse::<f64>() {
+        Ok(n) if n.is_finite() && n > 0.0 => Ok(Duration::from_secs_f64(n * scale)),
+        _ => Err(invalid()),
+    }
+}
+
+/// Parses a 4-digit hexadecimal USB vendor or product id.
+pub fn parse_usb_id(s: &str) -> Result<u16, PortSpecParseError> {
+    let t = s.trim();
+    let t = t.strip_prefix("0x").unwrap_or(t);
+    if t.is_empty() || t.len() > 4 {
+        return Err(PortSpecParseError::InvalidUsbId {
+            value: s.to_string(),
+        });
+    }
+    u16::from_str_radix(t, 16).map_err(|_| PortSpecParseError::InvalidUsbId {
+        value: s.to_string(),
+    })
+}
+
+impl LinePolicy {
+    /// Level to drive the line to, or `None` to leave it untouched.
+    pub fn level(self) -> Option<bool> {
+        match self {
+            Self::On => Some(true),
+            Self::Off => Some(false),
+            Self::Keep => None,
+        }
+    }
+}
+
+impl fmt::Display for Framing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parity = match self.parity {
+            Parity::None => 'N',
+            Parity::Odd => 'O',
+            Parity::Even => 'E',
+        };
+        write!(f, "{}{}{}", self.data_bits, parity, self.stop_bits)
+    }
+}
+
+impl FromStr for Framing {
+    type Err = PortSpecParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || PortSpecParseError::InvalidFraming {
+            value: s.to_string(),
+        };
+
+        let &[d, p, st] = s.trim().as_bytes() else {
+            return Err(invalid());
+        };
+
+        let data_bits = match d {
+            b'5'..=b'8' => d - b'0',
+            _ => return Err(invalid()),
+        };
+        let parity = match p.to_ascii_uppercase() {
+            b'N' => Parity::None,
+            b'O' => Parity::Odd,
+            b'E' => Parity::Even,
+            _ => return Err(invalid()),
+        };
+        let stop_bits = match st {
+            b'1' | b'2' => st - b'0',
+            _ => return Err(invalid()),
+        };
+
+        Ok(Self {
+            data_bits,
+            parity,
+            stop_bits,
+        })
+    }
+}
+
+impl FromStr for FlowControl {
+    type Err = PortSpecParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "none" | "off" => Ok(Self::None),
+            "sw" | "software" | "xonxoff" => Ok(Self::Software),
+            "hw" | "hardware" | "rtscts" => Ok(Self::Hardware),
+            _ => Err(PortSpecParseError::InvalidOption {
+                key: "flow".to_string(),
+                value: s.to_string(),
+            }),
         }
     }
 }
 
+impl FromStr for LinePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "on" | "true" | "1" => Ok(Self::On),
+            "off" | "false" | "0" => Ok(Self::Off),
+            "keep" => Ok(Self::Keep),
+            other => Err(format!(
+                "invalid line policy '{other}' (expected on, off or keep)"
+            )),
+        }
+    }
+}
+
+impl FromStr for PacketKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let length = |width, big_endian| Ok(Self::Length { width, big_endian });
+        match s.trim().to_ascii_lowercase().as_str() {
+            "cobs" => Ok(Self::Cobs),
+            "slip" => Ok(Self::Slip),
+            "len8" => length(1, false),
+            "len16le" => length(2, false),
+            "len16be" => length(2, true),
+            "len32le" => length(4, false),
+            "len32be" => length(4, true),
+            other => Err(format!(
+                "invalid frame format '{other}' (expected cobs, slip, len8, len16le, len16be, len32le or len32be)"
+            )),
+        }
+    }
+}
+
+impl FromStr for Crc {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "none" => Ok(Self::None),
+            "crc8" => Ok(Self::Crc8),
+            "crc16" => Ok(Self::Crc16),
+            "crc16-modbus" => Ok(Self::Crc16Modbus),
+            "crc32" => Ok(Self::Crc32),
+            other => Err(format!(
+                "invalid crc '{other}' (expected none, crc8, crc16, crc16-modbus or crc32)"
+            )),
+        }
+    }
+}
+
+impl FromStr for Delimiter {
+    type Err = String;
+
+    /// `any`, `lf`, `crlf`, `cr`, a byte such as `0x00`, or a string with
+    /// `\n`, `\r`, `\t`, `\0` and `\xNN` escapes.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "invalid delimiter '{s}' (expected any, lf, crlf, cr, a byte like 0x00 or a string)"
+            )
+        };
+        match s.to_ascii_lowercase().as_str() {
+            "any" => return Ok(Self::Any),
+            "lf" => return Ok(Self::Lf),
+            "crlf" => return Ok(Self::CrLf),
+            "cr" => return Ok(Self::Cr),
+            _ => {}
+        }
+        if let Some(hex) = s.strip_prefix("0x").filter(|h| h.len() == 2) {
+            return hex_byte(hex)
+                .map(|b| Self::Bytes(vec![b]))
+                .ok_or_else(invalid);
+        }
+
+        let mut bytes = Vec::new();
+        let mut chars = s.chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                let mut utf8 = [0u8; 4];
+                bytes.extend_from_slice(c.encode_utf8(&mut utf8).as_bytes());
+                continue;
+            }
+            match chars.next() {
+                Some('n') => bytes.push(b'\n'),
+                Some('r') => bytes.push(b'\r'),
+                Some('t') => bytes.push(b'\t'),
+                Some('0') => bytes.push(0),
+                Some('\\') => bytes.push(b'\\'),
+                Some('x') => {
+                    let hex: String = chars.by_ref().take(2).collect();
+                    bytes.push(hex_byte(&hex).ok_or_else(invalid)?);
+                }
+                _ => return Err(invalid()),
+            }
+        }
+        if bytes.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Self::Bytes(bytes))
+    }
+}
+
+/// Exactly two hex digits.
+fn hex_byte(s: &str) -> Option<u8> {
+    let digits = s.len() == 2 && s.bytes().all(|b| b.is_ascii_hexdigit());
+    digits.then(|| u8::from_str_radix(s, 16).ok()).flatten()
+}
+
 impl FromStr for PortSpec {
     type Err = PortSpecParseError;
 
@@ -52,44 +891,587 @@ impl FromStr for PortSpec {
             return Err(PortSpecParseError::EmptySpec);
         }
 
-        let mut parts = raw.splitn(3, ':');
-        let path = parts.next().unwrap_or("").trim();
-        if path.is_empty() {
-            return Err(PortSpecParseError::MissingPath);
-        }
+        let mut spec = Self::default();
 
-        let p2 = parts.next().map(str::trim).filter(|v| !v.is_empty());
-        let p3 = parts.next().map(str::trim).filter(|v| !v.is_empty());
+        // `cmd:COMMAND[::alias][:key=value...]`, `file:PATH[::alias]` and
+        // `stdin[:alias]` first: a command may well contain `://`.
+        let parts = if let Some(local) = Transport::parse_local(raw) {
+            let (transport, rest) = local?;
+            spec.path = transport.to_string();
+            spec.transport = transport;
+            rest.split(':').map(str::trim)
+        } else if raw.contains("://") {
+            // Network sources: `scheme://address[:alias][:key=value...]`.
+            let (transport, rest) = Transport::parse_url(raw)?;
+            spec.path = transport.to_string();
+            spec.transport = transport;
+            rest.split(':').map(str::trim)
+        } else {
+            let mut parts = raw.split(':').map(str::trim);
+            let first = parts.next().unwrap_or("");
+            if first.is_empty() {
+                return Err(PortSpecParseError::MissingPath);
+            }
 
-        // Accepted forms:
-        // - path
-        // - path:baud
-        // - path:alias
-        // - path:baud:alias
-        let (baud, alias) = match (p2, p3) {
-            (None, None) => (None, None),
-            (Some(x), None) => {
-                if let Ok(b) = x.parse::<u32>() {
-                    (Some(b), None)
-                } else {
-                    (None, Some(x.to_string()))
+            // The target comes first: a device path, `usb:VID:PID`,
+            // `by-id:NAME`, or straight away a `serial=`/`manufacturer=`/
+            // `product=` selector.
+            match first {
+                "usb" => {
+                    let vid = parts.next().ok_or(PortSpecParseError::MissingPath)?;
+                    let pid = parts.next().ok_or(PortSpecParseError::MissingPath)?;
+                    let usb = spec.usb.get_or_insert_default();
+                    usb.vid = Some(parse_usb_id(vid)?);
+                    usb.pid = Some(parse_usb_id(pid)?);
                 }
+                "by-id" => {
+                    let name = parts
+                        .next()
+                        .filter(|n| !n.is_empty())
+                        .ok_or(PortSpecParseError::MissingPath)?;
+                    spec.path = format!("{BY_ID_DIR}/{name}");
+                }
+                _ => match first.split_once('=') {
+                    Some((key, value)) => spec.apply_option(key.trim(), value.trim())?,
+                    None => spec.path = first.to_string(),
+                },
             }
-            (Some(x), Some(y)) => {
+            parts
+        };
+
+        // Fields after the path are told apart by shape:
+        // - `key=value` is an option (eol, flow, dtr, rts)
+        // - a framing token such as `8N1` or `7E1`
+        // - `hex` for the raw byte view
+        // - otherwise positional: [baud][:alias], or just [alias] for a
+        //   network source other than rfc2217
+        let mut positional = Vec::new();
+        for part in parts.filter(|v| !v.is_empty()) {
+            if let Some((key, value)) = part.split_once('=') {
+                spec.apply_option(key.trim(), value.trim())?;
+            } else if looks_like_framing(part) {
+                spec.framing = Some(part.parse()?);
+            } else if part.eq_ignore_ascii_case("hex") {
+                spec.hex = Some(true);
+            } else {
+                positional.push(part);
+            }
+        }
+
+        if let Some(usb) = &spec.usb
+            && spec.transport == Transport::Serial
+        {
+            if !spec.path.is_empty() {
+                return Err(PortSpecParseError::UnexpectedField {
+                    value: usb.to_string(),
+                });
+            }
+            spec.path = usb.to_string();
+        }
+
+        match positional.as_slice() {
+            [] => {}
+            [x] if !spec.transport.has_line_settings() => spec.alias = Some(x.to_string()),
+            [_, extra, ..] if !spec.transport.has_line_settings() => {
+                return Err(PortSpecParseError::UnexpectedField {
+                    value: extra.to_string(),
+                });
+            }
+            [x] => match x.parse::<u32>() {
+                Ok(b) => spec.baud = Some(b),
+                Err(_) => spec.alias = Some(x.to_string()),
+            },
+            [x, y] => {
                 let b = x
                     .parse::<u32>()
                     .map_err(|_| PortSpecParseError::InvalidBaud {
                         value: x.to_string(),
                     })?;
-                (Some(b), Some(y.to_string()))
+                spec.baud = Some(b);
+                spec.alias = Some(y.to_string());
             }
-            (None, Some(_)) => unreachable!(),
-        };
+            [_, _, extra, ..] => {
+                return Err(PortSpecParseError::UnexpectedField {
+                    value: extra.to_string(),
+                });
+            }
+        }
 
-        Ok(Self {
+        spec.validate()?;
+        Ok(spec)
+    }
+}
+
+/// Digit, letter, digit: parsed as framing so `9N1` is an error rather than
+/// silently becoming an alias.
+fn looks_like_framing(s: &str) -> bool {
+    matches!(
+        s.as_bytes(),
+        [d, p, st] if d.is_ascii_digit() && p.is_ascii_alphabetic() && st.is_ascii_digit()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(path: &str) -> PortSpec {
+        PortSpec {
             path: path.to_string(),
-            baud: baud,
-            alias: alias,
-        })
+            ..PortSpec::default()
+        }
+    }
+
+    fn check_ok(cases: Vec<(&str, PortSpec)>) {
+        for (input, want) in cases {
+            assert_eq!(input.parse::<PortSpec>(), Ok(want), "{input}");
+        }
+    }
+
+    fn check_err(cases: Vec<(&str, PortSpecParseError)>) {
+        for (input, want) in cases {
+            assert_eq!(input.parse::<PortSpec>(), Err(want), "{input}");
+        }
+    }
+
+    fn invalid(key: &str, value: &str) -> PortSpecParseError {
+        PortSpecParseError::InvalidOption {
+            key: key.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    fn requires(key: &str, requires: &str) -> PortSpecParseError {
+        PortSpecParseError::OptionRequires {
+            key: key.to_string(),
+            requires: requires.to_string(),
+        }
+    }
+
+    fn conflict(key: &str, other: &str) -> PortSpecParseError {
+        PortSpecParseError::ConflictingOptions {
+            key: key.to_string(),
+            other: other.to_string(),
+        }
+    }
+
+    #[test]
+    fn serial_ports() {
+        check_ok(vec![
+            ("/dev/ttyUSB0", spec("/dev/ttyUSB0")),
+            (
+                "/dev/ttyUSB0:GPS",
+                PortSpec {
+                    alias: Some("GPS".into()),
+                    ..spec("/dev/ttyUSB0")
+                },
+            ),
+            (
+                "COM3:9600",
+                PortSpec {
+                    baud: Some(9600),
+                    ..spec("COM3")
+                },
+            ),
+            (
+                " /dev/ttyUSB0 : 9600 : 7e2 : GPS ",
+                PortSpec {
+                    baud: Some(9600),
+                    alias: Some("GPS".into()),
+                    framing: Some(Framing {
+                        data_bits: 7,
+                        parity: Parity::Even,
+                        stop_bits: 2,
+                    }),
+                    ..spec("/dev/ttyUSB0")
+                },
+            ),
+            (
+                "/dev/ttyACM0:eol=crlf:flow=hw:dtr=off:rts=keep",
+                PortSpec {
+                    line_ending: Some(LineEnding::CrLf),
+                    flow_control: Some(FlowControl::Hardware),
+                    dtr: Some(LinePolicy::Off),
+                    rts: Some(LinePolicy::Keep),
+                    ..spec("/dev/ttyACM0")
+                },
+            ),
+            (
+                "/dev/ttyACM0:HEX:idle=50ms:max_line=16",
+                PortSpec {
+                    hex: Some(true),
+                    idle_flush: Some(Duration::from_millis(50)),
+                    max_line: Some(16),
+                    ..spec("/dev/ttyACM0")
+                },
+            ),
+            (
+                "/dev/ttyACM0:delim=0x00:keep_empty=on",
+                PortSpec {
+                    delimiter: Some(Delimiter::Bytes(vec![0])),
+                    keep_empty: Some(true),
+                    ..spec("/dev/ttyACM0")
+                },
+            ),
+            (
+                "/dev/ttyACM0:frame=len16be:header=4:crc=crc16-modbus",
+                PortSpec {
+                    packets: Some(PacketKind::Length {
+                        width: 2,
+                        big_endian: true,
+                    }),
+                    header: Some(4),
+                    crc: Some(Crc::Crc16Modbus),
+                    ..spec("/dev/ttyACM0")
+                },
+            ),
+            (
+                "/dev/ttyACM0:defmt=fw.elf:min_level=warn:ansi=strip:device_ts=zephyr",
+                PortSpec {
+                    defmt: Some(PathBuf::from("fw.elf")),
+                    min_level: Some(LogLevel::Warn),
+                    ansi: Some(AnsiPolicy::Strip),
+                    device_ts: Some(TimestampFormat::Zephyr),
+                    ..spec("/dev/ttyACM0")
+                },
+            ),
+        ]);
+    }
+
+    #[test]
+    fn serial_port_errors() {
+        check_err(vec![
+            ("  ", PortSpecParseError::EmptySpec),
+            (":9600", PortSpecParseError::MissingPath),
+            (
+                "/dev/ttyUSB0:fast:GPS",
+                PortSpecParseError::InvalidBaud {
+                    value: "fast".into(),
+                },
+            ),
+            (
+                "/dev/ttyUSB0:9600:GPS:extra",
+                PortSpecParseError::UnexpectedField {
+                    value: "extra".into(),
+                },
+            ),
+            (
+                "/dev/ttyUSB0:9N1",
+                PortSpecParseError::InvalidFraming {
+                    value: "9N1".into(),
+                },
+            ),
+            (
+                "/dev/ttyUSB0:speed=fast",
+                PortSpecParseError::UnknownOption {
+                    key: "speed".into(),
+                },
+            ),
+            ("/dev/ttyUSB0:eol=lfcr", invalid("eol", "lfcr")),
+            ("/dev/ttyUSB0:flow=maybe", invalid("flow", "maybe")),
+            ("/dev/ttyUSB0:max_line=0", invalid("max_line", "0")),
+            ("/dev/ttyUSB0:idle=soon", invalid("idle", "soon")),
+            ("/dev/ttyUSB0:defmt=", invalid("defmt", "")),
+            (
+                "/dev/ttyUSB0:restart=on",
+                PortSpecParseError::CommandOnlyOption {
+                    key: "restart".into(),
+                },
+            ),
+        ]);
+    }
+
+    #[test]
+    fn conflicting_options() {
+        check_err(vec![
+            (
+                "/dev/ttyUSB0:header=4",
+                requires("header", "frame=len8/len16le/..."),
+            ),
+            (
+                "/dev/ttyUSB0:frame=cobs:header=4",
+                requires("header", "frame=len8/len16le/..."),
+            ),
+            (
+                "/dev/ttyUSB0:frame=len16le:header=1",
+                requires("header", "at least 2 bytes for the length field"),
+            ),
+            (
+                "/dev/ttyUSB0:crc=crc8",
+                requires("crc", "frame=cobs, slip or len..."),
+            ),
+            ("/dev/ttyUSB0:hex:frame=slip", conflict("hex", "frame")),
+            (
+                "/dev/ttyUSB0:defmt=fw.elf:frame=cobs",
+                conflict("defmt", "frame"),
+            ),
+            ("/dev/ttyUSB0:defmt=fw.elf:hex", conflict("defmt", "hex")),
+        ]);
+    }
+
+    #[test]
+    fn usb_selectors() {
+        let usb = |vid, pid, serial: Option<&str>| UsbMatch {
+            vid,
+            pid,
+            serial: serial.map(str::to_string),
+            ..UsbMatch::default()
+        };
+        check_ok(vec![
+            (
+                "usb:2e8a:000a:GPS",
+                PortSpec {
+                    usb: Some(usb(Some(0x2E8A), Some(0x000A), None)),
+                    alias: Some("GPS".into()),
+                    ..spec("usb:2e8a:000a")
+                },
+            ),
+            (
+                "usb:0x2E8A:A:serial=E661:115200",
+                PortSpec {
+                    usb: Some(usb(Some(0x2E8A), Some(0x000A), Some("E661"))),
+                    baud: Some(115200),
+                    ..spec("usb:2e8a:000a:serial=E661")
+                },
+            ),
+            (
+                "serial=E661:GPS",
+                PortSpec {
+                    usb: Some(usb(None, None, Some("E661"))),
+                    alias: Some("GPS".into()),
+                    ..spec("serial=E661")
+                },
+            ),
+            (
+                "product=Pico:manufacturer=Raspberry",
+                PortSpec {
+                    usb: Some(UsbMatch {
+                        manufacturer: Some("Raspberry".into()),
+                        product: Some("Pico".into()),
+                        ..UsbMatch::default()
+                    }),
+                    ..spec("manufacturer=Raspberry:product=Pico")
+                },
+            ),
+            (
+                "by-id:usb-FTDI_FT232R-if00-port0:GPS",
+                PortSpec {
+                    alias: Some("GPS".into()),
+                    ..spec("/dev/serial/by-id/usb-FTDI_FT232R-if00-port0")
+                },
+            ),
+        ]);
+    }
+
+    #[test]
+    fn usb_selector_errors() {
+        let usb_id = |value: &str| PortSpecParseError::InvalidUsbId {
+            value: value.into(),
+        };
+        check_err(vec![
+            ("usb:2e8a", PortSpecParseError::MissingPath),
+            ("usb:xyz:000a", usb_id("xyz")),
+            ("usb:2e8a:12345", usb_id("12345")),
+            ("by-id", PortSpecParseError::MissingPath),
+            ("by-id::GPS", PortSpecParseError::MissingPath),
+            ("serial=", invalid("serial", "")),
+            (
+                "/dev/ttyUSB0:serial=E661",
+                PortSpecParseError::UnexpectedField {
+                    value: "serial=E661".into(),
+                },
+            ),
+            (
+                "tcp://host:4000:product=Pico",
+                PortSpecParseError::SerialOnlyOption { key: "usb".into() },
+            ),
+        ]);
+    }
+
+    #[test]
+    fn network_sources() {
+        let net = |transport: Transport| PortSpec {
+            path: transport.to_string(),
+            transport,
+            ..PortSpec::default()
+        };
+        check_ok(vec![
+            (
+                "tcp://10.0.0.5:4000:Bridge",
+                PortSpec {
+                    alias: Some("Bridge".into()),
+                    ..net(Transport::TcpClient {
+                        addr: "10.0.0.5:4000".into(),
+                    })
+                },
+            ),
+            (
+                "tcp-listen://4000:delim=lf",
+                PortSpec {
+                    delimiter: Some(Delimiter::Lf),
+                    ..net(Transport::TcpListen {
+                        addr: "0.0.0.0:4000".into(),
+                    })
+                },
+            ),
+            (
+                "udp://[::1]:5000:U",
+                PortSpec {
+                    alias: Some("U".into()),
+                    ..net(Transport::Udp {
+                        addr: "[::1]:5000".into(),
+                    })
+                },
+            ),
+            (
+                "unix:///tmp/app.sock:App",
+                PortSpec {
+                    alias: Some("App".into()),
+                    ..net(Transport::Unix {
+                        path: "/tmp/app.sock".into(),
+                    })
+                },
+            ),
+            (
+                "rfc2217://moxa:4001:9600:8N1:Rem",
+                PortSpec {
+                    baud: Some(9600),
+                    framing: Some(Framing::default()),
+                    alias: Some("Rem".into()),
+                    ..net(Transport::Rfc2217 {
+                        addr: "moxa:4001".into(),
+                    })
+                },
+            ),
+        ]);
+    }
+
+    #[test]
+    fn local_sources() {
+        let command = |command: &str, restart| PortSpec {
+            path: format!("cmd:{command}"),
+            transport: Transport::Command {
+                command: command.to_string(),
+                restart,
+            },
+            ..PortSpec::default()
+        };
+        let file = |path: &str| PortSpec {
+            path: format!("file:{path}"),
+            transport: Transport::File {
+                path: path.to_string(),
+            },
+            ..PortSpec::default()
+        };
+        let stdin = PortSpec {
+            path: "stdin".into(),
+            transport: Transport::Stdin,
+            ..PortSpec::default()
+        };
+        check_ok(vec![
+            (
+                "cmd:curl http://host/log",
+                command("curl http://host/log", false),
+            ),
+            (
+                "cmd:ssh lab-1 journalctl -f::Lab:restart=on:eol=crlf",
+                PortSpec {
+                    alias: Some("Lab".into()),
+                    line_ending: Some(LineEnding::CrLf),
+                    ..command("ssh lab-1 journalctl -f", true)
+                },
+            ),
+            (
+                "cmd:'nc a:1':Web",
+                PortSpec {
+                    alias: Some("Web".into()),
+                    ..command("nc a:1", false)
+                },
+            ),
+            ("file:/var/log/syslog", file("/var/log/syslog")),
+            (
+                r#"file:"C:/logs/a.log"::Log:hex"#,
+                PortSpec {
+                    alias: Some("Log".into()),
+                    hex: Some(true),
+                    ..file("C:/logs/a.log")
+                },
+            ),
+            ("stdin", stdin.clone()),
+            (
+                "-:In",
+                PortSpec {
+                    alias: Some("In".into()),
+                    ..stdin
+                },
+            ),
+        ]);
+    }
+
+    #[test]
+    fn source_errors() {
+        let address = |value: &str| PortSpecParseError::InvalidAddress {
+            value: value.into(),
+        };
+        let serial_only = |key: &str| PortSpecParseError::SerialOnlyOption { key: key.into() };
+        check_err(vec![
+            (
+                "ftp://host:21",
+                PortSpecParseError::UnknownScheme {
+                    scheme: "ftp".into(),
+                },
+            ),
+            ("tcp://host", address("host")),
+            ("tcp://4000", address("4000")),
+            ("udp://[::1:5000", address("[::1:5000")),
+            ("unix://", PortSpecParseError::MissingPath),
+            ("tcp://host:4000:flow=hw", serial_only("flow")),
+            ("tcp://host:4000:8N1", serial_only("framing")),
+            ("file:/x.log::Log:dtr=off", serial_only("dtr")),
+            (
+                "tcp://host:4000:A:B",
+                PortSpecParseError::UnexpectedField { value: "B".into() },
+            ),
+            ("cmd:", PortSpecParseError::MissingPath),
+            ("file:  ::Log", PortSpecParseError::MissingPath),
+            (
+                "cmd:'nc a:1:Web",
+                PortSpecParseError::UnterminatedQuote {
+                    value: "'nc a:1:Web".into(),
+                },
+            ),
+            (
+                "cmd:'nc' a",
+                PortSpecParseError::UnexpectedField { value: "a".into() },
+            ),
+            (
+                "file:/x.log::Log:restart=on",
+                PortSpecParseError::CommandOnlyOption {
+                    key: "restart".into(),
+                },
+            ),
+        ]);
+    }
+
+    #[test]
+    fn delimiters() {
+        let bytes = |b: &[u8]| Ok(Delimiter::Bytes(b.to_vec()));
+        let cases: [(&str, Result<Delimiter, ()>); 12] = [
+            ("any", Ok(Delimiter::Any)),
+            ("CRLF", Ok(Delimiter::CrLf)),
+            ("0x1e", bytes(&[0x1E])),
+            ("0x1", bytes(b"0x1")),
+            ("END", bytes(b"END")),
+            (r"\r\n\0", bytes(b"\r\n\0")),
+            (r"\x1e", bytes(&[0x1E])),
+            (r"\x1eX", bytes(&[0x1E, b'X'])),
+            (r"\x1", Err(())),
+            (r"\x+1", Err(())),
+            ("0x+1", Err(())),
+            (r"\q", Err(())),
+        ];
+        for (input, want) in cases {
+            assert_eq!(input.parse::<Delimiter>().map_err(|_| ()), want, "{input}");
+        }
     }
 }
@@ -1,4 +1,7 @@
+use crate::processing::filter::regex_reason;
 use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
 use std::time::SystemTime;
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -22,7 +25,8 @@ impl fmt::Display for SourceId {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Ordered from least to most severe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum LogLevel {
     Trace,
     Debug,
@@ -31,15 +35,251 @@ pub enum LogLevel {
     Error,
 }
 
+impl LogLevel {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Trace => "trace",
+            Self::Debug => "debug",
+            Self::Info => "info",
+            Self::Warn => "warn",
+            Self::Error => "error",
+        }
+    }
+}
+
+impl FromStr for LogLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "trace" => Ok(Self::Trace),
+            "debug" => Ok(Self::Debug),
+            "info" => Ok(Self::Info),
+            "warn" | "warning" => Ok(Self::Warn),
+            "error" | "err" => Ok(Self::Error),
+            other => Err(format!(
+                "invalid level '{other}' (expected trace, debug, info, warn or error)"
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PortState {
+    Connected,
+    Disconnected,
+}
+
+/// Terminator appended to commands written to a port.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineEnding {
+    Cr,
+    #[default]
+    Lf,
+    CrLf,
+}
+
+impl LineEnding {
+    pub fn as_bytes(self) -> &'static [u8] {
+        match self {
+            Self::Cr => b"\r",
+            Self::Lf => b"\n",
+            Self::CrLf => b"\r\n",
+        }
+    }
+}
+
+impl FromStr for LineEnding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "cr" => Ok(Self::Cr),
+            "lf" => Ok(Self::Lf),
+            "crlf" => Ok(Self::CrLf),
+            other => Err(format!(
+                "invalid line ending '{other}' (expected cr, lf or crlf)"
+            )),
+        }
+    }
+}
+
+/// What to do with ANSI escape sequences a device puts in its lines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AnsiPolicy {
+    /// Leave the line untouched.
+    Pass,
+    /// Remove every escape sequence.
+    Strip,
+    /// Keep colors, drop other sequences (cursor movement, titles, ...) and
+    /// reset the colors at the end of the line.
+    #[default]
+    Sanitize,
+}
+
+impl FromStr for AnsiPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "pass" => Ok(Self::Pass),
+            "strip" => Ok(Self::Strip),
+            "sanitize" => Ok(Self::Sanitize),
+            other => Err(format!(
+                "invalid ANSI policy '{other}' (expected pass, strip or sanitize)"
+            )),
+        }
+    }
+}
+
+/// Where a device prints its own timestamp, usually the time since boot.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TimestampFormat {
+    /// Zephyr: `[00:00:01.234,567]`.
+    Zephyr,
+    /// ESP-IDF: `I (1234) tag: ...`, in milliseconds.
+    EspIdf,
+    /// Linux kernel: `[    1.234567]`, in seconds.
+    Kernel,
+    /// A regex whose `ts` group, or first group, holds a number of `unit`s.
+    Pattern { regex: String, unit: TimeUnit },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeUnit {
+    Seconds,
+    Millis,
+    Micros,
+    Nanos,
+}
+
+impl TimeUnit {
+    pub fn as_secs(self) -> f64 {
+        match self {
+            Self::Seconds => 1.0,
+            Self::Millis => 1e-3,
+            Self::Micros => 1e-6,
+            Self::Nanos => 1e-9,
+        }
+    }
+}
+
+impl FromStr for TimeUnit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "s" => Ok(Self::Seconds),
+            "ms" => Ok(Self::Millis),
+            "us" => Ok(Self::Micros),
+            "ns" => Ok(Self::Nanos),
+            other => Err(format!(
+                "invalid time unit '{other}' (expected s, ms, us or ns)"
+            )),
+        }
+    }
+}
+
+impl FromStr for TimestampFormat {
+    type Err = String;
+
+    /// `zephyr`, `esp-idf`, `kernel` or `/REGEX/UNIT`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some(body) = s.strip_prefix('/') {
+            let (regex, unit) = body
+                .rsplit_once('/')
+                .ok_or_else(|| format!("invalid timestamp format '{s}' (expected /REGEX/UNIT)"))?;
+            let unit = unit.parse()?;
+            let re = regex::Regex::new(regex)
+                .map_err(|e| format!("invalid timestamp regex '{regex}': {}", regex_reason(&e)))?;
+            if re.captures_len() < 2 {
+                return Err(format!(
+                    "timestamp regex '{regex}' needs a group holding the time"
+                ));
+            }
+            return Ok(Self::Pattern {
+                regex: regex.to_string(),
+                unit,
+            });
+        }
+        match s.to_ascii_lowercase().as_str() {
+            "zephyr" => Ok(Self::Zephyr),
+            "esp-idf" | "espidf" => Ok(Self::EspIdf),
+            "kernel" => Ok(Self::Kernel),
+            other => Err(format!(
+                "invalid timestamp format '{other}' (expected zephyr, esp-idf, kernel or /REGEX/UNIT)"
+            )),
+        }
+    }
+}
+
+/// Encoding used for the `--output` file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    /// The same rendering as the terminal, without colors.
+    #[default]
+    Text,
+    /// One JSON object per event.
+    Jsonl,
+}
+
+impl OutputFormat {
+    /// JSON Lines for `.jsonl` and `.json` files, text otherwise.
+    pub fn for_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("jsonl") || ext.eq_ignore_ascii_case("json") => {
+                Self::Jsonl
+            }
+            _ => Self::Text,
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "text" => Ok(Self::Text),
+            "jsonl" | "json" => Ok(Self::Jsonl),
+            other => Err(format!(
+                "invalid output format '{other}' (expected text or jsonl)"
+            )),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum AppEvent {
     LogLine {
         source: SourceId,
         ts: SystemTime,
+        /// Known when the source itself carries levels, e.g. defmt.
+        level: Option<LogLevel>,
         raw: String,
     },
+    /// A decoded binary frame, see `PacketFraming`, or a chunk of a port's
+    /// raw byte stream in hex view. `offset` is the chunk's position in the
+    /// stream, and 0 for frames.
+    Frame {
+        source: SourceId,
+        ts: SystemTime,
+        offset: u64,
+        bytes: Vec<u8>,
+    },
     System {
         level: LogLevel,
         message: String,
     },
+    PortState {
+        source: SourceId,
+        state: PortState,
+    },
+    /// A command that was written to a port, echoed back locally.
+    Echo {
+        source: SourceId,
+        ts: SystemTime,
+        text: String,
+    },
 }
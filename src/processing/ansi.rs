@@ -0,0 +1,135 @@
+use crate::core::AnsiPolicy;
+use std::borrow::Cow;
+
+const ESC: u8 = 0x1b;
+const RESET: &str = "\x1b[0m";
+
+/// A run of bytes that is not visible text.
+enum Hidden {
+    /// `ESC [ ... m`: colors and text attributes.
+    Sgr,
+    /// Any other escape sequence, or a control character other than tab.
+    Other,
+}
+
+/// Applies `policy` to a device line.
+pub fn apply(policy: AnsiPolicy, raw: String) -> String {
+    match policy {
+        AnsiPolicy::Pass => raw,
+        AnsiPolicy::Strip => match strip(&raw) {
+            Cow::Borrowed(_) => raw,
+            Cow::Owned(s) => s,
+        },
+        AnsiPolicy::Sanitize => match sanitize(&raw) {
+            Cow::Borrowed(_) => raw,
+            Cow::Owned(s) => s,
+        },
+    }
+}
+
+/// The text of `raw` without escape sequences and control characters.
+pub fn strip(raw: &str) -> Cow<'_, str> {
+    if !has_hidden(raw) {
+        return Cow::Borrowed(raw);
+    }
+    Cow::Owned(strip_mapped(raw).0)
+}
+
+/// Keeps the colors of `raw` and drops everything else `strip` removes,
+/// resetting the colors at the end so they cannot leak past the line.
+pub fn sanitize(raw: &str) -> Cow<'_, str> {
+    if !has_hidden(raw) {
+        return Cow::Borrowed(raw);
+    }
+    let mut out = String::with_capacity(raw.len() + RESET.len());
+    let mut colored = false;
+    let mut text = 0;
+    scan(raw, |start, end, hidden| {
+        out.push_str(&raw[text..start]);
+        if let Hidden::Sgr = hidden {
+            out.push_str(&raw[start..end]);
+            colored = true;
+        }
+        text = end;
+    });
+    out.push_str(&raw[text..]);
+    if colored {
+        out.push_str(RESET);
+    }
+    Cow::Owned(out)
+}
+
+/// The text of `raw` as `strip` returns it, and for each of its bytes the
+/// position of that byte in `raw`.
+pub(crate) fn strip_mapped(raw: &str) -> (String, Vec<usize>) {
+    let mut visible = String::with_capacity(raw.len());
+    let mut at = Vec::with_capacity(raw.len());
+    let mut text = 0;
+    let mut keep = |visible: &mut String, from: usize, to: usize| {
+        visible.push_str(&raw[from..to]);
+        at.extend(from..to);
+    };
+    scan(raw, |start, end, _| {
+        keep(&mut visible, text, start);
+        text = end;
+    });
+    keep(&mut visible, text, raw.len());
+    (visible, at)
+}
+
+fn has_hidden(raw: &str) -> bool {
+    raw.bytes().any(is_control)
+}
+
+fn is_control(b: u8) -> bool {
+    (b < 0x20 && b != b'\t') || b == 0x7f
+}
+
+/// Calls `hidden` with the byte range of each escape sequence and control
+/// character in `raw`, in order.
+fn scan(raw: &str, mut hidden: impl FnMut(usize, usize, Hidden)) {
+    let bytes = raw.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        let b = bytes[i];
+        if b != ESC {
+            if is_control(b) {
+                hidden(i, i + 1, Hidden::Other);
+            }
+            i += 1;
+            continue;
+        }
+
+        let start = i;
+        i += 1;
+        let kind = match bytes.get(i) {
+            // CSI: parameters and intermediates, then a final byte.
+            Some(b'[') => {
+                i += 1;
+                while i < bytes.len() && !(0x40..=0x7e).contains(&bytes[i]) {
+                    i += 1;
+                }
+                let sgr = bytes.get(i) == Some(&b'm')
+                    && bytes[start + 2..i]
+                        .iter()
+                        .all(|b| b.is_ascii_digit() || matches!(b, b';' | b':'));
+                i = (i + 1).min(bytes.len());
+                if sgr { Hidden::Sgr } else { Hidden::Other }
+            }
+            // OSC: up to BEL or ST (ESC \).
+            Some(b']') => {
+                while i < bytes.len() && bytes[i] != 0x07 && bytes[i] != ESC {
+                    i += 1;
+                }
+                i = (i + if bytes.get(i) == Some(&ESC) { 2 } else { 1 }).min(bytes.len());
+                Hidden::Other
+            }
+            Some(_) => {
+                i += raw[i..].chars().next().map_or(1, char::len_utf8);
+                Hidden::Other
+            }
+            None => Hidden::Other,
+        };
+        hidden(start, i, kind);
+    }
+}
@@ -0,0 +1,750 @@
+use crate::core::{LogLevel, SourceId};
+use crate::processing::{ProcessedEvent, ansi};
+
+use regex::{Regex, RegexBuilder};
+use std::borrow::Cow;
+use std::fmt;
+use std::iter::Peekable;
+use std::vec;
+
+/// A compiled filter expression over events.
+///
+/// ```text
+/// expr  := and ( ("||" | "or") and )*
+/// and   := unary ( ("&&" | "and") unary )*
+/// unary := ("!" | "not") unary | "(" expr ")" | term
+/// term  := /regex/[i] | "text"[i] | words... | src:NAME | src:/regex/[i]
+///        | level(:|=|>=|>|<=|<)LEVEL | kind:(line|frame|echo|sys)
+/// ```
+///
+/// Adjacent bare words match as one phrase joined by single spaces.
+#[derive(Debug, Clone)]
+pub struct Filter {
+    expr: Expr,
+    /// Whether a `kind:` term appears, which is what lets system messages
+    /// through at all.
+    kinds: bool,
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Text(String),
+    Regex(Regex),
+    Source(SourceMatch),
+    Level(LevelOp, LogLevel),
+    Kind(Kind),
+    Not(Box<Expr>),
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Line,
+    Frame,
+    Echo,
+    /// System messages and port state changes.
+    Sys,
+}
+
+/// What the terms of an expression look at in one event.
+struct Subject<'a> {
+    kind: Kind,
+    source: Option<&'a SourceId>,
+    level: Option<LogLevel>,
+    text: Cow<'a, str>,
+}
+
+#[derive(Debug, Clone)]
+enum SourceMatch {
+    /// The alias or the port path.
+    Name(String),
+    /// Matched against the label.
+    Regex(Regex),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LevelOp {
+    Eq,
+    Ge,
+    Gt,
+    Le,
+    Lt,
+}
+
+/// Why an expression did not compile; `column` is 1-based.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilterError {
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for FilterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at column {}", self.message, self.column)
+    }
+}
+
+impl std::error::Error for FilterError {}
+
+impl Filter {
+    pub fn parse(src: &str) -> Result<Self, FilterError> {
+        let mut parser = Parser {
+            tokens: lex(src)?.into_iter().peekable(),
+            end: src.chars().count(),
+        };
+        let expr = parser.or()?;
+        match parser.tokens.peek() {
+            Some((column, Token::RParen)) => return Err(error(*column, "unexpected ')'")),
+            Some((column, _)) => return Err(error(*column, "expected '&&' or '||'")),
+            None => {}
+        }
+        Ok(Self {
+            kinds: expr.has_kind(),
+            expr,
+        })
+    }
+
+    /// Lines containing `needle`, as a plain substring.
+    pub fn text(needle: &str) -> Self {
+        Self {
+            expr: Expr::Text(needle.to_string()),
+            kinds: false,
+        }
+    }
+
+    /// Events from the port with this alias or path.
+    pub fn source(name: &str) -> Self {
+        Self {
+            expr: Expr::Source(SourceMatch::Name(name.to_string())),
+            kinds: false,
+        }
+    }
+
+    /// Everything but lines of a known level below `min`, i.e.
+    /// `!(level<min)`.
+    pub fn min_level(min: LogLevel) -> Self {
+        Self {
+            expr: Expr::Not(Box::new(Expr::Level(LevelOp::Lt, min))),
+            kinds: false,
+        }
+    }
+
+    /// Events matching either filter.
+    pub fn or(self, other: Filter) -> Self {
+        let mut any = match self.expr {
+            Expr::Or(any) => any,
+            expr => vec![expr],
+        };
+        any.push(other.expr);
+        Self {
+            expr: Expr::Or(any),
+            kinds: self.kinds || other.kinds,
+        }
+    }
+
+    /// Lines matching both filters.
+    pub fn and(self, other: Filter) -> Self {
+        let mut all = match self.expr {
+            Expr::And(all) => all,
+            expr => vec![expr],
+        };
+        all.push(other.expr);
+        Self {
+            expr: Expr::And(all),
+            kinds: self.kinds || other.kinds,
+        }
+    }
+
+    /// Lines are matched without their escape sequences, frames by their
+    /// bytes as text and echoed commands by the command. Frames and
+    /// commands have no level, so level terms never match them. System
+    /// messages and port state changes only pass an expression with a
+    /// `kind:` term; for those `src:` matches the port of a state change and
+    /// the text is the message.
+    pub fn allows(&self, event: &ProcessedEvent) -> bool {
+        let subject = match event {
+            ProcessedEvent::Line {
+                source, level, raw, ..
+            } => Subject {
+                kind: Kind::Line,
+                source: Some(source),
+                level: *level,
+                text: ansi::strip(raw),
+            },
+            ProcessedEvent::Frame { source, bytes, .. } => Subject {
+                kind: Kind::Frame,
+                source: Some(source),
+                level: None,
+                text: String::from_utf8_lossy(bytes),
+            },
+            ProcessedEvent::Echo { source, text, .. } => Subject {
+                kind: Kind::Echo,
+                source: Some(source),
+                level: None,
+                text: Cow::Borrowed(text),
+            },
+            ProcessedEvent::System { level, message, .. } => Subject {
+                kind: Kind::Sys,
+                source: None,
+                level: Some(*level),
+                text: Cow::Borrowed(message),
+            },
+            ProcessedEvent::PortState { source, .. } => Subject {
+                kind: Kind::Sys,
+                source: Some(source),
+                level: None,
+                text: Cow::Borrowed(""),
+            },
+        };
+        if subject.kind == Kind::Sys && !self.kinds {
+            return false;
+        }
+        self.expr.eval(&subject)
+    }
+}
+
+impl std::ops::Not for Filter {
+    type Output = Filter;
+
+    fn not(self) -> Filter {
+        Filter {
+            expr: Expr::Not(Box::new(self.expr)),
+            kinds: self.kinds,
+        }
+    }
+}
+
+impl Expr {
+    fn eval(&self, subject: &Subject) -> bool {
+        match self {
+            Self::Text(needle) => subject.text.contains(needle.as_str()),
+            Self::Regex(re) => re.is_match(&subject.text),
+            Self::Source(SourceMatch::Name(name)) => subject.source.is_some_and(|source| {
+                source.alias.as_deref() == Some(name.as_str()) || source.port == *name
+            }),
+            Self::Source(SourceMatch::Regex(re)) => {
+                subject.source.is_some_and(|s| re.is_match(&s.label()))
+            }
+            Self::Kind(kind) => subject.kind == *kind,
+            Self::Level(op, want) => subject.level.is_some_and(|have| match op {
+                LevelOp::Eq => have == *want,
+                LevelOp::Ge => have >= *want,
+                LevelOp::Gt => have > *want,
+                LevelOp::Le => have <= *want,
+                LevelOp::Lt => have < *want,
+            }),
+            Self::Not(e) => !e.eval(subject),
+            Self::And(all) => all.iter().all(|e| e.eval(subject)),
+            Self::Or(any) => any.iter().any(|e| e.eval(subject)),
+        }
+    }
+
+    fn has_kind(&self) -> bool {
+        match self {
+            Self::Kind(_) => true,
+            Self::Not(e) => e.has_kind(),
+            Self::And(all) | Self::Or(all) => all.iter().any(Expr::has_kind),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Token {
+    LParen,
+    RParen,
+    Not,
+    And,
+    Or,
+    /// A bare word; adjacent ones form a phrase.
+    Word(String),
+    Term(Expr),
+}
+
+fn error(column: usize, message: &str) -> FilterError {
+    FilterError {
+        column,
+        message: message.to_string(),
+    }
+}
+
+/// Splits the expression into tokens, each with its 1-based column.
+fn lex(src: &str) -> Result<Vec<(usize, Token)>, FilterError> {
+    let chars = src.chars().collect::<Vec<_>>();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let column = i + 1;
+        let next = chars.get(i + 1).copied();
+
+        let token = match c {
+            c if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            '(' => {
+                i += 1;
+                Token::LParen
+            }
+            ')' => {
+                i += 1;
+                Token::RParen
+            }
+            '!' => {
+                i += 1;
+                Token::Not
+            }
+            '&' if next == Some('&') => {
+                i += 2;
+                Token::And
+            }
+            '|' if next == Some('|') => {
+                i += 2;
+                Token::Or
+            }
+            '/' => {
+                let (pattern, ignore_case) = delimited(&chars, &mut i, '/')?;
+                Token::Term(Expr::Regex(regex(&pattern, ignore_case, column)?))
+            }
+            '"' => {
+                let (text, ignore_case) = delimited(&chars, &mut i, '"')?;
+                Token::Term(text_expr(&text, ignore_case, column)?)
+            }
+            _ => {
+                let start = i;
+                let word = bare(&chars, &mut i);
+                match word.as_str() {
+                    "and" | "AND" => Token::And,
+                    "or" | "OR" => Token::Or,
+                    "not" | "NOT" => Token::Not,
+                    _ => match predicate(&chars, start, &mut i)? {
+                        Some(expr) => Token::Term(expr),
+                        None => Token::Word(word),
+                    },
+                }
+            }
+        };
+        tokens.push((column, token));
+    }
+    Ok(tokens)
+}
+
+/// A bare word: everything up to whitespace, a parenthesis or `&&`/`||`.
+fn bare(chars: &[char], i: &mut usize) -> String {
+    let mut word = String::new();
+    while let Some(&c) = chars.get(*i) {
+        let pair = |p: char| c == p && chars.get(*i + 1) == Some(&p);
+        if c.is_whitespace() || c == '(' || c == ')' || pair('&') || pair('|') {
+            break;
+        }
+        word.push(c);
+        *i += 1;
+    }
+    word
+}
+
+/// `src:...`, `kind:...` and `level...` terms starting at `start`; `None`
+/// for plain words, leaving `i` past the word.
+fn predicate(chars: &[char], start: usize, i: &mut usize) -> Result<Option<Expr>, FilterError> {
+    let rest = chars[start..].iter().collect::<String>();
+
+    if rest.starts_with("kind:") {
+        *i = start + 5;
+        let value_column = *i + 1;
+        let kind = match bare(chars, i).as_str() {
+            "line" => Kind::Line,
+            "frame" => Kind::Frame,
+            "echo" => Kind::Echo,
+            "sys" | "system" => Kind::Sys,
+            other => {
+                return Err(error(
+                    value_column,
+                    &format!("invalid kind '{other}' (expected line, frame, echo or sys)"),
+                ));
+            }
+        };
+        return Ok(Some(Expr::Kind(kind)));
+    }
+
+    if rest.starts_with("src:") {
+        *i = start + 4;
+        let value_column = *i + 1;
+        let matcher = match chars.get(*i) {
+            Some('/') => {
+                let (pattern, ignore_case) = delimited(chars, i, '/')?;
+                SourceMatch::Regex(regex(&pattern, ignore_case, value_column)?)
+            }
+            Some('"') => SourceMatch::Name(delimited(chars, i, '"')?.0),
+            _ => {
+                let name = bare(chars, i);
+                if name.is_empty() {
+                    return Err(error(value_column, "expected a port name after 'src:'"));
+                }
+                SourceMatch::Name(name)
+            }
+        };
+        return Ok(Some(Expr::Source(matcher)));
+    }
+
+    let Some(after) = rest.strip_prefix("level") else {
+        return Ok(None);
+    };
+    // `level` without an operator, or `levels`, is an ordinary word.
+    let Some((op, len)) = [
+        (">=", LevelOp::Ge),
+        ("<=", LevelOp::Le),
+        (">", LevelOp::Gt),
+        ("<", LevelOp::Lt),
+        ("=", LevelOp::Eq),
+        (":", LevelOp::Eq),
+    ]
+    .into_iter()
+    .find(|(s, _)| after.starts_with(s))
+    .map(|(s, op)| (op, s.len())) else {
+        return Ok(None);
+    };
+
+    *i = start + "level".len() + len;
+    let value_column = *i + 1;
+    let name = bare(chars, i);
+    let level = name
+        .parse::<LogLevel>()
+        .map_err(|e| error(value_column, &e))?;
+    Ok(Some(Expr::Level(op, level)))
+}
+
+/// The body of a `/regex/` or `"text"` starting at `i`, and whether an `i`
+/// flag follows. `\` escapes the delimiter (and `\\` in text).
+fn delimited(chars: &[char], i: &mut usize, delim: char) -> Result<(String, bool), FilterError> {
+    let column = *i + 1;
+    let mut body = String::new();
+    *i += 1;
+    loop {
+        match chars.get(*i) {
+            None => {
+                let what = if delim == '/' { "regex" } else { "string" };
+                return Err(error(column, &format!("unterminated {what}")));
+            }
+            Some('\\') if chars.get(*i + 1) == Some(&delim) => {
+                body.push(delim);
+                *i += 2;
+            }
+            Some('\\') if delim == '"' && chars.get(*i + 1) == Some(&'\\') => {
+                body.push('\\');
+                *i += 2;
+            }
+            Some(&c) if c == delim => {
+                *i += 1;
+                break;
+            }
+            Some(&c) => {
+                body.push(c);
+                *i += 1;
+            }
+        }
+    }
+
+    let mut ignore_case = false;
+    while let Some(&c) = chars.get(*i).filter(|c| c.is_alphanumeric()) {
+        if c != 'i' {
+            return Err(error(
+                *i + 1,
+                &format!("unknown flag '{c}' (only 'i' is supported)"),
+            ));
+        }
+        ignore_case = true;
+        *i += 1;
+    }
+    Ok((body, ignore_case))
+}
+
+fn regex(pattern: &str, ignore_case: bool, column: usize) -> Result<Regex, FilterError> {
+    RegexBuilder::new(pattern)
+        .case_insensitive(ignore_case)
+        .build()
+        .map_err(|e| {
+            let reason = regex_reason(&e);
+            error(column, &format!("invalid regex /{pattern}/: {reason}"))
+        })
+}
+
+/// What is wrong with a regex, in one line. A syntax error's full message
+/// spans several lines with a caret diagram; its last line says what is
+/// wrong.
+pub(crate) fn regex_reason(e: &regex::Error) -> String {
+    match e {
+        regex::Error::Syntax(msg) => {
+            let reason = msg.lines().last().unwrap_or_default();
+            reason.strip_prefix("error: ").unwrap_or(reason).to_string()
+        }
+        e => e.to_string(),
+    }
+}
+
+fn text_expr(text: &str, ignore_case: bool, column: usize) -> Result<Expr, FilterError> {
+    if text.is_empty() {
+        return Err(error(column, "empty string"));
+    }
+    Ok(if ignore_case {
+        Expr::Regex(regex(&regex::escape(text), true, column)?)
+    } else {
+        Expr::Text(text.to_string())
+    })
+}
+
+struct Parser {
+    tokens: Peekable<vec::IntoIter<(usize, Token)>>,
+    /// Number of characters, for errors at the end of the input.
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&mut self) -> Option<&Token> {
+        self.tokens.peek().map(|(_, t)| t)
+    }
+
+    fn column(&mut self) -> usize {
+        self.tokens
+            .peek()
+            .map_or(self.end + 1, |(column, _)| *column)
+    }
+
+    fn or(&mut self) -> Result<Expr, FilterError> {
+        let mut any = vec![self.and()?];
+        while self
+            .tokens
+            .next_if(|(_, t)| matches!(t, Token::Or))
+            .is_some()
+        {
+            any.push(self.and()?);
+        }
+        Ok(if any.len() == 1 {
+            any.remove(0)
+        } else {
+            Expr::Or(any)
+        })
+    }
+
+    fn and(&mut self) -> Result<Expr, FilterError> {
+        let mut all = vec![self.unary()?];
+        while self
+            .tokens
+            .next_if(|(_, t)| matches!(t, Token::And))
+            .is_some()
+        {
+            all.push(self.unary()?);
+        }
+        Ok(if all.len() == 1 {
+            all.remove(0)
+        } else {
+            Expr::And(all)
+        })
+    }
+
+    fn unary(&mut self) -> Result<Expr, FilterError> {
+        let column = self.column();
+        let Some((_, token)) = self.tokens.next() else {
+            return Err(error(column, "expected a term"));
+        };
+        match token {
+            Token::Not => Ok(Expr::Not(Box::new(self.unary()?))),
+            Token::LParen => {
+                let inner = self.or()?;
+                if !matches!(self.peek(), Some(Token::RParen)) {
+                    return Err(error(self.column(), "expected ')'"));
+                }
+                self.tokens.next();
+                Ok(inner)
+            }
+            Token::Term(expr) => Ok(expr),
+            Token::Word(mut phrase) => {
+                while let Some((_, Token::Word(word))) =
+                    self.tokens.next_if(|(_, t)| matches!(t, Token::Word(_)))
+                {
+                    phrase.push(' ');
+                    phrase.push_str(&word);
+                }
+                Ok(Expr::Text(phrase))
+            }
+            Token::RParen => Err(error(column, "unexpected ')'")),
+            Token::And | Token::Or => Err(error(column, "expected a term before the operator")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::SystemTime;
+
+    fn source(port: &str, alias: Option<&str>) -> SourceId {
+        SourceId {
+            port: port.to_string(),
+            alias: alias.map(str::to_string),
+        }
+    }
+
+    fn line(source: SourceId, level: Option<LogLevel>, raw: &str) -> ProcessedEvent {
+        ProcessedEvent::Line {
+            ts: SystemTime::UNIX_EPOCH,
+            source,
+            level,
+            device_ts: None,
+            raw: raw.to_string(),
+        }
+    }
+
+    fn frame(source: SourceId, bytes: &[u8]) -> ProcessedEvent {
+        ProcessedEvent::Frame {
+            ts: SystemTime::UNIX_EPOCH,
+            source,
+            offset: 0,
+            bytes: bytes.to_vec(),
+        }
+    }
+
+    fn system(message: &str) -> ProcessedEvent {
+        ProcessedEvent::System {
+            ts: SystemTime::UNIX_EPOCH,
+            level: LogLevel::Warn,
+            message: message.to_string(),
+        }
+    }
+
+    fn allows(expr: &str, event: &ProcessedEvent) -> bool {
+        Filter::parse(expr).unwrap().allows(event)
+    }
+
+    fn column(expr: &str) -> usize {
+        Filter::parse(expr).unwrap_err().column
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        let gps = source("/dev/ttyUSB0", Some("GPS"));
+        let modem = source("/dev/ttyUSB1", Some("Modem"));
+
+        let expr = "src:GPS && fix || level>=error";
+        assert!(allows(expr, &line(gps.clone(), None, "fix ok")));
+        assert!(allows(
+            expr,
+            &line(modem.clone(), Some(LogLevel::Error), "x")
+        ));
+        assert!(!allows(expr, &line(modem.clone(), None, "fix ok")));
+
+        let expr = "src:GPS && (fix || level>=error)";
+        assert!(!allows(expr, &line(modem, Some(LogLevel::Error), "x")));
+        assert!(allows(expr, &line(gps.clone(), Some(LogLevel::Error), "x")));
+
+        assert!(!allows("not fix or lost", &line(gps.clone(), None, "fix")));
+        assert!(allows("!fix && !lost", &line(gps, None, "ok")));
+    }
+
+    #[test]
+    fn bare_words_form_one_phrase() {
+        let s = source("/dev/ttyUSB0", None);
+        assert!(allows(
+            "fix  lost",
+            &line(s.clone(), None, "gps fix lost now")
+        ));
+        assert!(!allows("fix lost", &line(s.clone(), None, "lost fix")));
+        assert!(allows("fix lost || boot", &line(s.clone(), None, "boot")));
+        assert!(allows("AT+ OK", &line(s, None, "AT+ OK")));
+    }
+
+    #[test]
+    fn source_by_quoted_path_alias_or_regex() {
+        let s = source("/dev/x", Some("Board"));
+        assert!(allows("src:\"/dev/x\"", &line(s.clone(), None, "a")));
+        assert!(allows("src:Board", &line(s.clone(), None, "a")));
+        assert!(allows("src:/^bo/i", &line(s.clone(), None, "a")));
+        assert!(!allows("src:/^bo/", &line(s.clone(), None, "a")));
+        assert!(!allows("src:\"/dev/y\"", &line(s, None, "a")));
+    }
+
+    #[test]
+    fn case_flags() {
+        let s = source("/dev/ttyUSB0", None);
+        assert!(allows("\"boot\"i", &line(s.clone(), None, "BOOT done")));
+        assert!(!allows("\"boot\"", &line(s.clone(), None, "BOOT done")));
+        assert!(allows("/^e \\(/i", &line(s.clone(), None, "E (12) x")));
+        assert!(allows("\"a.b\"i", &line(s.clone(), None, "A.B")));
+        assert!(!allows("\"a.b\"i", &line(s, None, "AxB")));
+    }
+
+    #[test]
+    fn lines_are_matched_without_escapes() {
+        let s = source("/dev/ttyUSB0", None);
+        assert!(allows(
+            "\"fix lost\"",
+            &line(s, None, "fix \x1b[31mlost\x1b[0m")
+        ));
+    }
+
+    #[test]
+    fn frames_match_source_and_text_but_no_level() {
+        let radio = source("/dev/ttyUSB2", Some("Radio"));
+        let other = source("/dev/ttyUSB3", None);
+        assert!(allows("src:Radio", &frame(radio.clone(), b"\x01OK\xff")));
+        assert!(!allows("src:Radio", &frame(other, b"OK")));
+        assert!(allows(
+            "OK && kind:frame",
+            &frame(radio.clone(), b"\x01OK\xff")
+        ));
+        assert!(!allows("level<=error", &frame(radio.clone(), b"OK")));
+        assert!(!allows("kind:line", &frame(radio, b"OK")));
+    }
+
+    #[test]
+    fn system_messages_need_a_kind_term() {
+        assert!(!allows("/never/", &system("stdin closed")));
+        assert!(!allows("!/never/", &system("stdin closed")));
+        assert!(allows("kind:sys", &system("stdin closed")));
+        assert!(allows("kind:sys && level>=warn", &system("x")));
+        assert!(!allows("kind:sys && src:GPS", &system("x")));
+
+        let f = Filter::parse("kind:sys").unwrap().and(!Filter::text("x"));
+        assert!(f.allows(&system("stdin closed")));
+        assert!(!f.allows(&system("x")));
+    }
+
+    #[test]
+    fn errors_point_at_their_column() {
+        assert_eq!(column("a &&"), 5);
+        assert_eq!(column("|| a"), 1);
+        assert_eq!(column("(a"), 3);
+        assert_eq!(column("a )"), 3);
+        assert_eq!(column("a b)"), 4);
+        assert_eq!(column("  /abc"), 3);
+        assert_eq!(column("\"abc"), 1);
+        assert_eq!(column("\"abc\"x"), 6);
+        assert_eq!(column("/a/ix"), 5);
+        assert_eq!(column("x || /(/"), 6);
+        assert_eq!(column("level>=loud"), 8);
+        assert_eq!(column("src:"), 5);
+        assert_eq!(column("kind:nope"), 6);
+        assert_eq!(column("\"\""), 1);
+
+        let e = Filter::parse("level:x").unwrap_err();
+        assert!(e.message.starts_with("invalid level 'x'"), "{e}");
+    }
+
+    #[test]
+    fn min_level_per_source() {
+        let gps = source("/dev/ttyUSB0", Some("GPS"));
+        let modem = source("/dev/ttyUSB1", None);
+        let f = (!Filter::source("GPS"))
+            .or(Filter::min_level(LogLevel::Info))
+            .and(Filter::source("GPS").or(Filter::min_level(LogLevel::Warn)));
+
+        assert!(f.allows(&line(gps.clone(), Some(LogLevel::Info), "x")));
+        assert!(!f.allows(&line(gps.clone(), Some(LogLevel::Debug), "x")));
+        assert!(!f.allows(&line(modem.clone(), Some(LogLevel::Info), "x")));
+        assert!(f.allows(&line(modem.clone(), Some(LogLevel::Error), "x")));
+        assert!(f.allows(&line(modem.clone(), None, "x")));
+        assert!(f.allows(&frame(modem, b"x")));
+    }
+}
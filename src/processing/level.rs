@@ -0,0 +1,114 @@
+use crate::core::LogLevel;
+use regex::Regex;
+
+/// A user-supplied level rule: either a fixed level for every line the
+/// regex matches, or a `level` capture group holding a level word.
+#[derive(Debug, Clone)]
+pub struct LevelPattern {
+    regex: Regex,
+    level: Option<LogLevel>,
+}
+
+impl LevelPattern {
+    pub fn new(pattern: &str, level: Option<LogLevel>) -> Result<Self, String> {
+        let regex = Regex::new(pattern).map_err(|e| format!("level pattern '{pattern}': {e}"))?;
+        if level.is_none() && !regex.capture_names().any(|n| n == Some("level")) {
+            return Err(format!(
+                "level pattern '{pattern}' needs a (?<level>...) group or a fixed level"
+            ));
+        }
+        Ok(Self { regex, level })
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Rule {
+    /// The `level` group (or the first group) holds a level word or letter.
+    Word(Regex),
+    /// The first group is a syslog `<PRI>` value.
+    Syslog(Regex),
+    Fixed(Regex, LogLevel),
+}
+
+/// Recognizes the level of a device line from its text. User patterns are
+/// tried first, then the built-in embedded formats.
+#[derive(Debug, Clone)]
+pub struct LevelDetector {
+    rules: Vec<Rule>,
+}
+
+impl Default for LevelDetector {
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
+}
+
+impl LevelDetector {
+    pub fn new(patterns: Vec<LevelPattern>) -> Self {
+        let builtin = |re: &str| Regex::new(re).expect("built-in level pattern");
+
+        let mut rules = patterns
+            .into_iter()
+            .map(|p| match p.level {
+                Some(level) => Rule::Fixed(p.regex, level),
+                None => Rule::Word(p.regex),
+            })
+            .collect::<Vec<_>>();
+        rules.extend([
+            // syslog: `<13>Oct 11 22:14:15 host app: ...`
+            Rule::Syslog(builtin(r"^<(\d{1,3})>")),
+            // Zephyr: `[00:00:01.234,000] <err> main: ...`
+            Rule::Word(builtin(r"<(err|wrn|inf|dbg)> ")),
+            // ESP-IDF: `E (1234) wifi: ...`
+            Rule::Word(builtin(
+                r"^([EWIDV]) \((?:\d+|\d{2}:\d{2}:\d{2}\.\d{3})\) [^:]*:",
+            )),
+            // logcat threadtime: `10-17 12:00:00.123  1234  1240 W Tag: ...`
+            Rule::Word(builtin(
+                r"^\d{2}-\d{2} \d{2}:\d{2}:\d{2}\.\d{3}\s+\d+\s+\d+ ([VDIWEFA]) ",
+            )),
+            // logcat brief and tag: `W/Tag( 1234): ...`, `W/Tag: ...`
+            Rule::Word(builtin(r"^([VDIWEFA])/[^:(]+(?:\(\s*\d+\))?: ")),
+            // `[ERROR] ...`, `[warn] ...`
+            Rule::Word(builtin(
+                r"(?i)\[(trace|debug|info|notice|warn|warning|error|err|fatal|crit|critical)\]",
+            )),
+        ]);
+        Self { rules }
+    }
+
+    /// `text` is the line without escape sequences.
+    pub fn detect(&self, text: &str) -> Option<LogLevel> {
+        self.rules.iter().find_map(|rule| match rule {
+            Rule::Word(re) => {
+                let caps = re.captures(text)?;
+                let word = caps.name("level").or_else(|| caps.get(1))?;
+                word_level(word.as_str())
+            }
+            Rule::Syslog(re) => {
+                let pri = re.captures(text)?[1].parse::<u8>().ok()?;
+                Some(match pri % 8 {
+                    0..=3 => LogLevel::Error,
+                    4 => LogLevel::Warn,
+                    5 | 6 => LogLevel::Info,
+                    _ => LogLevel::Debug,
+                })
+            }
+            Rule::Fixed(re, level) => re.is_match(text).then_some(*level),
+        })
+    }
+}
+
+/// Level names, abbreviations and the single letters used by ESP-IDF and
+/// logcat.
+fn word_level(word: &str) -> Option<LogLevel> {
+    Some(match word.to_ascii_lowercase().as_str() {
+        "v" | "t" | "verbose" | "trace" | "trc" => LogLevel::Trace,
+        "d" | "debug" | "dbg" => LogLevel::Debug,
+        "i" | "n" | "info" | "inf" | "notice" => LogLevel::Info,
+        "w" | "warn" | "warning" | "wrn" => LogLevel::Warn,
+        "e" | "f" | "a" | "error" | "err" | "fatal" | "assert" | "crit" | "critical" | "alert"
+        | "emerg" | "panic" => LogLevel::Error,
+        _ => return None,
+    })
+}
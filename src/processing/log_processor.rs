@@ -1,36 +1,149 @@
-use crate::core::{AppEvent, AppResult, LogLevel, SourceId};
-use std::time::SystemTime;
+use crate::core::{
+    AnsiPolicy, AppEvent, AppResult, LogLevel, PortState, SourceId, TimestampFormat,
+};
+use crate::processing::ansi;
+use crate::processing::level::{LevelDetector, LevelPattern};
+use crate::processing::timestamp::DeviceClock;
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ProcessedEvent {
     Line {
+        /// When the host received the line.
         ts: SystemTime,
         source: SourceId,
+        /// From the source, e.g. defmt, or recognized in the text.
+        level: Option<LogLevel>,
+        /// The time the device printed, when the port has a timestamp
+        /// format and the line carries one.
+        device_ts: Option<Duration>,
         raw: String,
     },
+    Frame {
+        ts: SystemTime,
+        source: SourceId,
+        offset: u64,
+        bytes: Vec<u8>,
+    },
     System {
         ts: SystemTime,
         level: LogLevel,
         message: String,
     },
+    PortState {
+        ts: SystemTime,
+        source: SourceId,
+        state: PortState,
+    },
+    Echo {
+        ts: SystemTime,
+        source: SourceId,
+        text: String,
+    },
 }
 
 #[derive(Clone, Default)]
-pub struct LogProcessor;
+pub struct LogProcessor {
+    levels: LevelDetector,
+    ansi: AnsiPolicy,
+    port_ansi: HashMap<SourceId, AnsiPolicy>,
+    clock: Option<DeviceClock>,
+    port_clocks: HashMap<SourceId, Option<DeviceClock>>,
+}
 
 impl LogProcessor {
     pub fn new() -> Self {
-        Self {}
+        Self {
+            levels: LevelDetector::default(),
+            ansi: AnsiPolicy::default(),
+            port_ansi: HashMap::new(),
+            clock: None,
+            port_clocks: HashMap::new(),
+        }
+    }
+
+    /// The ANSI policy for ports without one of their own, e.g. hotplugged
+    /// ports.
+    pub fn with_ansi(mut self, policy: AnsiPolicy) -> Self {
+        self.ansi = policy;
+        self
+    }
+
+    pub fn with_port_ansi(mut self, source: SourceId, policy: AnsiPolicy) -> Self {
+        self.port_ansi.insert(source, policy);
+        self
+    }
+
+    /// The device timestamp format for ports without one of their own.
+    pub fn with_device_ts(mut self, format: Option<&TimestampFormat>) -> Self {
+        self.clock = format.map(DeviceClock::new);
+        self
+    }
+
+    pub fn with_port_device_ts(
+        mut self,
+        source: SourceId,
+        format: Option<&TimestampFormat>,
+    ) -> Self {
+        self.port_clocks
+            .insert(source, format.map(DeviceClock::new));
+        self
+    }
+
+    /// Level rules tried before the built-in formats.
+    pub fn with_level_patterns(mut self, patterns: Vec<LevelPattern>) -> Self {
+        self.levels = LevelDetector::new(patterns);
+        self
     }
 
     pub fn process(&self, event: AppEvent) -> AppResult<ProcessedEvent> {
         Ok(match event {
-            AppEvent::LogLine { source, ts, raw } => ProcessedEvent::Line { ts, source, raw },
+            AppEvent::LogLine {
+                source,
+                ts,
+                level,
+                raw,
+            } => {
+                let policy = self.port_ansi.get(&source).copied().unwrap_or(self.ansi);
+                let raw = ansi::apply(policy, raw);
+                let text = ansi::strip(&raw);
+                let clock = match self.port_clocks.get(&source) {
+                    Some(clock) => clock.as_ref(),
+                    None => self.clock.as_ref(),
+                };
+                let device_ts = clock.and_then(|c| c.parse(&text));
+                let level = level.or_else(|| self.levels.detect(&text));
+                ProcessedEvent::Line {
+                    ts,
+                    source,
+                    level,
+                    device_ts,
+                    raw,
+                }
+            }
+            AppEvent::Frame {
+                source,
+                ts,
+                offset,
+                bytes,
+            } => ProcessedEvent::Frame {
+                ts,
+                source,
+                offset,
+                bytes,
+            },
             AppEvent::System { level, message } => ProcessedEvent::System {
                 ts: SystemTime::now(),
                 level,
                 message,
             },
+            AppEvent::PortState { source, state } => ProcessedEvent::PortState {
+                ts: SystemTime::now(),
+                source,
+                state,
+            },
+            AppEvent::Echo { source, ts, text } => ProcessedEvent::Echo { ts, source, text },
         })
     }
 }
@@ -1,3 +1,10 @@
+pub mod ansi;
+pub mod filter;
+pub mod level;
 pub mod log_processor;
+pub mod timestamp;
 
+pub use filter::{Filter, FilterError};
+pub use level::LevelPattern;
 pub use log_processor::{LogProcessor, ProcessedEvent};
+pub use timestamp::{ClockDrift, DeviceClock};
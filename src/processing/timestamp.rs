@@ -0,0 +1,184 @@
+use crate::core::{SourceId, TimestampFormat};
+use regex::Regex;
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+
+/// Reads a device's own timestamp from the text of its lines.
+#[derive(Debug, Clone)]
+pub struct DeviceClock {
+    regex: Regex,
+    /// Seconds per unit of the captured number; `None` for Zephyr's
+    /// `hh:mm:ss.mmm,uuu`.
+    scale: Option<f64>,
+}
+
+impl DeviceClock {
+    pub fn new(format: &TimestampFormat) -> Self {
+        let builtin = |re: &str| Regex::new(re).expect("built-in timestamp pattern");
+        match format {
+            TimestampFormat::Zephyr => Self {
+                regex: builtin(r"^\[(\d+):(\d{2}):(\d{2})\.(\d{3})(?:,(\d{3}))?\]"),
+                scale: None,
+            },
+            TimestampFormat::EspIdf => Self {
+                regex: builtin(r"^[EWIDV] \((\d+)\) "),
+                scale: Some(1e-3),
+            },
+            TimestampFormat::Kernel => Self {
+                regex: builtin(r"^\[\s*(\d+\.\d+)\]"),
+                scale: Some(1.0),
+            },
+            TimestampFormat::Pattern { regex, unit } => Self {
+                // Checked when the format was parsed.
+                regex: Regex::new(regex).expect("validated timestamp regex"),
+                scale: Some(unit.as_secs()),
+            },
+        }
+    }
+
+    /// `text` is the line without escape sequences.
+    pub fn parse(&self, text: &str) -> Option<Duration> {
+        let caps = self.regex.captures(text)?;
+        match self.scale {
+            Some(scale) => {
+                let value = caps.name("ts").or_else(|| caps.get(1))?;
+                let value = value.as_str().parse::<f64>().ok()?;
+                Duration::try_from_secs_f64(value * scale).ok()
+            }
+            None => {
+                let part = |i: usize| caps.get(i).map_or(Some(0), |m| m.as_str().parse().ok());
+                let secs = part(1)? * 3600 + part(2)? * 60 + part(3)?;
+                let micros = part(4)? * 1000 + part(5)?;
+                Some(Duration::from_secs(secs) + Duration::from_micros(micros))
+            }
+        }
+    }
+}
+
+/// Width of the windows whose smallest host-minus-device offset is kept:
+/// the host receives a line late, never early, so the minimum is the
+/// sample least disturbed by latency.
+const WINDOW_SECS: f64 = 10.0;
+const FIRST_REPORT_SECS: f64 = 60.0;
+const REPORT_EVERY_SECS: f64 = 300.0;
+
+/// Estimates, per port, how fast the device clock runs against the host
+/// clock, from the pairs of timestamps on its lines.
+#[derive(Debug, Default)]
+pub struct ClockDrift {
+    ports: HashMap<SourceId, Drift>,
+}
+
+impl ClockDrift {
+    /// Adds a line's timestamps; returns a message when an estimate is due
+    /// or the device clock restarted.
+    pub fn observe(
+        &mut self,
+        source: &SourceId,
+        host: SystemTime,
+        device: Duration,
+    ) -> Option<String> {
+        let host = host.duration_since(SystemTime::UNIX_EPOCH).ok()?;
+        let drift = self.ports.entry(source.clone()).or_default();
+        let label = source.label();
+
+        let device = device.as_secs_f64();
+        if drift.origin.is_some() && device < drift.last {
+            let last = drift.last;
+            *drift = Drift::default();
+            drift.add(host.as_secs_f64(), device);
+            return Some(format!(
+                "{label}: device clock went back from {last:.3}s to {device:.3}s, restarting drift estimate"
+            ));
+        }
+        drift.add(host.as_secs_f64(), device);
+
+        let (span, ppm) = drift.due()?;
+        Some(format!(
+            "{label}: device clock drift {ppm:+.1} ppm against the host over {span:.0}s"
+        ))
+    }
+}
+
+#[derive(Debug)]
+struct Drift {
+    /// Device time and offset of the first sample.
+    origin: Option<(f64, f64)>,
+    last: f64,
+    /// The open window: its index and lowest sample.
+    window: Option<(i64, f64, f64)>,
+    /// Least-squares sums over the closed windows' samples.
+    n: f64,
+    sx: f64,
+    sy: f64,
+    sxx: f64,
+    sxy: f64,
+    next_report: f64,
+}
+
+impl Default for Drift {
+    fn default() -> Self {
+        Self {
+            origin: None,
+            last: 0.0,
+            window: None,
+            n: 0.0,
+            sx: 0.0,
+            sy: 0.0,
+            sxx: 0.0,
+            sxy: 0.0,
+            next_report: FIRST_REPORT_SECS,
+        }
+    }
+}
+
+impl Drift {
+    fn add(&mut self, host: f64, device: f64) {
+        self.last = device;
+        let offset = host - device;
+        let (x0, y0) = *self.origin.get_or_insert((device, offset));
+        let (x, y) = (device - x0, offset - y0);
+
+        let index = (x / WINDOW_SECS).floor() as i64;
+        match self.window {
+            Some((i, _, low)) if i == index => {
+                if y < low {
+                    self.window = Some((i, x, y));
+                }
+            }
+            Some((_, wx, wy)) => {
+                self.close(wx, wy);
+                self.window = Some((index, x, y));
+            }
+            None => self.window = Some((index, x, y)),
+        }
+    }
+
+    fn close(&mut self, x: f64, y: f64) {
+        self.n += 1.0;
+        self.sx += x;
+        self.sy += y;
+        self.sxx += x * x;
+        self.sxy += x * y;
+    }
+
+    /// The device time covered and the drift in ppm, positive when the
+    /// device clock runs fast, once a report is due.
+    fn due(&mut self) -> Option<(f64, f64)> {
+        let x0 = self.origin?.0;
+        let span = self.last - x0;
+        if span < self.next_report || self.n < 3.0 {
+            return None;
+        }
+        let denom = self.n * self.sxx - self.sx * self.sx;
+        if denom <= 0.0 {
+            return None;
+        }
+        while self.next_report <= span {
+            self.next_report += REPORT_EVERY_SECS;
+        }
+        // The offset grows when the device clock falls behind.
+        let slope = (self.n * self.sxy - self.sx * self.sy) / denom;
+        Some((span, -slope * 1e6))
+    }
+}
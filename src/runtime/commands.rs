@@ -0,0 +1,140 @@
+use crate::core::{AppError, AppEvent, AppResult, LogLevel, SourceId};
+
+use std::io::BufRead;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+
+const COMMAND_QUEUE: usize = 32;
+
+type Target = (SourceId, mpsc::Sender<String>);
+
+/// Routes typed commands to the write half of each registered port.
+#[derive(Clone, Default)]
+pub struct CommandRouter {
+    targets: Arc<Mutex<Vec<Target>>>,
+}
+
+impl CommandRouter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a port and returns the queue its source task should drain.
+    pub fn register(&self, source: SourceId) -> mpsc::Receiver<String> {
+        let (tx, rx) = mpsc::channel(COMMAND_QUEUE);
+        if let Ok(mut targets) = self.targets.lock() {
+            targets.retain(|(s, _)| *s != source);
+            targets.push((source, tx));
+        }
+        rx
+    }
+
+    pub fn unregister(&self, source: &SourceId) {
+        if let Ok(mut targets) = self.targets.lock() {
+            targets.retain(|(s, _)| s != source);
+        }
+    }
+
+    /// Finds a registered port by alias or device path.
+    pub fn resolve(&self, label: &str) -> Option<SourceId> {
+        let targets = self.targets.lock().ok()?;
+        targets
+            .iter()
+            .map(|(s, _)| s)
+            .find(|s| s.alias.as_deref() == Some(label))
+            .or_else(|| targets.iter().map(|(s, _)| s).find(|s| s.port == label))
+            .cloned()
+    }
+
+    pub fn send(&self, target: &SourceId, text: String) -> AppResult<()> {
+        let tx = self
+            .targets
+            .lock()
+            .ok()
+            .and_then(|t| {
+                t.iter()
+                    .find(|(s, _)| s == target)
+                    .map(|(_, tx)| tx.clone())
+            })
+            .ok_or_else(|| AppError::Command(format!("unknown port '{target}'")))?;
+
+        tx.try_send(text).map_err(|e| match e {
+            mpsc::error::TrySendError::Full(_) => {
+                AppError::Command(format!("command queue full for '{target}'"))
+            }
+            mpsc::error::TrySendError::Closed(_) => {
+                AppError::Command(format!("port '{target}' is no longer open"))
+            }
+        })
+    }
+
+    /// Sends an input line, honouring an optional `@label` prefix.
+    ///
+    /// Without a prefix the command goes to `default`, or to the only
+    /// registered port when there is exactly one.
+    pub fn dispatch(&self, input: &str, default: Option<&SourceId>) -> AppResult<SourceId> {
+        let (label, text) = split_target(input);
+
+        let target = match label {
+            Some(label) => self
+                .resolve(label)
+                .ok_or_else(|| AppError::Command(format!("unknown port '{label}'")))?,
+            None => match default {
+                Some(s) => s.clone(),
+                None => self.sole_target().ok_or_else(|| {
+                    AppError::Command(
+                        "several ports are open: prefix the command with @label".to_string(),
+                    )
+                })?,
+            },
+        };
+
+        self.send(&target, text.to_string())?;
+        Ok(target)
+    }
+
+    fn sole_target(&self) -> Option<SourceId> {
+        let targets = self.targets.lock().ok()?;
+        match targets.as_slice() {
+            [(s, _)] => Some(s.clone()),
+            _ => None,
+        }
+    }
+}
+
+/// Splits `@label rest` into its target and payload.
+pub fn split_target(input: &str) -> (Option<&str>, &str) {
+    match input.strip_prefix('@') {
+        Some(rest) => match rest.split_once(char::is_whitespace) {
+            Some((label, text)) => (Some(label), text),
+            None => (Some(rest), ""),
+        },
+        None => (None, input),
+    }
+}
+
+/// Forwards lines typed on stdin to the router.
+///
+/// Runs on a plain thread rather than the runtime's blocking pool, since a
+/// pending stdin read would otherwise keep the runtime from shutting down.
+pub fn spawn_stdin_commands(router: CommandRouter, events: mpsc::Sender<AppEvent>) {
+    std::thread::spawn(move || {
+        let stdin = std::io::stdin();
+        for line in stdin.lock().lines() {
+            let Ok(line) = line else { break };
+            if line.trim().is_empty() {
+                continue;
+            }
+            if let Err(e) = router.dispatch(&line, None)
+                && events
+                    .blocking_send(AppEvent::System {
+                        level: LogLevel::Warn,
+                        message: e.to_string(),
+                    })
+                    .is_err()
+            {
+                break;
+            }
+        }
+    });
+}
@@ -1,52 +1,16 @@
 use crate::core::{AppEvent, AppResult, LogLevel};
-use crate::processing::{LogProcessor, ProcessedEvent};
+use crate::processing::{ClockDrift, LogProcessor, ProcessedEvent};
 use crate::runtime::Shutdown;
 use std::time::SystemTime;
 use tokio::sync::mpsc;
 
-#[derive(Clone, Default)]
-pub struct LineFilter {
-    include: Option<String>,
-    exclude: Vec<String>,
-}
-
-impl LineFilter {
-    pub fn new(include: Option<String>, exclude: Vec<String>) -> Self {
-        let include = include.and_then(|s| {
-            let t = s.trim().to_string();
-            if t.is_empty() { None } else { Some(t) }
-        });
-
-        let exclude = exclude
-            .into_iter()
-            .map(|s| s.trim().to_string())
-            .filter(|s| !s.is_empty())
-            .collect::<Vec<_>>();
-
-        Self { include, exclude }
-    }
-
-    pub fn allows(&self, raw: &str) -> bool {
-        if let Some(inc) = &self.include {
-            if !raw.contains(inc) {
-                return false;
-            }
-        }
-        for ex in &self.exclude {
-            if raw.contains(ex) {
-                return false;
-            }
-        }
-        true
-    }
-}
-
 pub struct Engine {
     processor: LogProcessor,
     out: mpsc::Sender<ProcessedEvent>,
     shutdown: Shutdown,
     dropped: u64,
-    filter: LineFilter,
+    drift: Option<ClockDrift>,
+    backpressure: bool,
 }
 
 impl Engine {
@@ -60,12 +24,22 @@ impl Engine {
             out,
             shutdown,
             dropped: 0,
-            filter: LineFilter::default(),
+            drift: None,
+            backpressure: false,
         }
     }
 
-    pub fn with_filter(mut self, filter: LineFilter) -> Self {
-        self.filter = filter;
+    /// Reports how fast each device clock runs against the host clock, for
+    /// ports with device timestamps.
+    pub fn with_clock_drift(mut self, on: bool) -> Self {
+        self.drift = on.then(ClockDrift::default);
+        self
+    }
+
+    /// Waits for the sinks instead of dropping events when they fall behind.
+    /// Only suitable when no live source can overrun its own buffers.
+    pub fn with_backpressure(mut self, on: bool) -> Self {
+        self.backpressure = on;
         self
     }
 
@@ -83,22 +57,49 @@ impl Engine {
                 }
                 evt = rx.recv() => {
                     let Some(evt) = evt else { break; };
-
-                    if let AppEvent::LogLine { raw, .. } = &evt {
-                        if !self.filter.allows(raw) {
-                            continue;
-                        }
-                    }
-
-                    let out = self.processor.process(evt)?;
-                    self.publish(out);
+                    self.handle(evt).await?;
                 }
             }
         }
 
+        // Deliver what the sources had already queued.
+        while let Ok(evt) = rx.try_recv() {
+            self.handle(evt).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn handle(&mut self, evt: AppEvent) -> AppResult<()> {
+        let out = self.processor.process(evt)?;
+        if let ProcessedEvent::Line {
+            ts,
+            source,
+            device_ts: Some(dev),
+            ..
+        } = &out
+            && let Some(drift) = &mut self.drift
+            && let Some(message) = drift.observe(source, *ts, *dev)
+        {
+            self.send(ProcessedEvent::System {
+                ts: SystemTime::now(),
+                level: LogLevel::Info,
+                message,
+            })
+            .await;
+        }
+        self.send(out).await;
         Ok(())
     }
 
+    async fn send(&mut self, event: ProcessedEvent) {
+        if self.backpressure {
+            let _ = self.out.send(event).await;
+        } else {
+            self.publish(event);
+        }
+    }
+
     fn publish(&mut self, event: ProcessedEvent) {
         if self.out.is_closed() {
             self.dropped = 0;
@@ -1,5 +1,7 @@
+pub mod commands;
 pub mod engine;
 pub mod shutdown;
 
+pub use commands::CommandRouter;
 pub use engine::Engine;
 pub use shutdown::{Shutdown, ShutdownHandle, shutdown_channel};
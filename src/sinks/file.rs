@@ -1,50 +1,90 @@
 use crate::core::{LogLevel, SourceId};
 use crate::processing::ProcessedEvent;
-use crate::sinks::EventSink;
+use crate::sinks::rotate::{RotatingFile, RotationPolicy};
+use crate::sinks::{EventSink, RenderOptions, fmt_device_ts, hex_dump};
 
 use chrono::{DateTime, SecondsFormat, Utc};
 
-use std::fs::File;
-use std::io::{BufWriter, Write};
 use std::path::PathBuf;
 use std::sync::Mutex;
 use std::time::SystemTime;
 
 pub struct FileSink {
-    w: Mutex<BufWriter<File>>,
+    w: Mutex<RotatingFile>,
+    render: RenderOptions,
 }
 
 impl FileSink {
-    pub fn new(path: PathBuf) -> std::io::Result<Self> {
-        if let Some(parent) = path.parent() {
-            std::fs::create_dir_all(parent)?;
-        }
-        let f = File::create(path)?;
+    pub fn new(
+        path: PathBuf,
+        rotation: RotationPolicy,
+        render: RenderOptions,
+    ) -> std::io::Result<Self> {
         Ok(Self {
-            w: Mutex::new(BufWriter::new(f)),
+            w: Mutex::new(RotatingFile::open(path, rotation)?),
+            render,
         })
     }
 }
 
 impl EventSink for FileSink {
     fn emit(&self, event: &ProcessedEvent) {
-        let mut w = match self.w.lock() {
-            Ok(g) => g,
-            Err(_) => return,
+        let Some(record) = render(event, self.render) else {
+            return;
         };
 
-        match event {
-            ProcessedEvent::Line { ts, source, raw } => {
-                let ts = fmt_ts(*ts);
-                let src = fmt_source(source);
-                let _ = writeln!(w, "[{ts}] {src} │ {raw}");
-            }
-            ProcessedEvent::System { ts, level, message } => {
-                let ts = fmt_ts(*ts);
-                let lvl = fmt_level(*level);
-                let _ = writeln!(w, "[{ts}] [SYS] {lvl} ▸ {message}");
-            }
+        if let Ok(mut w) = self.w.lock() {
+            let _ = w.write_record(&record);
+        }
+    }
+}
+
+/// Formats an event as one line of the text log, a binary frame as one line
+/// per hex dump row, or `None` for events the text log leaves out.
+pub(crate) fn render(event: &ProcessedEvent, options: RenderOptions) -> Option<String> {
+    match event {
+        ProcessedEvent::Line {
+            ts,
+            source,
+            level,
+            device_ts,
+            raw,
+        } => {
+            let ts = fmt_ts(*ts);
+            let dev = device_ts
+                .map(|d| format!(" [{}]", fmt_device_ts(d)))
+                .unwrap_or_default();
+            let src = fmt_source(source);
+            let raw = options.text(raw);
+            Some(match level {
+                Some(level) => format!("[{ts}]{dev} {src} {} │ {raw}", fmt_level(*level)),
+                None => format!("[{ts}]{dev} {src} │ {raw}"),
+            })
+        }
+        ProcessedEvent::Frame {
+            ts,
+            source,
+            offset,
+            bytes,
+        } => {
+            let ts = fmt_ts(*ts);
+            let src = fmt_source(source);
+            let rows = hex_dump(bytes, *offset)
+                .map(|row| format!("[{ts}] {src} │ {row}"))
+                .collect::<Vec<_>>();
+            Some(rows.join("\n"))
+        }
+        ProcessedEvent::System { ts, level, message } => {
+            let ts = fmt_ts(*ts);
+            let lvl = fmt_level(*level);
+            Some(format!("[{ts}] [SYS] {lvl} ▸ {message}"))
+        }
+        ProcessedEvent::Echo { ts, source, text } => {
+            let ts = fmt_ts(*ts);
+            let src = fmt_source(source);
+            Some(format!("[{ts}] {src} » {text}"))
         }
+        ProcessedEvent::PortState { .. } => None,
     }
 }
 
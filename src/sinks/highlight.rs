@@ -0,0 +1,306 @@
+use crate::processing::ansi;
+use crate::processing::filter::regex_reason;
+
+use regex::{Regex, RegexBuilder};
+use std::str::FromStr;
+
+const RESET: &str = "\x1b[0m";
+
+/// A terminal color: one of the 16 named ones, a 256-color index or RGB.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Color {
+    /// 0-7 for black, red, green, yellow, blue, magenta, cyan, white.
+    Named(u8),
+    Bright(u8),
+    Indexed(u8),
+    Rgb(u8, u8, u8),
+}
+
+const NAMES: [&str; 8] = [
+    "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+];
+
+impl Color {
+    /// SGR parameters with `base` 30 for the foreground or 40 for the
+    /// background.
+    fn sgr(self, base: u8) -> String {
+        match self {
+            Self::Named(n) => (base + n).to_string(),
+            Self::Bright(n) => (base + 60 + n).to_string(),
+            Self::Indexed(n) => format!("{};5;{n}", base + 8),
+            Self::Rgb(r, g, b) => format!("{};2;{r};{g};{b}", base + 8),
+        }
+    }
+}
+
+impl FromStr for Color {
+    type Err = String;
+
+    /// `red`, `bright-red`, `#ff8800` or a 256-color index such as `208`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_ascii_lowercase();
+        let named = |name: &str| NAMES.iter().position(|n| *n == name).map(|n| n as u8);
+
+        if let Some(n) = named(&s) {
+            return Ok(Self::Named(n));
+        }
+        if let Some(n) = s.strip_prefix("bright-").and_then(named) {
+            return Ok(Self::Bright(n));
+        }
+        if let Some(hex) = s.strip_prefix('#').filter(|h| h.len() == 6)
+            && let Ok(rgb) = u32::from_str_radix(hex, 16)
+        {
+            return Ok(Self::Rgb((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8));
+        }
+        if let Ok(n) = s.parse::<u8>() {
+            return Ok(Self::Indexed(n));
+        }
+        Err(format!(
+            "invalid color '{s}' (expected a name such as red or bright-cyan, #rrggbb or 0-255)"
+        ))
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Style {
+    pub fg: Option<Color>,
+    pub bg: Option<Color>,
+    pub bold: bool,
+    pub dim: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub reverse: bool,
+}
+
+impl Style {
+    pub fn fg(color: Color) -> Self {
+        Self {
+            fg: Some(color),
+            ..Self::default()
+        }
+    }
+
+    pub fn dim() -> Self {
+        Self {
+            dim: true,
+            ..Self::default()
+        }
+    }
+
+    /// Yellow and bold, for rules that name no style.
+    fn highlight() -> Self {
+        Self {
+            fg: Some(Color::Named(3)),
+            bold: true,
+            ..Self::default()
+        }
+    }
+
+    /// Applies the attributes named in `words`, comma separated: colors,
+    /// `on-COLOR` backgrounds and `bold`, `dim`, `italic`, `underline`,
+    /// `reverse`.
+    pub fn apply(&mut self, words: &str) -> Result<(), String> {
+        for word in words.split(',').map(str::trim).filter(|w| !w.is_empty()) {
+            match word.to_ascii_lowercase().as_str() {
+                "bold" => self.bold = true,
+                "dim" => self.dim = true,
+                "italic" => self.italic = true,
+                "underline" => self.underline = true,
+                "reverse" => self.reverse = true,
+                w => match w.strip_prefix("on-") {
+                    Some(bg) => self.bg = Some(bg.parse()?),
+                    None => self.fg = Some(w.parse()?),
+                },
+            }
+        }
+        Ok(())
+    }
+
+    fn sgr(&self) -> String {
+        let mut params = Vec::new();
+        for (on, code) in [
+            (self.bold, "1"),
+            (self.dim, "2"),
+            (self.italic, "3"),
+            (self.underline, "4"),
+            (self.reverse, "7"),
+        ] {
+            if on {
+                params.push(code.to_string());
+            }
+        }
+        params.extend(self.fg.map(|c| c.sgr(30)));
+        params.extend(self.bg.map(|c| c.sgr(40)));
+        if params.is_empty() {
+            return String::new();
+        }
+        format!("\x1b[{}m", params.join(";"))
+    }
+}
+
+impl FromStr for Style {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut style = Self::default();
+        style.apply(s)?;
+        if style == Self::default() {
+            return Err(format!("invalid style '{s}'"));
+        }
+        Ok(style)
+    }
+}
+
+/// A pattern and how to show what it matches. When the regex has capture
+/// groups only the groups are styled, otherwise the whole match.
+#[derive(Debug, Clone)]
+pub struct HighlightRule {
+    regex: Regex,
+    style: Style,
+}
+
+impl HighlightRule {
+    pub fn regex(pattern: &str, ignore_case: bool, style: Option<Style>) -> Result<Self, String> {
+        let regex = RegexBuilder::new(pattern)
+            .case_insensitive(ignore_case)
+            .build()
+            .map_err(|e| format!("invalid highlight regex /{pattern}/: {}", regex_reason(&e)))?;
+        Ok(Self {
+            regex,
+            style: style.unwrap_or_else(Style::highlight),
+        })
+    }
+
+    pub fn text(text: &str, style: Option<Style>) -> Result<Self, String> {
+        if text.is_empty() {
+            return Err("empty highlight text".to_string());
+        }
+        Self::regex(&regex::escape(text), false, style)
+    }
+
+    /// Byte ranges of `text` to style.
+    fn spans<'a>(&'a self, text: &'a str) -> Box<dyn Iterator<Item = (usize, usize)> + 'a> {
+        if self.regex.captures_len() > 1 {
+            Box::new(self.regex.captures_iter(text).flat_map(|caps| {
+                caps.iter()
+                    .skip(1)
+                    .flatten()
+                    .map(|m| (m.start(), m.end()))
+                    .collect::<Vec<_>>()
+            }))
+        } else {
+            Box::new(self.regex.find_iter(text).map(|m| (m.start(), m.end())))
+        }
+    }
+}
+
+impl FromStr for HighlightRule {
+    type Err = String;
+
+    /// `TEXT[=STYLE]` or `/REGEX/[i][=STYLE]`; see `Style::apply`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(body) = s.strip_prefix('/') {
+            let mut pattern = String::new();
+            let mut chars = body.char_indices();
+            let rest = loop {
+                match chars.next() {
+                    None => return Err(format!("unterminated highlight regex '{s}'")),
+                    Some((_, '\\')) if chars.as_str().starts_with('/') => {
+                        pattern.push('/');
+                        chars.next();
+                    }
+                    Some((i, '/')) => break &body[i + 1..],
+                    Some((_, c)) => pattern.push(c),
+                }
+            };
+            let (flags, style) = rest.split_once('=').unwrap_or((rest, ""));
+            let ignore_case = match flags {
+                "" => false,
+                "i" => true,
+                other => return Err(format!("unknown highlight flags '{other}' in '{s}'")),
+            };
+            let style = (!style.is_empty()).then(|| style.parse()).transpose()?;
+            return Self::regex(&pattern, ignore_case, style);
+        }
+
+        // Text may itself contain `=`: only a valid style after the last one
+        // is taken as the style.
+        match s.rsplit_once('=') {
+            Some((text, style)) if !text.is_empty() => match style.parse() {
+                Ok(style) => Self::text(text, Some(style)),
+                Err(_) => Self::text(s, None),
+            },
+            _ => Self::text(s, None),
+        }
+    }
+}
+
+/// Applies highlight rules to a line in one pass. Matching runs on the text
+/// without the escape sequences and control characters the device sent,
+/// which are copied through untouched. Where rules overlap, the earlier rule wins.
+#[derive(Debug, Clone, Default)]
+pub struct Highlighter {
+    rules: Vec<HighlightRule>,
+}
+
+impl Highlighter {
+    pub fn new(rules: Vec<HighlightRule>) -> Self {
+        Self { rules }
+    }
+
+    /// Renders `raw` in the `base` style with the highlights on top.
+    pub fn render(&self, raw: &str, base: Option<&Style>) -> String {
+        let base = base.map(Style::sgr).unwrap_or_default();
+        if self.rules.is_empty() {
+            return if base.is_empty() {
+                raw.to_string()
+            } else {
+                format!("{base}{raw}{RESET}")
+            };
+        }
+
+        let (visible, at) = ansi::strip_mapped(raw);
+        let mut owner = vec![None; visible.len()];
+        for (i, rule) in self.rules.iter().enumerate() {
+            for (start, end) in rule.spans(&visible) {
+                for o in owner[start..end].iter_mut().filter(|o| o.is_none()) {
+                    *o = Some(i);
+                }
+            }
+        }
+
+        let mut out = String::with_capacity(raw.len() + 32);
+        out.push_str(&base);
+        let mut active: Option<usize> = None;
+        let mut copied = 0;
+        for (v, c) in visible.char_indices() {
+            let r = at[v];
+            if copied < r {
+                // Escape sequences from the device; our styles go back on
+                // after them in case they reset them.
+                out.push_str(&raw[copied..r]);
+                out.push_str(&base);
+                if let Some(i) = active {
+                    out.push_str(&self.rules[i].style.sgr());
+                }
+            }
+            if owner[v] != active {
+                if active.is_some() {
+                    out.push_str(RESET);
+                    out.push_str(&base);
+                }
+                if let Some(i) = owner[v] {
+                    out.push_str(&self.rules[i].style.sgr());
+                }
+                active = owner[v];
+            }
+            out.push(c);
+            copied = r + c.len_utf8();
+        }
+        out.push_str(&raw[copied..]);
+        if active.is_some() || !base.is_empty() {
+            out.push_str(RESET);
+        }
+        out
+    }
+}
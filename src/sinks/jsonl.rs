@@ -0,0 +1,147 @@
+use crate::core::{LogLevel, PortState, SourceId};
+use crate::processing::ProcessedEvent;
+use crate::sinks::rotate::{RotatingFile, RotationPolicy};
+use crate::sinks::{EventSink, RenderOptions};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::Serialize;
+
+use std::borrow::Cow;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+/// Writes one JSON object per event, for jq and other tooling.
+pub struct JsonlSink {
+    w: Mutex<RotatingFile>,
+    render: RenderOptions,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Record<'a> {
+    Line {
+        ts: String,
+        port: &'a str,
+        alias: Option<&'a str>,
+        level: Option<&'static str>,
+        /// Seconds, see `TimestampFormat`.
+        device_ts: Option<f64>,
+        raw: Cow<'a, str>,
+    },
+    /// A binary frame, its bytes as lowercase hex.
+    Frame {
+        ts: String,
+        port: &'a str,
+        alias: Option<&'a str>,
+        offset: u64,
+        len: usize,
+        hex: String,
+    },
+    System {
+        ts: String,
+        level: &'static str,
+        message: &'a str,
+    },
+    PortState {
+        ts: String,
+        port: &'a str,
+        alias: Option<&'a str>,
+        state: &'static str,
+    },
+    Echo {
+        ts: String,
+        port: &'a str,
+        alias: Option<&'a str>,
+        text: &'a str,
+    },
+}
+
+impl JsonlSink {
+    pub fn new(
+        path: PathBuf,
+        rotation: RotationPolicy,
+        render: RenderOptions,
+    ) -> std::io::Result<Self> {
+        Ok(Self {
+            w: Mutex::new(RotatingFile::open(path, rotation)?),
+            render,
+        })
+    }
+}
+
+impl EventSink for JsonlSink {
+    fn emit(&self, event: &ProcessedEvent) {
+        let Some(line) = render(event, self.render) else {
+            return;
+        };
+
+        if let Ok(mut w) = self.w.lock() {
+            let _ = w.write_record(&line);
+        }
+    }
+}
+
+/// Serializes an event as one JSON Lines record.
+pub(crate) fn render(event: &ProcessedEvent, options: RenderOptions) -> Option<String> {
+    let record = match event {
+        ProcessedEvent::Line {
+            ts,
+            source,
+            level,
+            device_ts,
+            raw,
+        } => Record::Line {
+            ts: fmt_ts(*ts),
+            port: &source.port,
+            alias: alias(source),
+            level: level.map(LogLevel::as_str),
+            device_ts: device_ts.map(|d| d.as_secs_f64()),
+            raw: options.text(raw),
+        },
+        ProcessedEvent::Frame {
+            ts,
+            source,
+            offset,
+            bytes,
+        } => Record::Frame {
+            ts: fmt_ts(*ts),
+            port: &source.port,
+            alias: alias(source),
+            offset: *offset,
+            len: bytes.len(),
+            hex: bytes.iter().map(|b| format!("{b:02x}")).collect(),
+        },
+        ProcessedEvent::System { ts, level, message } => Record::System {
+            ts: fmt_ts(*ts),
+            level: level.as_str(),
+            message,
+        },
+        ProcessedEvent::PortState { ts, source, state } => Record::PortState {
+            ts: fmt_ts(*ts),
+            port: &source.port,
+            alias: alias(source),
+            state: match state {
+                PortState::Connected => "connected",
+                PortState::Disconnected => "disconnected",
+            },
+        },
+        ProcessedEvent::Echo { ts, source, text } => Record::Echo {
+            ts: fmt_ts(*ts),
+            port: &source.port,
+            alias: alias(source),
+            text,
+        },
+    };
+
+    serde_json::to_string(&record).ok()
+}
+
+fn fmt_ts(ts: SystemTime) -> String {
+    let dt: DateTime<Utc> = ts.into();
+    dt.to_rfc3339_opts(SecondsFormat::Micros, true)
+}
+
+fn alias(source: &SourceId) -> Option<&str> {
+    source.alias.as_deref()
+}
@@ -1,8 +1,15 @@
 pub mod file;
+pub mod highlight;
+pub mod jsonl;
+pub mod per_port;
+pub mod rotate;
 pub mod stdout;
+pub mod tui;
 
-use crate::processing::ProcessedEvent;
+use crate::processing::{Filter, ProcessedEvent, ansi};
+use std::borrow::Cow;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::mpsc;
 use tokio::task::JoinHandle;
 
@@ -10,6 +17,58 @@ pub trait EventSink: Send + Sync + 'static {
     fn emit(&self, event: &ProcessedEvent);
 }
 
+/// How the file sinks write events.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RenderOptions {
+    /// Keep the escape sequences of device lines instead of writing plain
+    /// text.
+    pub keep_ansi: bool,
+}
+
+impl RenderOptions {
+    /// A device line as written to a file.
+    pub(crate) fn text(self, raw: &str) -> Cow<'_, str> {
+        if self.keep_ansi {
+            Cow::Borrowed(raw)
+        } else {
+            ansi::strip(raw)
+        }
+    }
+}
+
+/// Bytes per row of a hex dump.
+const HEX_ROW: usize = 16;
+
+/// Formats bytes as hex dump rows: offset, bytes, and the bytes as text
+/// with control characters as visible symbols (`␍`, `␊`, `␛`, ...) and
+/// non-ASCII bytes as `.`. `offset` is the position of the first byte.
+pub(crate) fn hex_dump(bytes: &[u8], offset: u64) -> impl Iterator<Item = String> + '_ {
+    bytes.chunks(HEX_ROW).enumerate().map(move |(i, row)| {
+        let hex = row
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let ascii = row
+            .iter()
+            .map(|&b| match b {
+                0x20..=0x7E => b as char,
+                // Unicode control pictures, U+2400 to U+241F and U+2421.
+                0x00..=0x1F => char::from_u32(0x2400 + b as u32).unwrap_or('.'),
+                0x7F => '␡',
+                _ => '.',
+            })
+            .collect::<String>();
+        let at = offset + (i * HEX_ROW) as u64;
+        format!("{at:08x}  {hex:<47}  |{ascii}|")
+    })
+}
+
+/// A device timestamp as seconds since boot: `+12.345678`.
+pub(crate) fn fmt_device_ts(ts: Duration) -> String {
+    format!("+{}.{:06}", ts.as_secs(), ts.subsec_micros())
+}
+
 pub fn spawn_sink_worker(
     sink: Arc<dyn EventSink>,
     capacity: usize,
@@ -25,20 +84,67 @@ pub fn spawn_sink_worker(
     (tx, handle)
 }
 
+/// A sink's queue and the events it takes.
+pub struct Route {
+    tx: mpsc::Sender<Arc<ProcessedEvent>>,
+    filter: Option<Filter>,
+    system: bool,
+}
+
+impl Route {
+    pub fn new(tx: mpsc::Sender<Arc<ProcessedEvent>>) -> Self {
+        Self {
+            tx,
+            filter: None,
+            system: false,
+        }
+    }
+
+    pub fn with_filter(mut self, filter: Option<Filter>) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Lets system messages and port state changes past the filter, for the
+    /// terminal.
+    pub fn with_system(mut self, always: bool) -> Self {
+        self.system = always;
+        self
+    }
+
+    fn wants(&self, event: &ProcessedEvent) -> bool {
+        let system = matches!(
+            event,
+            ProcessedEvent::System { .. } | ProcessedEvent::PortState { .. }
+        );
+        (self.system && system) || self.filter.as_ref().is_none_or(|f| f.allows(event))
+    }
+}
+
+/// Copies each event to every sink whose route takes it. With
+/// `backpressure` a slow sink holds up the others instead of missing events.
 pub fn spawn_fanout(
     mut rx: mpsc::Receiver<ProcessedEvent>,
-    sinks: Vec<mpsc::Sender<Arc<ProcessedEvent>>>,
+    routes: Vec<Route>,
+    backpressure: bool,
 ) -> JoinHandle<()> {
     tokio::spawn(async move {
         while let Some(evt) = rx.recv().await {
             let evt = Arc::new(evt);
 
-            for tx in sinks.iter() {
-                let _ = tx.try_send(evt.clone());
+            for route in routes.iter().filter(|r| r.wants(&evt)) {
+                if backpressure {
+                    let _ = route.tx.send(evt.clone()).await;
+                } else {
+                    let _ = route.tx.try_send(evt.clone());
+                }
             }
         }
     })
 }
 
 pub use file::FileSink;
+pub use jsonl::JsonlSink;
+pub use per_port::PerPortSink;
 pub use stdout::StdoutSink;
+pub use tui::TuiSink;
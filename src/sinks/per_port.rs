@@ -0,0 +1,221 @@
+use crate::core::{AppEvent, LogLevel, OutputFormat, PortState, SourceId};
+use crate::processing::ProcessedEvent;
+use crate::sinks::rotate::{RotatingFile, RotationPolicy};
+use crate::sinks::{EventSink, RenderOptions, file, jsonl};
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+/// How long after a failed open a port's file is tried again, unless the
+/// port reconnects first.
+const RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Writes each port's traffic to its own file, named from a path template
+/// with `{alias}` and/or `{port}` placeholders. `{port}` is the device file
+/// name (`ttyACM0`), which `{alias}` also falls back to.
+///
+/// A port's file is only created once its first line (or echoed command)
+/// arrives. System events carry no source and are left to the combined file.
+/// Two ports whose names give the same path get numbered files, `-2`, `-3`,
+/// ... before the extension.
+pub struct PerPortSink {
+    template: String,
+    format: OutputFormat,
+    rotation: RotationPolicy,
+    render: RenderOptions,
+    events: Option<mpsc::Sender<AppEvent>>,
+    files: Mutex<Files>,
+}
+
+#[derive(Default)]
+struct Files {
+    ports: HashMap<SourceId, PortFile>,
+    /// Which port each path was given to.
+    owners: HashMap<PathBuf, SourceId>,
+}
+
+struct PortFile {
+    path: PathBuf,
+    state: FileState,
+}
+
+enum FileState {
+    Open(RotatingFile),
+    /// Not open since `at`; the error has been reported.
+    Failed {
+        at: Instant,
+    },
+}
+
+impl PerPortSink {
+    pub fn new(
+        template: PathBuf,
+        format: OutputFormat,
+        rotation: RotationPolicy,
+        render: RenderOptions,
+    ) -> Self {
+        Self {
+            template: template.to_string_lossy().into_owned(),
+            format,
+            rotation,
+            render,
+            events: None,
+            files: Mutex::new(Files::default()),
+        }
+    }
+
+    /// Reports files that cannot be opened or written as system messages.
+    pub fn with_events(mut self, events: mpsc::Sender<AppEvent>) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    fn path_for(&self, source: &SourceId) -> PathBuf {
+        let port = Path::new(&source.port)
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| source.port.clone());
+
+        let alias = source.alias.as_deref().unwrap_or(&port);
+
+        PathBuf::from(
+            self.template
+                .replace("{alias}", &file_component(alias))
+                .replace("{port}", &file_component(&port)),
+        )
+    }
+
+    /// The template's path for `source`, numbered if another port has it.
+    fn claim(&self, files: &mut Files, source: &SourceId) -> PathBuf {
+        let base = self.path_for(source);
+        let mut path = base.clone();
+        let mut n = 1;
+        while files.owners.get(&path).is_some_and(|owner| owner != source) {
+            n += 1;
+            path = numbered(&base, n);
+        }
+        if n > 1 {
+            self.system(
+                LogLevel::Warn,
+                format!(
+                    "{}: {} is taken by another port, writing {} instead",
+                    source.label(),
+                    base.display(),
+                    path.display()
+                ),
+            );
+        }
+        files.owners.insert(path.clone(), source.clone());
+        path
+    }
+
+    fn open(&self, source: &SourceId, path: &Path) -> FileState {
+        match RotatingFile::open(path.to_path_buf(), self.rotation.clone()) {
+            Ok(w) => FileState::Open(w),
+            Err(e) => {
+                self.system(
+                    LogLevel::Error,
+                    format!(
+                        "{}: cannot open {}: {e}, retrying",
+                        source.label(),
+                        path.display()
+                    ),
+                );
+                FileState::Failed { at: Instant::now() }
+            }
+        }
+    }
+
+    fn system(&self, level: LogLevel, message: String) {
+        if let Some(events) = &self.events {
+            let _ = events.try_send(AppEvent::System { level, message });
+        }
+    }
+}
+
+impl EventSink for PerPortSink {
+    fn emit(&self, event: &ProcessedEvent) {
+        let (source, opens) = match event {
+            ProcessedEvent::Line { source, .. }
+            | ProcessedEvent::Frame { source, .. }
+            | ProcessedEvent::Echo { source, .. } => (source, true),
+            ProcessedEvent::PortState { source, .. } => (source, false),
+            ProcessedEvent::System { .. } => return,
+        };
+        let reconnected = matches!(
+            event,
+            ProcessedEvent::PortState {
+                state: PortState::Connected,
+                ..
+            }
+        );
+
+        let record = match self.format {
+            OutputFormat::Text => file::render(event, self.render),
+            OutputFormat::Jsonl => jsonl::render(event, self.render),
+        };
+
+        let Ok(mut files) = self.files.lock() else {
+            return;
+        };
+        if !files.ports.contains_key(source) {
+            if !opens {
+                return;
+            }
+            let path = self.claim(&mut files, source);
+            let state = self.open(source, &path);
+            files.ports.insert(source.clone(), PortFile { path, state });
+        }
+        let Some(file) = files.ports.get_mut(source) else {
+            return;
+        };
+
+        if let FileState::Failed { at } = file.state
+            && (reconnected || (opens && at.elapsed() >= RETRY_INTERVAL))
+        {
+            // Only the first failure is reported.
+            file.state = match RotatingFile::open(file.path.clone(), self.rotation.clone()) {
+                Ok(w) => {
+                    self.system(
+                        LogLevel::Info,
+                        format!("{}: writing {} again", source.label(), file.path.display()),
+                    );
+                    FileState::Open(w)
+                }
+                Err(_) => FileState::Failed { at: Instant::now() },
+            };
+        }
+
+        if let (FileState::Open(w), Some(record)) = (&mut file.state, record) {
+            let _ = w.write_record(&record);
+        }
+    }
+}
+
+/// `logs/gps.log` as `logs/gps-2.log`.
+fn numbered(path: &Path, n: usize) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(ext) => format!("{stem}-{n}.{}", ext.to_string_lossy()),
+        None => format!("{stem}-{n}"),
+    };
+    path.with_file_name(name)
+}
+
+/// Makes a label safe to use as a single path component.
+fn file_component(s: &str) -> String {
+    s.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>()
+        .trim_start_matches('.')
+        .to_string()
+}
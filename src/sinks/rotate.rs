@@ -0,0 +1,378 @@
+use chrono::{DateTime, Timelike, Utc};
+use regex::Regex;
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+use std::thread::JoinHandle;
+
+const ROTATED_TS: &str = "%Y%m%dT%H%M%S";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RotationPolicy {
+    pub max_bytes: Option<u64>,
+    pub every: Option<RotateEvery>,
+    /// Rotated segments to keep; older ones are deleted.
+    pub keep: Option<usize>,
+    pub compress: Compression,
+    /// Append to an existing file on startup instead of truncating it.
+    pub append: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RotateEvery {
+    Hourly,
+    Daily,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Compression {
+    #[default]
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl FromStr for RotateEvery {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "hourly" | "hour" => Ok(Self::Hourly),
+            "daily" | "day" => Ok(Self::Daily),
+            other => Err(format!(
+                "invalid rotation period '{other}' (expected hourly or daily)"
+            )),
+        }
+    }
+}
+
+impl FromStr for Compression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "none" => Ok(Self::None),
+            "gzip" | "gz" => Ok(Self::Gzip),
+            "zstd" | "zst" => Ok(Self::Zstd),
+            other => Err(format!(
+                "invalid compression '{other}' (expected none, gzip or zstd)"
+            )),
+        }
+    }
+}
+
+impl Compression {
+    fn extension(self) -> Option<&'static str> {
+        match self {
+            Self::None => None,
+            Self::Gzip => Some("gz"),
+            Self::Zstd => Some("zst"),
+        }
+    }
+}
+
+/// Parses a byte size such as `4096`, `512K`, `100M` or `2G` (binary units).
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let t = s.trim();
+    let split = t.find(|c: char| !c.is_ascii_digit()).unwrap_or(t.len());
+    let (digits, unit) = t.split_at(split);
+
+    let n = digits
+        .parse::<u64>()
+        .map_err(|_| format!("invalid size '{s}'"))?;
+    let mult: u64 = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1 << 10,
+        "M" | "MB" | "MIB" => 1 << 20,
+        "G" | "GB" | "GIB" => 1 << 30,
+        _ => return Err(format!("invalid size '{s}' (expected e.g. 512K, 100M, 1G)")),
+    };
+
+    match n.checked_mul(mult) {
+        Some(0) | None => Err(format!("invalid size '{s}'")),
+        Some(v) => Ok(v),
+    }
+}
+
+/// Expands `{date}`, `{time}` and `{datetime}` in a path template.
+pub fn render_template(template: &Path, now: DateTime<Utc>) -> PathBuf {
+    let s = template.to_string_lossy();
+    if !s.contains('{') {
+        return template.to_path_buf();
+    }
+    PathBuf::from(
+        s.replace("{date}", &now.format("%Y-%m-%d").to_string())
+            .replace("{time}", &now.format("%H%M%S").to_string())
+            .replace("{datetime}", &now.format(ROTATED_TS).to_string()),
+    )
+}
+
+/// A log file that rolls over by size and/or time.
+///
+/// Rotation happens between records, so a line is never split across two
+/// segments. The closed segment is renamed to `<name>.<start time>` and then
+/// optionally compressed on a background thread, which is waited for when
+/// the file is dropped.
+pub struct RotatingFile {
+    template: PathBuf,
+    policy: RotationPolicy,
+    path: PathBuf,
+    w: BufWriter<File>,
+    written: u64,
+    opened: DateTime<Utc>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl RotatingFile {
+    pub fn open(template: PathBuf, policy: RotationPolicy) -> io::Result<Self> {
+        // Compressions cut short by an earlier exit; their originals are
+        // still in place.
+        for part in segments(&template).into_iter().filter(|p| is_part(p)) {
+            let _ = std::fs::remove_file(part);
+        }
+
+        let now = Utc::now();
+        let path = render_template(&template, now);
+        let (w, written) = open_segment(&path, policy.append)?;
+        Ok(Self {
+            template,
+            policy,
+            path,
+            w,
+            written,
+            opened: now,
+            workers: Vec::new(),
+        })
+    }
+
+    /// Writes `record` followed by a newline, rotating beforehand if due.
+    pub fn write_record(&mut self, record: &str) -> io::Result<()> {
+        let now = Utc::now();
+        if self.written > 0 && self.due(now) && self.rotate(now).is_err() {
+            // Keep writing to the current segment; try again next period.
+            self.written = 0;
+            self.opened = now;
+        }
+
+        self.w.write_all(record.as_bytes())?;
+        self.w.write_all(b"\n")?;
+        self.written += record.len() as u64 + 1;
+        Ok(())
+    }
+
+    fn due(&self, now: DateTime<Utc>) -> bool {
+        if self.policy.max_bytes.is_some_and(|max| self.written >= max) {
+            return true;
+        }
+        match self.policy.every {
+            Some(every) => period_start(self.opened, every) != period_start(now, every),
+            None => false,
+        }
+    }
+
+    fn rotate(&mut self, now: DateTime<Utc>) -> io::Result<()> {
+        self.w.flush()?;
+
+        let rotated = unused_path(&format!(
+            "{}.{}",
+            self.path.display(),
+            self.opened.format(ROTATED_TS)
+        ));
+        std::fs::rename(&self.path, &rotated)?;
+
+        let path = render_template(&self.template, now);
+        let (w, written) = open_segment(&path, true)?;
+        self.w = w;
+        self.path = path;
+        self.written = written;
+        self.opened = now;
+
+        let compress = self.policy.compress;
+        let keep = self.policy.keep;
+        let template = self.template.clone();
+        self.workers.retain(|w| !w.is_finished());
+        self.workers.push(std::thread::spawn(move || {
+            if let Some(ext) = compress.extension() {
+                let _ = compress_file(&rotated, compress, ext);
+            }
+            if let Some(keep) = keep {
+                prune(&template, keep);
+            }
+        }));
+
+        Ok(())
+    }
+}
+
+impl Drop for RotatingFile {
+    fn drop(&mut self) {
+        let _ = self.w.flush();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn open_segment(path: &Path, append: bool) -> io::Result<(BufWriter<File>, u64)> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let f = if append {
+        OpenOptions::new().create(true).append(true).open(path)?
+    } else {
+        File::create(path)?
+    };
+    let len = f.metadata().map(|m| m.len()).unwrap_or(0);
+    Ok((BufWriter::new(f), len))
+}
+
+fn period_start(t: DateTime<Utc>, every: RotateEvery) -> DateTime<Utc> {
+    let t = t
+        .with_nanosecond(0)
+        .and_then(|t| t.with_second(0))
+        .and_then(|t| t.with_minute(0))
+        .unwrap_or(t);
+    match every {
+        RotateEvery::Hourly => t,
+        RotateEvery::Daily => t.with_hour(0).unwrap_or(t),
+    }
+}
+
+/// Picks a name for a rotated segment that clashes with no earlier segment,
+/// compressed or not, by appending `.1`, `.2`, ... when needed.
+fn unused_path(base: &str) -> PathBuf {
+    let taken = |p: &str| {
+        ["", ".gz", ".zst"]
+            .iter()
+            .any(|ext| Path::new(&format!("{p}{ext}")).exists())
+    };
+    if !taken(base) {
+        return PathBuf::from(base);
+    }
+    (1..)
+        .map(|n| format!("{base}.{n}"))
+        .find(|p| !taken(p))
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(base))
+}
+
+/// Compresses `path` into `path.<ext>` and removes the original once the
+/// compressed copy is complete.
+fn compress_file(path: &Path, compress: Compression, ext: &str) -> io::Result<()> {
+    let out_path = PathBuf::from(format!("{}.{ext}", path.display()));
+    let part = PathBuf::from(format!("{}.part", out_path.display()));
+
+    let mut input = File::open(path)?;
+    let output = BufWriter::new(File::create(&part)?);
+
+    match compress {
+        Compression::Gzip => {
+            let mut enc = flate2::write::GzEncoder::new(output, flate2::Compression::default());
+            io::copy(&mut input, &mut enc)?;
+            enc.finish()?.flush()?;
+        }
+        Compression::Zstd => {
+            let mut enc = zstd::Encoder::new(output, 0)?;
+            io::copy(&mut input, &mut enc)?;
+            enc.finish()?.flush()?;
+        }
+        Compression::None => return Ok(()),
+    }
+
+    std::fs::rename(&part, &out_path)?;
+    std::fs::remove_file(path)
+}
+
+/// Deletes all but the `keep` newest rotated segments of `template`.
+fn prune(template: &Path, keep: usize) {
+    let mut segments = segments(template)
+        .into_iter()
+        .filter(|p| !is_part(p))
+        .filter_map(|p| Some((std::fs::metadata(&p).ok()?.modified().ok()?, p)))
+        .collect::<Vec<_>>();
+
+    segments.sort_by_key(|(modified, _)| std::cmp::Reverse(*modified));
+    for (_, path) in segments.into_iter().skip(keep) {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+/// Every rotated segment of `template`, including unfinished `.part`
+/// compressions, in all the directories its placeholders expand to.
+fn segments(template: &Path) -> Vec<PathBuf> {
+    let Some(name) = template.file_name() else {
+        return Vec::new();
+    };
+    let Some(name) = placeholder_regex(
+        &name.to_string_lossy(),
+        r"\.\d{8}T\d{6}(\.\d+)?(\.gz|\.zst)?(\.part)?",
+    ) else {
+        return Vec::new();
+    };
+
+    let mut dirs = vec![PathBuf::new()];
+    for component in template.parent().into_iter().flat_map(Path::components) {
+        let Component::Normal(part) = component else {
+            dirs.iter_mut().for_each(|d| d.push(component));
+            continue;
+        };
+        let part = part.to_string_lossy();
+        if !has_placeholder(&part) {
+            dirs.iter_mut().for_each(|d| d.push(&*part));
+            continue;
+        }
+        let Some(re) = placeholder_regex(&part, "") else {
+            return Vec::new();
+        };
+        dirs = dirs
+            .iter()
+            .flat_map(|d| matching(d, &re))
+            .filter(|p| p.is_dir())
+            .collect();
+    }
+
+    dirs.iter()
+        .flat_map(|d| matching(d, &name))
+        .filter(|p| p.is_file())
+        .collect()
+}
+
+/// The entries of `dir` (the working directory when empty) whose names
+/// match `re`.
+fn matching(dir: &Path, re: &Regex) -> Vec<PathBuf> {
+    let read = if dir.as_os_str().is_empty() {
+        std::fs::read_dir(".")
+    } else {
+        std::fs::read_dir(dir)
+    };
+    let Ok(entries) = read else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter(|e| re.is_match(&e.file_name().to_string_lossy()))
+        .map(|e| dir.join(e.file_name()))
+        .collect()
+}
+
+fn has_placeholder(s: &str) -> bool {
+    ["{date}", "{time}", "{datetime}"]
+        .iter()
+        .any(|p| s.contains(p))
+}
+
+/// A regex for one path component of the template, placeholders matching
+/// any text, followed by `suffix`.
+fn placeholder_regex(component: &str, suffix: &str) -> Option<Regex> {
+    let pattern = regex::escape(component)
+        .replace(r"\{date\}", ".+")
+        .replace(r"\{time\}", ".+")
+        .replace(r"\{datetime\}", ".+");
+    Regex::new(&format!("^{pattern}{suffix}$")).ok()
+}
+
+fn is_part(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e == "part")
+}
@@ -1,6 +1,7 @@
 use crate::core::{LogLevel, SourceId};
 use crate::processing::ProcessedEvent;
-use crate::sinks::EventSink;
+use crate::sinks::highlight::{Color, HighlightRule, Highlighter, Style};
+use crate::sinks::{EventSink, fmt_device_ts, hex_dump};
 
 use chrono::{DateTime, SecondsFormat, Utc};
 use owo_colors::OwoColorize;
@@ -11,26 +12,18 @@ use std::time::SystemTime;
 
 #[derive(Clone, Default)]
 pub struct StdoutSink {
-    highlights: Vec<(String, String)>, // (pattern, colored)
+    highlighter: Highlighter,
 }
 
 impl StdoutSink {
     pub fn new() -> Self {
         Self {
-            highlights: Vec::new(),
+            highlighter: Highlighter::default(),
         }
     }
 
-    pub fn with_highlights(mut self, highlights: Vec<String>) -> Self {
-        self.highlights = highlights
-            .into_iter()
-            .map(|s| s.trim().to_string())
-            .filter(|s| !s.is_empty())
-            .map(|p| {
-                let colored = format!("{}", p.yellow().bold());
-                (p, colored)
-            })
-            .collect();
+    pub fn with_highlights(mut self, rules: Vec<HighlightRule>) -> Self {
+        self.highlighter = Highlighter::new(rules);
         self
     }
 }
@@ -38,11 +31,39 @@ impl StdoutSink {
 impl EventSink for StdoutSink {
     fn emit(&self, event: &ProcessedEvent) {
         match event {
-            ProcessedEvent::Line { ts, source, raw } => {
+            ProcessedEvent::Line {
+                ts,
+                source,
+                level,
+                device_ts,
+                raw,
+            } => {
                 let ts = fmt_ts(*ts).dimmed().to_string();
+                let dev = device_ts
+                    .map(|d| format!(" [{}]", fmt_device_ts(d).dimmed()))
+                    .unwrap_or_default();
                 let src = fmt_source(source);
-                let raw = apply_highlights(raw, &self.highlights);
-                println!("[{ts}] {src} │ {raw}");
+                // Lines the device colored itself keep their colors.
+                let base = level
+                    .filter(|_| !raw.contains('\x1b'))
+                    .and_then(message_style);
+                let raw = self.highlighter.render(raw, base.as_ref());
+                match level {
+                    Some(level) => println!("[{ts}]{dev} {src} {} │ {raw}", fmt_level(*level)),
+                    None => println!("[{ts}]{dev} {src} │ {raw}"),
+                }
+            }
+            ProcessedEvent::Frame {
+                ts,
+                source,
+                offset,
+                bytes,
+            } => {
+                let ts = fmt_ts(*ts).dimmed().to_string();
+                let src = fmt_source(source);
+                for row in hex_dump(bytes, *offset) {
+                    println!("[{ts}] {src} │ {}", row.cyan());
+                }
             }
             ProcessedEvent::System { ts, level, message } => {
                 let ts = fmt_ts(*ts).dimmed().to_string();
@@ -50,6 +71,13 @@ impl EventSink for StdoutSink {
                 let lvl = fmt_level(*level);
                 eprintln!("[{ts}] {sys} {lvl} ▸ {message}");
             }
+            ProcessedEvent::Echo { ts, source, text } => {
+                let ts = fmt_ts(*ts).dimmed().to_string();
+                let src = fmt_source(source);
+                let text = text.italic().to_string();
+                println!("[{ts}] {src} {} {text}", "»".bright_blue().bold());
+            }
+            ProcessedEvent::PortState { .. } => {}
         }
     }
 }
@@ -69,17 +97,31 @@ fn fmt_level(level: LogLevel) -> String {
     }
 }
 
+/// Errors and warnings stand out; trace and debug chatter recedes.
+fn message_style(level: LogLevel) -> Option<Style> {
+    match level {
+        LogLevel::Trace | LogLevel::Debug => Some(Style::dim()),
+        LogLevel::Info => None,
+        LogLevel::Warn => Some(Style::fg(Color::Named(3))),
+        LogLevel::Error => Some(Style::fg(Color::Named(1))),
+    }
+}
+
 fn fmt_source(source: &SourceId) -> String {
     let label = source.label();
     let tag = format!("[{label}]");
 
+    let (r, g, b) = source_color(source);
+    tag.truecolor(r, g, b).bold().to_string()
+}
+
+pub(crate) fn source_color(source: &SourceId) -> (u8, u8, u8) {
     let key = match &source.alias {
         Some(a) => format!("{}|{a}", source.port),
         None => format!("{}|", source.port),
     };
 
-    let (r, g, b) = color_from_key(&key);
-    tag.truecolor(r, g, b).bold().to_string()
+    color_from_key(&key)
 }
 
 fn color_from_key(key: &str) -> (u8, u8, u8) {
@@ -105,20 +147,3 @@ fn color_from_key(key: &str) -> (u8, u8, u8) {
 
     (r, g, b)
 }
-
-fn apply_highlights(raw: &str, patterns: &[(String, String)]) -> String {
-    if patterns.is_empty() {
-        return raw.to_string();
-    }
-
-    let mut s = raw.to_string();
-    for (p, colored) in patterns {
-        if p.is_empty() {
-            continue;
-        }
-        if s.contains(p) {
-            s = s.replace(p, colored);
-        }
-    }
-    s
-}
@@ -0,0 +1,146 @@
+pub mod state;
+pub mod ui;
+
+use crate::core::SourceId;
+use crate::processing::ProcessedEvent;
+use crate::runtime::{CommandRouter, Shutdown, ShutdownHandle};
+use crate::sinks::EventSink;
+
+use ratatui::DefaultTerminal;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+pub use state::{InputLine, TuiState, ViewMode};
+
+const FRAME_INTERVAL: Duration = Duration::from_millis(50);
+
+pub struct TuiSink {
+    state: Arc<Mutex<TuiState>>,
+    commands: CommandRouter,
+}
+
+impl TuiSink {
+    pub fn new(sources: Vec<SourceId>) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(TuiState::new(sources))),
+            commands: CommandRouter::new(),
+        }
+    }
+
+    pub fn with_commands(mut self, commands: CommandRouter) -> Self {
+        self.commands = commands;
+        self
+    }
+
+    /// Takes over the terminal and runs the render/input loop until shutdown.
+    ///
+    /// Quitting from the UI triggers `handle`, since Ctrl+C no longer raises
+    /// SIGINT once the terminal is in raw mode.
+    pub fn spawn_ui(
+        &self,
+        terminal: DefaultTerminal,
+        shutdown: Shutdown,
+        handle: ShutdownHandle,
+    ) -> JoinHandle<()> {
+        let state = self.state.clone();
+        let commands = self.commands.clone();
+        tokio::task::spawn_blocking(move || {
+            run_ui(terminal, state, commands, shutdown, handle);
+            ratatui::restore();
+        })
+    }
+}
+
+impl EventSink for TuiSink {
+    fn emit(&self, event: &ProcessedEvent) {
+        if let Ok(mut s) = self.state.lock() {
+            s.push(event);
+        }
+    }
+}
+
+fn run_ui(
+    mut terminal: DefaultTerminal,
+    state: Arc<Mutex<TuiState>>,
+    commands: CommandRouter,
+    shutdown: Shutdown,
+    handle: ShutdownHandle,
+) {
+    while !shutdown.is_triggered() {
+        {
+            let Ok(mut s) = state.lock() else { return };
+            if terminal.draw(|f| ui::draw(f, &mut s)).is_err() {
+                handle.trigger();
+                return;
+            }
+        }
+
+        match event::poll(FRAME_INTERVAL) {
+            Ok(true) => {}
+            Ok(false) => continue,
+            Err(_) => {
+                handle.trigger();
+                return;
+            }
+        }
+
+        let Ok(Event::Key(key)) = event::read() else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+
+        let Ok(mut s) = state.lock() else { return };
+        s.notice = None;
+
+        if s.input.active {
+            match key.code {
+                KeyCode::Esc => s.input.close(),
+                KeyCode::Enter => {
+                    if let Some(line) = s.input.submit() {
+                        let target = s.selected_source().cloned();
+                        if let Err(e) = commands.dispatch(&line, target.as_ref()) {
+                            s.notice = Some(e.to_string());
+                        }
+                    }
+                }
+                KeyCode::Backspace => {
+                    s.input.buffer.pop();
+                }
+                KeyCode::Up => s.input.history_prev(),
+                KeyCode::Down => s.input.history_next(),
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    handle.trigger()
+                }
+                KeyCode::Char(c) => s.input.buffer.push(c),
+                _ => {}
+            }
+            continue;
+        }
+
+        let page = s.page.max(1);
+        match key.code {
+            KeyCode::Char('i') | KeyCode::Char(':') | KeyCode::Enter => s.input.open(),
+            KeyCode::Char('q') | KeyCode::Esc => handle.trigger(),
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => handle.trigger(),
+            KeyCode::Char('g') => s.mode = ViewMode::Grid,
+            KeyCode::Char('t') => s.mode = ViewMode::Tabs,
+            KeyCode::Char('m') => s.mode = ViewMode::Merged,
+            KeyCode::Char(' ') | KeyCode::Char('p') => s.toggle_pause(),
+            KeyCode::Tab | KeyCode::Right => s.select_next(),
+            KeyCode::BackTab | KeyCode::Left => s.select_prev(),
+            KeyCode::Char(c @ '1'..='9') => s.select(c as usize - '1' as usize),
+            KeyCode::Up | KeyCode::Char('k') => s.scroll_up(1),
+            KeyCode::Down | KeyCode::Char('j') => s.scroll_down(1),
+            KeyCode::PageUp => s.scroll_up(page),
+            KeyCode::PageDown => s.scroll_down(page),
+            KeyCode::Home => s.scroll_top(),
+            KeyCode::End => s.scroll_bottom(),
+            _ => {}
+        }
+    }
+}
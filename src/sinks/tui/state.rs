@@ -0,0 +1,397 @@
+use crate::core::{LogLevel, PortState, SourceId};
+use crate::processing::{ProcessedEvent, ansi};
+use crate::sinks::hex_dump;
+
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant, SystemTime};
+
+const SCROLLBACK_LINES: usize = 10_000;
+const RATE_WINDOW: Duration = Duration::from_secs(5);
+const HISTORY_LINES: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ViewMode {
+    Grid,
+    Tabs,
+    Merged,
+}
+
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub ts: SystemTime,
+    pub device_ts: Option<Duration>,
+    pub source: Option<SourceId>,
+    pub level: Option<LogLevel>,
+    pub echo: bool,
+    pub text: String,
+}
+
+/// Bounded scrollback with an optional anchor.
+///
+/// `anchor` is the absolute index one past the last visible entry; `None`
+/// means the view follows the tail.
+pub struct Scrollback {
+    entries: VecDeque<Entry>,
+    pushed: u64,
+    anchor: Option<u64>,
+}
+
+impl Scrollback {
+    fn new() -> Self {
+        Self {
+            entries: VecDeque::new(),
+            pushed: 0,
+            anchor: None,
+        }
+    }
+
+    fn push(&mut self, entry: Entry) {
+        if self.entries.len() == SCROLLBACK_LINES {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+        self.pushed += 1;
+    }
+
+    fn first_index(&self) -> u64 {
+        self.pushed - self.entries.len() as u64
+    }
+
+    fn end(&self) -> u64 {
+        self.anchor.unwrap_or(self.pushed).max(self.first_index())
+    }
+
+    pub fn is_following(&self) -> bool {
+        self.anchor.is_none()
+    }
+
+    /// Returns up to `height` entries ending at the current view position.
+    pub fn visible(&self, height: usize) -> impl Iterator<Item = &Entry> {
+        let end = (self.end() - self.first_index()) as usize;
+        let start = end.saturating_sub(height);
+        self.entries.range(start..end)
+    }
+
+    fn scroll_up(&mut self, n: usize) {
+        let end = self.end().saturating_sub(n as u64).max(self.first_index());
+        self.anchor = Some(end);
+    }
+
+    fn scroll_down(&mut self, n: usize, paused: bool) {
+        let Some(end) = self.anchor else { return };
+        let end = end + n as u64;
+        self.anchor = if end >= self.pushed && !paused {
+            None
+        } else {
+            Some(end.min(self.pushed))
+        };
+    }
+
+    fn scroll_top(&mut self, height: usize) {
+        self.anchor = Some((self.first_index() + height as u64).min(self.pushed));
+    }
+
+    fn freeze(&mut self) {
+        if self.anchor.is_none() {
+            self.anchor = Some(self.pushed);
+        }
+    }
+
+    fn follow(&mut self) {
+        self.anchor = None;
+    }
+}
+
+pub struct Pane {
+    pub source: SourceId,
+    pub state: Option<PortState>,
+    pub lines: Scrollback,
+    arrivals: VecDeque<Instant>,
+}
+
+impl Pane {
+    fn new(source: SourceId) -> Self {
+        Self {
+            source,
+            state: None,
+            lines: Scrollback::new(),
+            arrivals: VecDeque::new(),
+        }
+    }
+
+    fn record_arrival(&mut self, now: Instant) {
+        self.arrivals.push_back(now);
+        self.trim_arrivals(now);
+    }
+
+    fn trim_arrivals(&mut self, now: Instant) {
+        while let Some(front) = self.arrivals.front() {
+            if now.duration_since(*front) > RATE_WINDOW {
+                self.arrivals.pop_front();
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// Lines per second over the last few seconds.
+    pub fn rate(&mut self, now: Instant) -> f64 {
+        self.trim_arrivals(now);
+        self.arrivals.len() as f64 / RATE_WINDOW.as_secs_f64()
+    }
+}
+
+/// Command prompt with a recall history of previously sent lines.
+#[derive(Default)]
+pub struct InputLine {
+    pub active: bool,
+    pub buffer: String,
+    history: Vec<String>,
+    recall: Option<usize>,
+}
+
+impl InputLine {
+    pub fn open(&mut self) {
+        self.active = true;
+        self.buffer.clear();
+        self.recall = None;
+    }
+
+    pub fn close(&mut self) {
+        self.active = false;
+        self.buffer.clear();
+        self.recall = None;
+    }
+
+    /// Closes the prompt and returns the entered line, recording it in history.
+    pub fn submit(&mut self) -> Option<String> {
+        let line = std::mem::take(&mut self.buffer);
+        self.close();
+        if line.trim().is_empty() {
+            return None;
+        }
+        if self.history.last() != Some(&line) {
+            self.history.push(line.clone());
+            if self.history.len() > HISTORY_LINES {
+                self.history.remove(0);
+            }
+        }
+        Some(line)
+    }
+
+    pub fn history_prev(&mut self) {
+        if self.history.is_empty() {
+            return;
+        }
+        let idx = match self.recall {
+            Some(i) => i.saturating_sub(1),
+            None => self.history.len() - 1,
+        };
+        self.recall = Some(idx);
+        self.buffer = self.history[idx].clone();
+    }
+
+    pub fn history_next(&mut self) {
+        let Some(i) = self.recall else { return };
+        if i + 1 < self.history.len() {
+            self.recall = Some(i + 1);
+            self.buffer = self.history[i + 1].clone();
+        } else {
+            self.recall = None;
+            self.buffer.clear();
+        }
+    }
+}
+
+pub struct TuiState {
+    pub panes: Vec<Pane>,
+    pub merged: Scrollback,
+    pub mode: ViewMode,
+    pub selected: usize,
+    pub paused: bool,
+    pub page: usize,
+    pub input: InputLine,
+    pub notice: Option<String>,
+    index: HashMap<SourceId, usize>,
+}
+
+impl TuiState {
+    pub fn new(sources: Vec<SourceId>) -> Self {
+        let mut state = Self {
+            panes: Vec::new(),
+            merged: Scrollback::new(),
+            mode: ViewMode::Grid,
+            selected: 0,
+            paused: false,
+            page: 1,
+            input: InputLine::default(),
+            notice: None,
+            index: HashMap::new(),
+        };
+        for source in sources {
+            state.pane_mut(&source);
+        }
+        state
+    }
+
+    fn pane_mut(&mut self, source: &SourceId) -> &mut Pane {
+        let idx = match self.index.get(source) {
+            Some(idx) => *idx,
+            None => {
+                let idx = self.panes.len();
+                self.panes.push(Pane::new(source.clone()));
+                self.index.insert(source.clone(), idx);
+                if self.paused {
+                    self.panes[idx].lines.freeze();
+                }
+                idx
+            }
+        };
+        &mut self.panes[idx]
+    }
+
+    pub fn push(&mut self, event: &ProcessedEvent) {
+        match event {
+            ProcessedEvent::Line {
+                ts,
+                source,
+                level,
+                device_ts,
+                raw,
+            } => {
+                let entry = Entry {
+                    ts: *ts,
+                    device_ts: *device_ts,
+                    source: Some(source.clone()),
+                    level: *level,
+                    echo: false,
+                    // Panes draw plain text; device colors would garble them.
+                    text: ansi::strip(raw).into_owned(),
+                };
+                let pane = self.pane_mut(source);
+                pane.record_arrival(Instant::now());
+                pane.lines.push(entry.clone());
+                self.merged.push(entry);
+            }
+            ProcessedEvent::Frame {
+                ts,
+                source,
+                offset,
+                bytes,
+            } => {
+                self.pane_mut(source).record_arrival(Instant::now());
+                for row in hex_dump(bytes, *offset) {
+                    let entry = Entry {
+                        ts: *ts,
+                        device_ts: None,
+                        source: Some(source.clone()),
+                        level: None,
+                        echo: false,
+                        text: row,
+                    };
+                    self.pane_mut(source).lines.push(entry.clone());
+                    self.merged.push(entry);
+                }
+            }
+            ProcessedEvent::System { ts, level, message } => {
+                self.merged.push(Entry {
+                    ts: *ts,
+                    device_ts: None,
+                    source: None,
+                    level: Some(*level),
+                    echo: false,
+                    text: message.clone(),
+                });
+            }
+            ProcessedEvent::Echo { ts, source, text } => {
+                let entry = Entry {
+                    ts: *ts,
+                    device_ts: None,
+                    source: Some(source.clone()),
+                    level: None,
+                    echo: true,
+                    text: text.clone(),
+                };
+                self.pane_mut(source).lines.push(entry.clone());
+                self.merged.push(entry);
+            }
+            ProcessedEvent::PortState { source, state, .. } => {
+                self.pane_mut(source).state = Some(*state);
+            }
+        }
+    }
+
+    /// Scrollback of the pane that keyboard navigation applies to.
+    fn focused_mut(&mut self) -> &mut Scrollback {
+        match self.mode {
+            ViewMode::Merged => &mut self.merged,
+            ViewMode::Grid | ViewMode::Tabs => match self.panes.get_mut(self.selected) {
+                Some(p) => &mut p.lines,
+                None => &mut self.merged,
+            },
+        }
+    }
+
+    /// Port that commands without an `@label` prefix are sent to.
+    pub fn selected_source(&self) -> Option<&SourceId> {
+        self.panes.get(self.selected).map(|p| &p.source)
+    }
+
+    pub fn select_next(&mut self) {
+        if !self.panes.is_empty() {
+            self.selected = (self.selected + 1) % self.panes.len();
+        }
+    }
+
+    pub fn select_prev(&mut self) {
+        if !self.panes.is_empty() {
+            self.selected = (self.selected + self.panes.len() - 1) % self.panes.len();
+        }
+    }
+
+    pub fn select(&mut self, idx: usize) {
+        if idx < self.panes.len() {
+            self.selected = idx;
+        }
+    }
+
+    pub fn scroll_up(&mut self, n: usize) {
+        self.focused_mut().scroll_up(n);
+    }
+
+    pub fn scroll_down(&mut self, n: usize) {
+        let paused = self.paused;
+        self.focused_mut().scroll_down(n, paused);
+    }
+
+    pub fn scroll_top(&mut self) {
+        let page = self.page;
+        self.focused_mut().scroll_top(page);
+    }
+
+    pub fn scroll_bottom(&mut self) {
+        let paused = self.paused;
+        let sb = self.focused_mut();
+        if paused {
+            sb.anchor = Some(sb.pushed);
+        } else {
+            sb.follow();
+        }
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+        for sb in self
+            .panes
+            .iter_mut()
+            .map(|p| &mut p.lines)
+            .chain(std::iter::once(&mut self.merged))
+        {
+            if self.paused {
+                sb.freeze();
+            } else {
+                sb.follow();
+            }
+        }
+    }
+}
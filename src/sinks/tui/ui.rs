@@ -0,0 +1,292 @@
+use crate::core::{LogLevel, PortState};
+use crate::sinks::fmt_device_ts;
+use crate::sinks::stdout::source_color;
+use crate::sinks::tui::state::{Entry, Pane, Scrollback, TuiState, ViewMode};
+
+use chrono::{DateTime, Utc};
+use ratatui::Frame;
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Paragraph, Tabs};
+
+use std::time::Instant;
+
+pub fn draw(frame: &mut Frame, state: &mut TuiState) {
+    let [body, status] =
+        Layout::vertical([Constraint::Min(1), Constraint::Length(1)]).areas(frame.area());
+
+    match state.mode {
+        ViewMode::Grid => draw_grid(frame, body, state),
+        ViewMode::Tabs => draw_tabs(frame, body, state),
+        ViewMode::Merged => {
+            let follow = state.merged.is_following();
+            let title = pane_title("merged", None, None, follow);
+            draw_scrollback(frame, body, &state.merged, title, true, true);
+            state.page = body.height.saturating_sub(2) as usize;
+        }
+    }
+
+    if state.input.active {
+        draw_input(frame, status, state);
+    } else {
+        draw_status(frame, status, state);
+    }
+}
+
+fn draw_grid(frame: &mut Frame, area: Rect, state: &mut TuiState) {
+    let n = state.panes.len();
+    if n == 0 {
+        frame.render_widget(Paragraph::new("waiting for sources..."), area);
+        return;
+    }
+
+    let cols = (n as f64).sqrt().ceil() as usize;
+    let rows = n.div_ceil(cols);
+
+    let row_areas = Layout::vertical(vec![Constraint::Fill(1); rows]).split(area);
+    let now = Instant::now();
+    for (r, row_area) in row_areas.iter().enumerate() {
+        let in_row = (n - r * cols).min(cols);
+        let cells = Layout::horizontal(vec![Constraint::Fill(1); in_row]).split(*row_area);
+        for (c, cell) in cells.iter().enumerate() {
+            let idx = r * cols + c;
+            let selected = idx == state.selected;
+            draw_pane(frame, *cell, &mut state.panes[idx], now, selected);
+            if selected {
+                state.page = cell.height.saturating_sub(2) as usize;
+            }
+        }
+    }
+}
+
+fn draw_tabs(frame: &mut Frame, area: Rect, state: &mut TuiState) {
+    if state.panes.is_empty() {
+        frame.render_widget(Paragraph::new("waiting for sources..."), area);
+        return;
+    }
+
+    let [tabs_area, pane_area] =
+        Layout::vertical([Constraint::Length(1), Constraint::Min(1)]).areas(area);
+
+    let titles = state
+        .panes
+        .iter()
+        .enumerate()
+        .map(|(i, p)| format!("{} {}", i + 1, p.source.label()))
+        .collect::<Vec<_>>();
+    let tabs = Tabs::new(titles)
+        .select(state.selected)
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    frame.render_widget(tabs, tabs_area);
+
+    let now = Instant::now();
+    draw_pane(
+        frame,
+        pane_area,
+        &mut state.panes[state.selected],
+        now,
+        true,
+    );
+    state.page = pane_area.height.saturating_sub(2) as usize;
+}
+
+fn draw_pane(frame: &mut Frame, area: Rect, pane: &mut Pane, now: Instant, selected: bool) {
+    let rate = pane.rate(now);
+    let label = pane.source.label();
+    let title = pane_title(&label, pane.state, Some(rate), pane.lines.is_following());
+    draw_scrollback(frame, area, &pane.lines, title, selected, false);
+}
+
+fn pane_title(
+    label: &str,
+    state: Option<PortState>,
+    rate: Option<f64>,
+    following: bool,
+) -> Line<'static> {
+    let mut spans = vec![Span::raw(format!(" {label} "))];
+    if let Some(state) = state {
+        spans.push(state_span(state));
+        spans.push(Span::raw(" "));
+    }
+    if let Some(rate) = rate {
+        spans.push(Span::raw(format!("{rate:.1} l/s ")));
+    }
+    if !following {
+        spans.push(Span::styled(
+            "[scroll] ",
+            Style::default().fg(Color::Yellow),
+        ));
+    }
+    Line::from(spans)
+}
+
+fn draw_scrollback(
+    frame: &mut Frame,
+    area: Rect,
+    lines: &Scrollback,
+    title: Line<'static>,
+    selected: bool,
+    with_source: bool,
+) {
+    let border = if selected {
+        Style::default().fg(Color::Cyan)
+    } else {
+        Style::default().fg(Color::DarkGray)
+    };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(border)
+        .title(title);
+
+    let height = area.height.saturating_sub(2) as usize;
+    let text = lines
+        .visible(height)
+        .map(|e| render_entry(e, with_source))
+        .collect::<Vec<_>>();
+
+    frame.render_widget(Paragraph::new(text).block(block), area);
+}
+
+fn render_entry(entry: &Entry, with_source: bool) -> Line<'static> {
+    let dt: DateTime<Utc> = entry.ts.into();
+    let mut spans = vec![
+        Span::styled(
+            dt.format("%H:%M:%S%.3f").to_string(),
+            Style::default().fg(Color::DarkGray),
+        ),
+        Span::raw(" "),
+    ];
+    if let Some(dev) = entry.device_ts {
+        spans.push(Span::styled(
+            fmt_device_ts(dev),
+            Style::default().fg(Color::DarkGray),
+        ));
+        spans.push(Span::raw(" "));
+    }
+
+    if with_source {
+        match &entry.source {
+            Some(source) => {
+                let (r, g, b) = source_color(source);
+                spans.push(Span::styled(
+                    format!("[{}]", source.label()),
+                    Style::default()
+                        .fg(Color::Rgb(r, g, b))
+                        .add_modifier(Modifier::BOLD),
+                ));
+            }
+            None => spans.push(Span::styled(
+                "[SYS]",
+                Style::default()
+                    .fg(Color::Magenta)
+                    .add_modifier(Modifier::BOLD),
+            )),
+        }
+        spans.push(Span::raw(" "));
+    }
+
+    if entry.echo {
+        spans.push(Span::styled(
+            format!("» {}", entry.text),
+            Style::default()
+                .fg(Color::LightBlue)
+                .add_modifier(Modifier::ITALIC),
+        ));
+        return Line::from(spans);
+    }
+
+    match entry.level {
+        Some(level) => spans.push(Span::styled(entry.text.clone(), level_style(level))),
+        None => spans.push(Span::raw(entry.text.clone())),
+    }
+
+    Line::from(spans)
+}
+
+fn draw_status(frame: &mut Frame, area: Rect, state: &mut TuiState) {
+    let now = Instant::now();
+    let mut spans = Vec::new();
+
+    let mode = match state.mode {
+        ViewMode::Grid => "GRID",
+        ViewMode::Tabs => "TABS",
+        ViewMode::Merged => "MERGED",
+    };
+    spans.push(Span::styled(
+        format!(" {mode} "),
+        Style::default().add_modifier(Modifier::REVERSED),
+    ));
+    if state.paused {
+        spans.push(Span::styled(
+            " PAUSED ",
+            Style::default()
+                .fg(Color::Black)
+                .bg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        ));
+    }
+    spans.push(Span::raw(" "));
+
+    if let Some(notice) = &state.notice {
+        spans.push(Span::styled(
+            format!("{notice}  "),
+            Style::default().fg(Color::Red),
+        ));
+    }
+
+    for pane in state.panes.iter_mut() {
+        let rate = pane.rate(now);
+        let (r, g, b) = source_color(&pane.source);
+        spans.push(Span::styled(
+            pane.source.label(),
+            Style::default().fg(Color::Rgb(r, g, b)),
+        ));
+        spans.push(Span::raw(" "));
+        match pane.state {
+            Some(s) => spans.push(state_span(s)),
+            None => spans.push(Span::styled("○", Style::default().fg(Color::DarkGray))),
+        }
+        spans.push(Span::raw(format!(" {rate:.1}/s  ")));
+    }
+
+    spans.push(Span::styled(
+        "q quit · g/t/m layout · tab pane · ↑↓ PgUp PgDn scroll · space pause · i send",
+        Style::default().fg(Color::DarkGray),
+    ));
+
+    frame.render_widget(Paragraph::new(Line::from(spans)), area);
+}
+
+fn draw_input(frame: &mut Frame, area: Rect, state: &TuiState) {
+    let target = state
+        .selected_source()
+        .map(|s| s.label())
+        .unwrap_or_default();
+    let line = Line::from(vec![
+        Span::styled(
+            format!(" {target} "),
+            Style::default().add_modifier(Modifier::REVERSED),
+        ),
+        Span::raw(format!(" {}", state.input.buffer)),
+        Span::styled("█", Style::default().fg(Color::Gray)),
+    ]);
+    frame.render_widget(Paragraph::new(line), area);
+}
+
+fn state_span(state: PortState) -> Span<'static> {
+    match state {
+        PortState::Connected => Span::styled("●", Style::default().fg(Color::Green)),
+        PortState::Disconnected => Span::styled("●", Style::default().fg(Color::Red)),
+    }
+}
+
+fn level_style(level: LogLevel) -> Style {
+    match level {
+        LogLevel::Trace => Style::default().fg(Color::DarkGray),
+        LogLevel::Debug => Style::default().fg(Color::Cyan),
+        LogLevel::Info => Style::default().fg(Color::Green),
+        LogLevel::Warn => Style::default().fg(Color::Yellow),
+        LogLevel::Error => Style::default().fg(Color::Red),
+    }
+}
@@ -0,0 +1,278 @@
+use crate::core::{AppEvent, LogLevel, SourceId};
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+/// Header of a raw capture file. It is followed by records, integers
+/// little-endian:
+///
+/// - `0x01` source: `id: u16`, `port_len: u16`, port, `alias_len: u16`
+///   (`0xFFFF` when there is no alias), alias
+/// - `0x02` data: `id: u16`, `ts_us: u64` (microseconds since the Unix
+///   epoch), `len: u32`, bytes exactly as read from the port
+///
+/// A source record always precedes the first data record that uses its id.
+pub const MAGIC: &[u8; 8] = b"OCTOCAP\x01";
+
+const TAG_SOURCE: u8 = 0x01;
+const TAG_DATA: u8 = 0x02;
+const NO_ALIAS: u16 = u16::MAX;
+
+/// Largest data record: longer reads are split, and a reader takes a
+/// longer length as corruption.
+const MAX_CHUNK: usize = 64 * 1024;
+
+/// Reads queued for the writer before ports wait for it.
+const QUEUE: usize = 1024;
+
+/// Shared handle that port tasks use to append the bytes they read. The
+/// file is written, and each record flushed, on a blocking thread of its
+/// own, so a capture cut short by a crash keeps everything up to its last
+/// read.
+#[derive(Clone)]
+pub struct CaptureWriter {
+    tx: mpsc::Sender<Op>,
+}
+
+enum Op {
+    Data {
+        source: SourceId,
+        ts: SystemTime,
+        bytes: Vec<u8>,
+    },
+    Close,
+}
+
+struct Inner {
+    w: BufWriter<File>,
+    ids: HashMap<SourceId, u16>,
+}
+
+impl CaptureWriter {
+    /// Creates the capture file and starts its writer. A write error stops
+    /// the capture, never the ports, and is reported on `events`.
+    pub fn spawn(
+        path: &Path,
+        events: mpsc::Sender<AppEvent>,
+    ) -> io::Result<(Self, JoinHandle<()>)> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut w = BufWriter::new(File::create(path)?);
+        w.write_all(MAGIC)?;
+        w.flush()?;
+
+        let path = path.to_path_buf();
+        let (tx, mut rx) = mpsc::channel(QUEUE);
+        let handle = tokio::task::spawn_blocking(move || {
+            let mut inner = Inner {
+                w,
+                ids: HashMap::new(),
+            };
+            let mut failed = false;
+            while let Some(Op::Data { source, ts, bytes }) = rx.blocking_recv() {
+                if failed {
+                    continue;
+                }
+                let written = inner
+                    .write_data(&source, ts, &bytes)
+                    .and_then(|()| inner.w.flush());
+                if let Err(e) = written {
+                    failed = true;
+                    let _ = events.try_send(AppEvent::System {
+                        level: LogLevel::Error,
+                        message: format!("capture stopped: {}: {e}", path.display()),
+                    });
+                }
+            }
+        });
+        Ok((Self { tx }, handle))
+    }
+
+    /// Queues one read, waiting while the writer is behind.
+    pub async fn record(&self, source: &SourceId, ts: SystemTime, bytes: &[u8]) {
+        for chunk in bytes.chunks(MAX_CHUNK) {
+            let op = Op::Data {
+                source: source.clone(),
+                ts,
+                bytes: chunk.to_vec(),
+            };
+            if self.tx.send(op).await.is_err() {
+                return;
+            }
+        }
+    }
+
+    /// Stops the writer once the reads queued so far are on disk; later
+    /// reads are not recorded.
+    pub async fn close(&self) {
+        let _ = self.tx.send(Op::Close).await;
+    }
+}
+
+impl Inner {
+    fn write_data(&mut self, source: &SourceId, ts: SystemTime, bytes: &[u8]) -> io::Result<()> {
+        let id = match self.ids.get(source) {
+            Some(&id) => id,
+            None => self.declare(source)?,
+        };
+
+        let ts_us = ts
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_micros() as u64)
+            .unwrap_or(0);
+
+        self.w.write_all(&[TAG_DATA])?;
+        self.w.write_all(&id.to_le_bytes())?;
+        self.w.write_all(&ts_us.to_le_bytes())?;
+        self.w.write_all(&(bytes.len() as u32).to_le_bytes())?;
+        self.w.write_all(bytes)
+    }
+
+    fn declare(&mut self, source: &SourceId) -> io::Result<u16> {
+        let id = u16::try_from(self.ids.len())
+            .map_err(|_| io::Error::other("too many sources in one capture"))?;
+
+        self.w.write_all(&[TAG_SOURCE])?;
+        self.w.write_all(&id.to_le_bytes())?;
+        write_str(&mut self.w, &source.port)?;
+        match &source.alias {
+            Some(alias) => write_str(&mut self.w, alias)?,
+            None => self.w.write_all(&NO_ALIAS.to_le_bytes())?,
+        }
+
+        self.ids.insert(source.clone(), id);
+        Ok(id)
+    }
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        let _ = self.w.flush();
+    }
+}
+
+fn write_str(w: &mut impl Write, s: &str) -> io::Result<()> {
+    let len = u16::try_from(s.len())
+        .ok()
+        .filter(|&n| n != NO_ALIAS)
+        .ok_or_else(|| io::Error::other("source name too long"))?;
+    w.write_all(&len.to_le_bytes())?;
+    w.write_all(s.as_bytes())
+}
+
+/// One chunk of bytes as originally read from a port.
+pub struct Chunk {
+    pub source: SourceId,
+    pub ts: SystemTime,
+    pub bytes: Vec<u8>,
+}
+
+/// Reads a capture sequentially.
+pub struct CaptureReader<R> {
+    r: R,
+    sources: HashMap<u16, SourceId>,
+    truncated: bool,
+}
+
+impl<R: AsyncRead + Unpin> CaptureReader<R> {
+    /// Checks the header and positions the reader on the first record.
+    pub async fn new(mut r: R) -> io::Result<Self> {
+        let mut magic = [0u8; 8];
+        r.read_exact(&mut magic).await?;
+        if &magic != MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not an octolog capture file",
+            ));
+        }
+        Ok(Self {
+            r,
+            sources: HashMap::new(),
+            truncated: false,
+        })
+    }
+
+    /// Returns the next data chunk, or `None` at the end of the file. A
+    /// partial last record, from a recording that was cut short, also ends
+    /// the file, see `truncated`.
+    pub async fn next_chunk(&mut self) -> io::Result<Option<Chunk>> {
+        loop {
+            let tag = match self.r.read_u8().await {
+                Ok(t) => t,
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+                Err(e) => return Err(e),
+            };
+
+            match self.read_record(tag).await {
+                Ok(Some(chunk)) => return Ok(Some(chunk)),
+                Ok(None) => {}
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                    self.truncated = true;
+                    return Ok(None);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Whether the file ended in the middle of a record.
+    pub fn truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// Reads the record after `tag`: a data chunk, or `None` for a source.
+    async fn read_record(&mut self, tag: u8) -> io::Result<Option<Chunk>> {
+        match tag {
+            TAG_SOURCE => {
+                let id = self.r.read_u16_le().await?;
+                let port = self.read_str().await?.unwrap_or_default();
+                let alias = self.read_str().await?;
+                self.sources.insert(id, SourceId { port, alias });
+                Ok(None)
+            }
+            TAG_DATA => {
+                let id = self.r.read_u16_le().await?;
+                let ts_us = self.r.read_u64_le().await?;
+                let len = self.r.read_u32_le().await? as usize;
+                if len > MAX_CHUNK {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("data record of {len} bytes, at most {MAX_CHUNK} expected"),
+                    ));
+                }
+                let mut bytes = vec![0u8; len];
+                self.r.read_exact(&mut bytes).await?;
+
+                let source = self.sources.get(&id).cloned().ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidData, "data for undeclared source")
+                })?;
+                Ok(Some(Chunk {
+                    source,
+                    ts: UNIX_EPOCH + Duration::from_micros(ts_us),
+                    bytes,
+                }))
+            }
+            other => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unknown capture record 0x{other:02x}"),
+            )),
+        }
+    }
+
+    async fn read_str(&mut self) -> io::Result<Option<String>> {
+        let len = self.r.read_u16_le().await?;
+        if len == NO_ALIAS {
+            return Ok(None);
+        }
+        let mut buf = vec![0u8; len as usize];
+        self.r.read_exact(&mut buf).await?;
+        Ok(Some(String::from_utf8_lossy(&buf).into_owned()))
+    }
+}
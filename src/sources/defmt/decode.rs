@@ -0,0 +1,407 @@
+use crate::core::LogLevel;
+use crate::sources::defmt::table::{Entry, Table, Tag};
+
+use chrono::DateTime;
+use defmt_parser::{DisplayHint, Fragment, Parameter, ParserMode, TimePrecision, Type};
+use std::ops::Range;
+
+#[derive(Debug)]
+pub enum DecodeError {
+    /// The frame continues past the bytes received so far.
+    Eof,
+    Malformed(String),
+}
+
+/// One decoded log statement.
+pub struct Decoded {
+    pub level: Option<LogLevel>,
+    pub text: String,
+}
+
+/// Decodes the log frame at the start of `bytes`; returns it and the number
+/// of bytes it took up.
+pub fn decode(table: &Table, bytes: &[u8]) -> Result<(Decoded, usize), DecodeError> {
+    let mut r = Reader {
+        table,
+        bytes,
+        pos: 0,
+    };
+
+    let index = r.u16()?;
+    let entry = r.entry(index)?;
+    let level = match entry.tag {
+        Tag::Log(level) => Some(level),
+        Tag::Println => None,
+        _ => {
+            return Err(DecodeError::Malformed(format!(
+                "index {index} is not a log statement"
+            )));
+        }
+    };
+
+    let timestamp = match &r.table.timestamp {
+        Some(format) => Some(r.format(format)?),
+        None => None,
+    };
+    let message = r.format(&entry.format)?;
+
+    let mut text = match timestamp {
+        Some(ts) => format!("{ts} {message}"),
+        None => message,
+    };
+    if let Some(loc) = &entry.location {
+        text.push_str(&format!(
+            " ({} @ {}:{})",
+            loc.module,
+            short_path(&loc.file),
+            loc.line
+        ));
+    }
+
+    Ok((Decoded { level, text }, r.pos))
+}
+
+/// `src/...` of a path inside a crate, the full path otherwise.
+fn short_path(path: &str) -> &str {
+    match path.rfind("/src/") {
+        Some(at) => &path[at + 1..],
+        None => path,
+    }
+}
+
+enum Arg {
+    Bool(bool),
+    Uxx(u128),
+    Ixx(i128),
+    F32(f32),
+    F64(f64),
+    Char(char),
+    Str(String),
+    /// Already formatted, e.g. a nested `Format` value.
+    Text(String),
+    Bytes(Vec<u8>),
+    List(Vec<String>),
+}
+
+struct Reader<'a> {
+    table: &'a Table,
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&[u8], DecodeError> {
+        let end = self.pos.checked_add(n).ok_or(DecodeError::Eof)?;
+        let out = self.bytes.get(self.pos..end).ok_or(DecodeError::Eof)?;
+        self.pos = end;
+        Ok(out)
+    }
+
+    /// A little-endian unsigned integer of `n` bytes.
+    fn uint(&mut self, n: usize) -> Result<u128, DecodeError> {
+        let bytes = self.take(n)?;
+        Ok(bytes.iter().rev().fold(0u128, |v, &b| v << 8 | b as u128))
+    }
+
+    fn int(&mut self, n: usize) -> Result<i128, DecodeError> {
+        let v = self.uint(n)?;
+        let shift = 128 - 8 * n as u32;
+        Ok(((v << shift) as i128) >> shift)
+    }
+
+    fn u16(&mut self) -> Result<u16, DecodeError> {
+        Ok(self.uint(2)? as u16)
+    }
+
+    /// A `usize` as the firmware sends it: always four bytes, which also
+    /// prefixes strings and slices.
+    fn len(&mut self) -> Result<usize, DecodeError> {
+        Ok(self.uint(4)? as usize)
+    }
+
+    fn entry(&self, index: u16) -> Result<&'a Entry, DecodeError> {
+        self.table
+            .entries
+            .get(&index)
+            .ok_or_else(|| DecodeError::Malformed(format!("unknown index {index}")))
+    }
+
+    /// Reads the arguments of `format` and renders it.
+    fn format(&mut self, format: &str) -> Result<String, DecodeError> {
+        let fragments = defmt_parser::parse(format, ParserMode::ForwardsCompatible)
+            .map_err(|e| DecodeError::Malformed(format!("bad format string {format:?}: {e}")))?;
+        let params = fragments
+            .iter()
+            .filter_map(|f| match f {
+                Fragment::Parameter(p) => Some(p),
+                Fragment::Literal(_) => None,
+            })
+            .collect::<Vec<_>>();
+
+        // Arguments are sent once each, in index order, however often and
+        // wherever the format string uses them.
+        let count = params.iter().map(|p| p.index + 1).max().unwrap_or(0);
+        let mut args = Vec::with_capacity(count);
+        for index in 0..count {
+            let uses = params
+                .iter()
+                .copied()
+                .filter(|p| p.index == index)
+                .collect::<Vec<_>>();
+            let arg = match uses.first().map(|p| &p.ty) {
+                None => Arg::Text(String::new()),
+                Some(Type::BitField(_)) => self.bitfield(&uses)?,
+                Some(ty) => self.value(ty)?,
+            };
+            args.push(arg);
+        }
+
+        let mut out = String::new();
+        for fragment in &fragments {
+            match fragment {
+                Fragment::Literal(s) => out.push_str(s),
+                Fragment::Parameter(p) => out.push_str(&render(&args[p.index], p)),
+            }
+        }
+        Ok(out)
+    }
+
+    fn value(&mut self, ty: &Type) -> Result<Arg, DecodeError> {
+        Ok(match ty {
+            Type::Bool => Arg::Bool(self.uint(1)? != 0),
+            Type::U8 => Arg::Uxx(self.uint(1)?),
+            Type::U16 => Arg::Uxx(self.uint(2)?),
+            Type::U32 => Arg::Uxx(self.uint(4)?),
+            Type::U64 => Arg::Uxx(self.uint(8)?),
+            Type::U128 => Arg::Uxx(self.uint(16)?),
+            Type::Usize => Arg::Uxx(self.uint(4)?),
+            Type::I8 => Arg::Ixx(self.int(1)?),
+            Type::I16 => Arg::Ixx(self.int(2)?),
+            Type::I32 => Arg::Ixx(self.int(4)?),
+            Type::I64 => Arg::Ixx(self.int(8)?),
+            Type::I128 => Arg::Ixx(self.int(16)?),
+            Type::Isize => Arg::Ixx(self.int(4)?),
+            Type::F32 => Arg::F32(f32::from_bits(self.uint(4)? as u32)),
+            Type::F64 => Arg::F64(f64::from_bits(self.uint(8)? as u64)),
+            Type::Char => {
+                let v = self.uint(4)? as u32;
+                Arg::Char(
+                    char::from_u32(v)
+                        .ok_or_else(|| DecodeError::Malformed(format!("invalid char {v:#x}")))?,
+                )
+            }
+            Type::Str => {
+                let n = self.len()?;
+                Arg::Str(String::from_utf8_lossy(self.take(n)?).into_owned())
+            }
+            Type::IStr => {
+                let index = self.u16()?;
+                Arg::Str(self.entry(index)?.format.clone())
+            }
+            Type::U8Slice => {
+                let n = self.len()?;
+                Arg::Bytes(self.take(n)?.to_vec())
+            }
+            Type::U8Array(n) => Arg::Bytes(self.take(*n)?.to_vec()),
+            Type::Format => Arg::Text(self.nested()?),
+            Type::FormatSlice => {
+                let n = self.len()?;
+                Arg::List((0..n).map(|_| self.nested()).collect::<Result<_, _>>()?)
+            }
+            Type::FormatArray(n) => {
+                Arg::List((0..*n).map(|_| self.nested()).collect::<Result<_, _>>()?)
+            }
+            Type::FormatSequence => {
+                let mut out = String::new();
+                loop {
+                    let index = self.u16()?;
+                    if index == 0 {
+                        break;
+                    }
+                    let entry = self.entry(index)?;
+                    out.push_str(&self.with_entry(entry)?);
+                }
+                Arg::Text(out)
+            }
+            // `Debug2Format`/`Display2Format` text, terminated by 0xFF.
+            Type::Debug | Type::Display => {
+                let rest = &self.bytes[self.pos.min(self.bytes.len())..];
+                let end = rest
+                    .iter()
+                    .position(|&b| b == 0xFF)
+                    .ok_or(DecodeError::Eof)?;
+                let text = String::from_utf8_lossy(&rest[..end]).into_owned();
+                self.pos += end + 1;
+                Arg::Text(text)
+            }
+            Type::BitField(_) => unreachable!("bitfields are read per argument"),
+        })
+    }
+
+    /// A bitfield argument carries only the bytes its widest use covers.
+    fn bitfield(&mut self, uses: &[&Parameter]) -> Result<Arg, DecodeError> {
+        let (lowest, highest) =
+            defmt_parser::get_max_bitfield_range(uses.iter().copied()).unwrap_or((0, 8));
+        let low_byte = lowest / 8;
+        let high_byte = (highest.max(1) - 1) / 8;
+        let size = match high_byte - low_byte + 1 {
+            1 => 1,
+            2 => 2,
+            3..=4 => 4,
+            5..=8 => 8,
+            _ => 16,
+        };
+        Ok(Arg::Uxx(self.uint(size)? << (low_byte as u32 * 8)))
+    }
+
+    /// A value formatted by its own interned format string.
+    fn nested(&mut self) -> Result<String, DecodeError> {
+        let index = self.u16()?;
+        let entry = self.entry(index)?;
+        self.with_entry(entry)
+    }
+
+    fn with_entry(&mut self, entry: &Entry) -> Result<String, DecodeError> {
+        if entry.tag == Tag::Derived && entry.format.contains('|') {
+            let variants = entry.format.split('|').collect::<Vec<_>>();
+            let n = if variants.len() <= 256 { 1 } else { 2 };
+            let discriminant = self.uint(n)? as usize;
+            let variant = variants.get(discriminant).ok_or_else(|| {
+                DecodeError::Malformed(format!("invalid enum discriminant {discriminant}"))
+            })?;
+            return self.format(variant);
+        }
+        self.format(&entry.format)
+    }
+}
+
+fn render(arg: &Arg, param: &Parameter) -> String {
+    let hint = param.hint.as_ref();
+    match arg {
+        Arg::Uxx(v) => match &param.ty {
+            Type::BitField(range) => unsigned(bits(*v, range), hint),
+            _ => unsigned(*v, hint),
+        },
+        Arg::Ixx(v) => match hint {
+            Some(DisplayHint::NoHint { .. }) | None => signed(*v, hint),
+            Some(_) if *v < 0 => format!("-{}", unsigned(v.unsigned_abs(), hint)),
+            Some(_) => unsigned(*v as u128, hint),
+        },
+        Arg::Bool(b) => b.to_string(),
+        Arg::F32(v) => v.to_string(),
+        Arg::F64(v) => v.to_string(),
+        Arg::Char(c) if hint == Some(&DisplayHint::Debug) => format!("{c:?}"),
+        Arg::Char(c) => c.to_string(),
+        Arg::Str(s) if hint == Some(&DisplayHint::Debug) => format!("{s:?}"),
+        Arg::Str(s) | Arg::Text(s) => s.clone(),
+        Arg::Bytes(bytes) => match hint {
+            Some(DisplayHint::Ascii) => format!("b\"{}\"", bytes.escape_ascii()),
+            _ => {
+                let items = bytes
+                    .iter()
+                    .map(|&b| unsigned(b as u128, hint))
+                    .collect::<Vec<_>>();
+                format!("[{}]", items.join(", "))
+            }
+        },
+        Arg::List(items) => format!("[{}]", items.join(", ")),
+    }
+}
+
+fn bits(v: u128, range: &Range<u8>) -> u128 {
+    let width = range.end.saturating_sub(range.start) as u32;
+    let mask = if width >= 128 {
+        u128::MAX
+    } else {
+        (1u128 << width) - 1
+    };
+    (v >> range.start) & mask
+}
+
+fn signed(v: i128, hint: Option<&DisplayHint>) -> String {
+    match hint {
+        Some(DisplayHint::NoHint { zero_pad }) => format!("{v:0zero_pad$}"),
+        _ => v.to_string(),
+    }
+}
+
+fn unsigned(v: u128, hint: Option<&DisplayHint>) -> String {
+    // Like Rust's `{:#06x}`, the padding width includes the prefix.
+    let pad = |prefix: &str, digits: String, zero_pad: usize| {
+        let width = zero_pad.saturating_sub(prefix.len());
+        format!("{prefix}{digits:0>width$}")
+    };
+
+    match hint {
+        Some(DisplayHint::NoHint { zero_pad }) => format!("{v:0zero_pad$}"),
+        Some(DisplayHint::Hexadecimal {
+            alternate,
+            uppercase,
+            zero_pad,
+        }) => {
+            let digits = if *uppercase {
+                format!("{v:X}")
+            } else {
+                format!("{v:x}")
+            };
+            pad(if *alternate { "0x" } else { "" }, digits, *zero_pad)
+        }
+        Some(DisplayHint::Octal {
+            alternate,
+            zero_pad,
+        }) => pad(
+            if *alternate { "0o" } else { "" },
+            format!("{v:o}"),
+            *zero_pad,
+        ),
+        Some(DisplayHint::Binary {
+            alternate,
+            zero_pad,
+        }) => pad(
+            if *alternate { "0b" } else { "" },
+            format!("{v:b}"),
+            *zero_pad,
+        ),
+        Some(DisplayHint::Ascii) => match u8::try_from(v) {
+            Ok(b) => format!("b'{}'", [b].escape_ascii()),
+            Err(_) => v.to_string(),
+        },
+        Some(DisplayHint::Seconds(precision)) => match precision {
+            TimePrecision::Micros => format!("{}.{:06}", v / 1_000_000, v % 1_000_000),
+            TimePrecision::Millis => format!("{}.{:03}", v / 1_000, v % 1_000),
+            TimePrecision::Seconds => v.to_string(),
+        },
+        Some(DisplayHint::Time(precision)) => {
+            let (secs, frac) = match precision {
+                TimePrecision::Micros => (v / 1_000_000, format!(".{:06}", v % 1_000_000)),
+                TimePrecision::Millis => (v / 1_000, format!(".{:03}", v % 1_000)),
+                TimePrecision::Seconds => (v, String::new()),
+            };
+            let (days, rest) = (secs / 86_400, secs % 86_400);
+            let clock = format!(
+                "{:02}:{:02}:{:02}{frac}",
+                rest / 3_600,
+                rest / 60 % 60,
+                rest % 60
+            );
+            if days > 0 {
+                format!("{days}:{clock}")
+            } else {
+                clock
+            }
+        }
+        Some(DisplayHint::ISO8601(precision)) => {
+            let millis = match precision {
+                TimePrecision::Micros => v / 1_000,
+                TimePrecision::Millis => v,
+                TimePrecision::Seconds => v.saturating_mul(1_000),
+            };
+            i64::try_from(millis)
+                .ok()
+                .and_then(DateTime::from_timestamp_millis)
+                .map(|dt| dt.to_rfc3339())
+                .unwrap_or_else(|| v.to_string())
+        }
+        _ => v.to_string(),
+    }
+}
@@ -0,0 +1,151 @@
+mod decode;
+mod table;
+
+use crate::core::LogLevel;
+use decode::DecodeError;
+use table::Encoding;
+
+use std::path::Path;
+use std::sync::Arc;
+
+pub use table::Table;
+
+/// Longest raw-encoded frame waited for before the stream is considered
+/// out of step.
+const MAX_FRAME: usize = 64 * 1024;
+
+/// The defmt table of an ELF file, to share between the ports that use it.
+pub fn load(path: &Path) -> Result<Arc<Table>, String> {
+    Table::load(path).map(Arc::new)
+}
+
+/// Turns a defmt byte stream, raw or rzCOBS framed as the firmware was
+/// built, into log lines.
+pub(crate) struct DefmtDecoder {
+    table: Arc<Table>,
+    acc: Vec<u8>,
+    pub errors: u64,
+}
+
+impl DefmtDecoder {
+    pub fn new(table: Arc<Table>) -> Self {
+        Self {
+            table,
+            acc: Vec::with_capacity(4096),
+            errors: 0,
+        }
+    }
+
+    pub fn table(&self) -> Arc<Table> {
+        self.table.clone()
+    }
+
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.acc.extend_from_slice(bytes);
+    }
+
+    pub fn clear(&mut self) {
+        self.acc.clear();
+    }
+
+    /// The next decoded statement and its level, or why bytes were dropped.
+    pub fn next_line(&mut self) -> Option<Result<(Option<LogLevel>, String), String>> {
+        let res = match self.table.encoding {
+            Encoding::Rzcobs => self.next_rzcobs()?,
+            Encoding::Raw => self.next_raw()?,
+        };
+        if res.is_err() {
+            self.errors += 1;
+        }
+        Some(res.map(|d| (d.level, d.text)))
+    }
+
+    fn next_rzcobs(&mut self) -> Option<Result<decode::Decoded, String>> {
+        loop {
+            let Some(end) = self.acc.iter().position(|&b| b == 0) else {
+                if self.acc.len() > MAX_FRAME {
+                    let len = self.acc.len();
+                    self.acc.clear();
+                    return Some(Err(format!("no frame boundary within {len} bytes")));
+                }
+                return None;
+            };
+            let raw = self.acc[..end].to_vec();
+            self.acc.drain(..=end);
+            if raw.is_empty() {
+                continue;
+            }
+
+            let Some(frame) = rzcobs_decode(&raw) else {
+                return Some(Err(format!("malformed rzCOBS frame ({} bytes)", raw.len())));
+            };
+            // The encoder pads the final group, so a frame may end in zeros
+            // beyond what the format string reads.
+            return Some(match decode::decode(&self.table, &frame) {
+                Ok((decoded, _)) => Ok(decoded),
+                Err(DecodeError::Eof) => Err(format!("truncated frame ({} bytes)", frame.len())),
+                Err(DecodeError::Malformed(e)) => Err(e),
+            });
+        }
+    }
+
+    /// Raw frames have no boundaries: a malformed one loses sync, so the
+    /// buffered bytes are dropped and decoding starts over with new data.
+    fn next_raw(&mut self) -> Option<Result<decode::Decoded, String>> {
+        if self.acc.is_empty() {
+            return None;
+        }
+        match decode::decode(&self.table, &self.acc) {
+            Ok((decoded, used)) => {
+                self.acc.drain(..used);
+                Some(Ok(decoded))
+            }
+            Err(DecodeError::Eof) if self.acc.len() <= MAX_FRAME => None,
+            Err(DecodeError::Eof) => {
+                let len = self.acc.len();
+                self.acc.clear();
+                Some(Err(format!("no complete frame within {len} bytes")))
+            }
+            Err(DecodeError::Malformed(e)) => {
+                let len = self.acc.len();
+                self.acc.clear();
+                Some(Err(format!("{e}; {len} bytes dropped")))
+            }
+        }
+    }
+}
+
+/// Reverse zero-compressing COBS, decoded from the end of the frame.
+fn rzcobs_decode(data: &[u8]) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(data.len() * 2);
+    let mut bytes = data.iter().rev().copied();
+    while let Some(code) = bytes.next() {
+        match code {
+            0x00 => return None,
+            // Seven bytes; set bits mark the zeros among them.
+            0x01..=0x7F => {
+                for i in 0..7 {
+                    if code & (1 << (6 - i)) == 0 {
+                        out.push(bytes.next()?);
+                    } else {
+                        out.push(0);
+                    }
+                }
+            }
+            // A zero after a run of non-zero bytes.
+            0x80..=0xFE => {
+                out.push(0);
+                for _ in 0..(code & 0x7F) + 7 {
+                    out.push(bytes.next()?);
+                }
+            }
+            0xFF => {
+                for _ in 0..134 {
+                    out.push(bytes.next()?);
+                }
+            }
+        }
+    }
+    out.reverse();
+    Some(out)
+}
@@ -0,0 +1,278 @@
+use crate::core::LogLevel;
+
+use gimli::{AttributeValue, EndianSlice, RunTimeEndian};
+use object::{Object, ObjectSection, ObjectSymbol};
+use serde::Deserialize;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Wire format version the decoder understands (defmt 0.3 and 1.x).
+const SUPPORTED_VERSION: &str = "4";
+
+/// How frames are delimited on the wire.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Raw,
+    Rzcobs,
+}
+
+/// What an interned string is used for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tag {
+    Log(LogLevel),
+    Println,
+    Timestamp,
+    /// A format string for a value: `Format` impls, `write!`, primitives.
+    Format,
+    /// A `#[derive(Format)]` string; enums list their variants split by `|`.
+    Derived,
+    /// An interned `{=istr}` string.
+    Str,
+    Other,
+}
+
+#[derive(Debug, Clone)]
+pub struct Location {
+    pub file: String,
+    pub line: u64,
+    pub module: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub tag: Tag,
+    pub format: String,
+    pub location: Option<Location>,
+}
+
+/// The interned strings of a firmware image, keyed by the index sent on
+/// the wire.
+#[derive(Debug)]
+pub struct Table {
+    pub entries: HashMap<u16, Entry>,
+    pub timestamp: Option<String>,
+    pub encoding: Encoding,
+}
+
+/// A `.defmt` symbol name.
+#[derive(Deserialize)]
+struct Symbol {
+    tag: String,
+    data: String,
+}
+
+impl Table {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let data = std::fs::read(path).map_err(|e| e.to_string())?;
+        let elf = object::File::parse(&*data).map_err(|e| format!("not an ELF file: {e}"))?;
+
+        let defmt = elf
+            .section_by_name(".defmt")
+            .ok_or("no .defmt section (is defmt linked in?)")?;
+
+        let mut version = None;
+        let mut encoding = None;
+        let mut entries = HashMap::new();
+        let mut timestamp = None;
+
+        for sym in elf.symbols() {
+            let Ok(name) = sym.name() else { continue };
+            if let Some(v) = name.strip_prefix("_defmt_version_ = ") {
+                version = Some(v.to_string());
+                continue;
+            }
+            if let Some(e) = name.strip_prefix("_defmt_encoding_ = ") {
+                encoding = Some(e.to_string());
+                continue;
+            }
+            if sym.section_index() != Some(defmt.index()) {
+                continue;
+            }
+            let Ok(symbol) = serde_json::from_str::<Symbol>(name) else {
+                continue;
+            };
+
+            let tag = parse_tag(&symbol.tag);
+            if tag == Tag::Timestamp {
+                timestamp = Some(symbol.data);
+                continue;
+            }
+            let Ok(index) = u16::try_from(sym.address()) else {
+                continue;
+            };
+            entries.insert(
+                index,
+                Entry {
+                    tag,
+                    format: symbol.data,
+                    location: None,
+                },
+            );
+        }
+
+        match version.as_deref() {
+            Some(SUPPORTED_VERSION) => {}
+            Some(v) => {
+                return Err(format!(
+                    "defmt wire version {v} is not supported (expected {SUPPORTED_VERSION})"
+                ));
+            }
+            None => return Err("no defmt version symbol".to_string()),
+        }
+        let encoding = match encoding.as_deref() {
+            Some("raw") => Encoding::Raw,
+            Some("rzcobs") | None => Encoding::Rzcobs,
+            Some(other) => return Err(format!("unknown defmt encoding '{other}'")),
+        };
+
+        // Locations are a nicety: firmware built without debug info still
+        // decodes.
+        if let Ok(locations) = locations(&elf) {
+            for (index, location) in locations {
+                if let Some(entry) = entries.get_mut(&index) {
+                    entry.location = Some(location);
+                }
+            }
+        }
+
+        Ok(Self {
+            entries,
+            timestamp,
+            encoding,
+        })
+    }
+}
+
+fn parse_tag(tag: &str) -> Tag {
+    match tag {
+        "defmt_trace" => Tag::Log(LogLevel::Trace),
+        "defmt_debug" => Tag::Log(LogLevel::Debug),
+        "defmt_info" => Tag::Log(LogLevel::Info),
+        "defmt_warn" => Tag::Log(LogLevel::Warn),
+        "defmt_error" => Tag::Log(LogLevel::Error),
+        "defmt_println" => Tag::Println,
+        "defmt_timestamp" => Tag::Timestamp,
+        "defmt_prim" | "defmt_fmt" | "defmt_write" => Tag::Format,
+        "defmt_derived" => Tag::Derived,
+        "defmt_str" => Tag::Str,
+        _ => Tag::Other,
+    }
+}
+
+/// Source locations of log statements from the DWARF info: each one is a
+/// `DEFMT_LOG_STATEMENT` static placed at its index in `.defmt`, and its
+/// module path is the chain of namespaces around it.
+fn locations(elf: &object::File<'_>) -> Result<HashMap<u16, Location>, gimli::Error> {
+    let endian = if elf.is_little_endian() {
+        RunTimeEndian::Little
+    } else {
+        RunTimeEndian::Big
+    };
+    let load = |id: gimli::SectionId| -> Result<Cow<'_, [u8]>, gimli::Error> {
+        Ok(elf
+            .section_by_name(id.name())
+            .and_then(|s| s.data().ok())
+            .map(Cow::Borrowed)
+            .unwrap_or(Cow::Borrowed(&[])))
+    };
+    let sections = gimli::DwarfSections::load(load)?;
+    let dwarf = sections.borrow(|s| EndianSlice::new(s, endian));
+
+    let mut out = HashMap::new();
+    let mut units = dwarf.units();
+    while let Some(header) = units.next()? {
+        let unit = dwarf.unit(header)?;
+        let mut entries = unit.entries();
+        let mut depth = 0isize;
+        let mut namespaces: Vec<(isize, String)> = Vec::new();
+
+        while let Some((delta, entry)) = entries.next_dfs()? {
+            depth += delta;
+            while namespaces.last().is_some_and(|(d, _)| *d >= depth) {
+                namespaces.pop();
+            }
+
+            let name = match entry.attr_value(gimli::DW_AT_name)? {
+                Some(v) => dwarf
+                    .attr_string(&unit, v)
+                    .ok()
+                    .map(|s| s.to_string_lossy().into_owned()),
+                None => None,
+            };
+
+            match entry.tag() {
+                gimli::DW_TAG_namespace => {
+                    if let Some(name) = name {
+                        namespaces.push((depth, name));
+                    }
+                }
+                gimli::DW_TAG_variable if name.as_deref() == Some("DEFMT_LOG_STATEMENT") => {
+                    let Some(index) = address(&unit, entry.attr_value(gimli::DW_AT_location)?)
+                    else {
+                        continue;
+                    };
+                    let line = entry
+                        .attr(gimli::DW_AT_decl_line)?
+                        .and_then(|a| a.udata_value())
+                        .unwrap_or(0);
+                    let file = match entry.attr_value(gimli::DW_AT_decl_file)? {
+                        Some(AttributeValue::FileIndex(i)) => file_name(&dwarf, &unit, i),
+                        _ => None,
+                    };
+                    let module = namespaces
+                        .iter()
+                        .map(|(_, n)| n.as_str())
+                        .collect::<Vec<_>>()
+                        .join("::");
+                    out.insert(
+                        index,
+                        Location {
+                            file: file.unwrap_or_else(|| "?".to_string()),
+                            line,
+                            module,
+                        },
+                    );
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(out)
+}
+
+type Slice<'a> = EndianSlice<'a, RunTimeEndian>;
+
+/// The index a `DW_OP_addr` location points at.
+fn address(unit: &gimli::Unit<Slice<'_>>, loc: Option<AttributeValue<Slice<'_>>>) -> Option<u16> {
+    let Some(AttributeValue::Exprloc(expr)) = loc else {
+        return None;
+    };
+    let mut ops = expr.operations(unit.encoding());
+    match ops.next() {
+        Ok(Some(gimli::Operation::Address { address })) => u16::try_from(address).ok(),
+        _ => None,
+    }
+}
+
+fn file_name(
+    dwarf: &gimli::Dwarf<Slice<'_>>,
+    unit: &gimli::Unit<Slice<'_>>,
+    index: u64,
+) -> Option<String> {
+    let header = unit.line_program.as_ref()?.header();
+    let file = header.file(index)?;
+    let name = dwarf.attr_string(unit, file.path_name()).ok()?;
+    let name = name.to_string_lossy().into_owned();
+    if name.starts_with('/') {
+        return Some(name);
+    }
+    let dir = file
+        .directory(header)
+        .and_then(|d| dwarf.attr_string(unit, d).ok())
+        .map(|d| d.to_string_lossy().into_owned());
+    Some(match dir {
+        Some(dir) if !dir.is_empty() => format!("{dir}/{name}"),
+        _ => name,
+    })
+}
@@ -0,0 +1,132 @@
+use crate::core::{Delimiter, LineFraming};
+
+/// Cuts a byte stream into lines according to a port's `LineFraming`.
+pub(crate) struct LineFramer {
+    framing: LineFraming,
+    acc: Vec<u8>,
+    /// Where the bytes not yet taken as lines start in `acc`; the taken ones
+    /// are only dropped on the next push.
+    start: usize,
+    /// With `Delimiter::Any`, the last line ended in CR: an LF right after
+    /// it completes the same CRLF terminator.
+    after_cr: bool,
+}
+
+impl LineFramer {
+    pub fn new(framing: LineFraming) -> Self {
+        Self {
+            framing,
+            acc: Vec::with_capacity(4096),
+            start: 0,
+            after_cr: false,
+        }
+    }
+
+    pub fn framing(&self) -> &LineFraming {
+        &self.framing
+    }
+
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.acc.drain(..self.start);
+        self.start = 0;
+        self.acc.extend_from_slice(bytes);
+    }
+
+    /// Whether bytes of an unterminated line are waiting.
+    pub fn has_partial(&self) -> bool {
+        self.start < self.acc.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.acc.clear();
+        self.start = 0;
+        self.after_cr = false;
+    }
+
+    /// The next complete line, split at `max_line` bytes if it is longer.
+    pub fn next_line(&mut self) -> Option<String> {
+        loop {
+            if self.after_cr {
+                match self.acc.get(self.start) {
+                    None => return None,
+                    Some(b'\n') => self.start += 1,
+                    Some(_) => {}
+                }
+                self.after_cr = false;
+            }
+
+            let max = self.framing.max_line;
+            let pending = self.acc.len() - self.start;
+            let (end, skip) = match self.find_delimiter() {
+                Some((end, len)) if end <= max => {
+                    self.after_cr = self.framing.delimiter == Delimiter::Any
+                        && self.acc[self.start + end] == b'\r';
+                    (end, len)
+                }
+                Some(_) => (self.cut(max), 0),
+                None if pending > max => (self.cut(max), 0),
+                None => return None,
+            };
+
+            let raw = self.start..self.start + end;
+            self.start += end + skip;
+            if let Some(line) = self.finish(&self.acc[raw]) {
+                return Some(line);
+            }
+        }
+    }
+
+    /// Takes the unterminated rest as a line, e.g. after an idle timeout or
+    /// at end of stream.
+    pub fn flush(&mut self) -> Option<String> {
+        // An empty partial line is no line at all.
+        let line = self
+            .has_partial()
+            .then(|| self.finish(&self.acc[self.start..]))
+            .flatten();
+        self.acc.clear();
+        self.start = 0;
+        line
+    }
+
+    /// The line without a CR left over from a CRLF ending; blank lines are
+    /// dropped unless `keep_empty` is set.
+    fn finish(&self, raw: &[u8]) -> Option<String> {
+        let raw = raw.strip_suffix(b"\r").unwrap_or(raw);
+        let line = String::from_utf8_lossy(raw).into_owned();
+        (self.framing.keep_empty || !line.trim().is_empty()).then_some(line)
+    }
+
+    /// Position and length of the first terminator after `start`.
+    fn find_delimiter(&self) -> Option<(usize, usize)> {
+        let acc = &self.acc[self.start..];
+        let find_seq = |seq: &[u8]| {
+            acc.windows(seq.len())
+                .position(|w| w == seq)
+                .map(|pos| (pos, seq.len()))
+        };
+
+        match &self.framing.delimiter {
+            Delimiter::Any => acc
+                .iter()
+                .position(|&b| b == b'\n' || b == b'\r')
+                .map(|pos| (pos, 1)),
+            Delimiter::Lf => find_seq(b"\n"),
+            Delimiter::Cr => find_seq(b"\r"),
+            Delimiter::CrLf => find_seq(b"\r\n"),
+            Delimiter::Bytes(seq) => find_seq(seq),
+        }
+    }
+
+    /// How many of the waiting bytes, at most `max`, make up the next piece
+    /// of an overlong line, backing off so a UTF-8 character is not cut in
+    /// half.
+    fn cut(&self, max: usize) -> usize {
+        let acc = &self.acc[self.start..];
+        let mut at = max;
+        while at > 0 && (acc[at] & 0xC0) == 0x80 {
+            at -= 1;
+        }
+        if at == 0 { max } else { at }
+    }
+}
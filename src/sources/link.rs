@@ -0,0 +1,445 @@
+use crate::core::{AppEvent, LineEnding, LogLevel, PortState, ResolvedPortSpec, SourceId};
+use crate::runtime::Shutdown;
+use crate::sources::defmt::DefmtDecoder;
+use crate::sources::framing::LineFramer;
+use crate::sources::packets::PacketDecoder;
+use crate::sources::{CaptureWriter, SourceContext};
+
+use std::future::Future;
+use std::time::SystemTime;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::mpsc;
+use tokio::time::{Duration, Instant, sleep, sleep_until};
+
+/// Per-port plumbing shared by every source: line, packet or defmt framing, command
+/// writes, raw capture, connection state reports and reconnect waits.
+pub(crate) struct Link {
+    pub source: SourceId,
+    /// Prefix for diagnostics, e.g. `serial` in "serial EOF".
+    kind: &'static str,
+    line_ending: LineEnding,
+    tx: mpsc::Sender<AppEvent>,
+    pub cmds: mpsc::Receiver<String>,
+    capture: Option<CaptureWriter>,
+    pub shutdown: Shutdown,
+    reconnect_delay: Duration,
+    state: Option<PortState>,
+    framer: LineFramer,
+    packets: Option<PacketDecoder>,
+    defmt: Option<DefmtDecoder>,
+    /// Hex view: raw chunks are emitted as frames instead of lines.
+    hex: bool,
+    /// Hex view with an idle timeout: bytes since the last gap.
+    hex_chunk: Vec<u8>,
+    /// Hex view: bytes emitted since connecting.
+    offset: u64,
+    last_data: Instant,
+    /// When the latest bytes were read; stamps the lines and frames they
+    /// complete, however long those wait to be sent.
+    received: SystemTime,
+}
+
+impl Link {
+    pub fn new(kind: &'static str, spec: &ResolvedPortSpec, ctx: SourceContext) -> Self {
+        Self {
+            source: spec.source_id(),
+            kind,
+            line_ending: spec.line_ending,
+            tx: ctx.tx,
+            cmds: ctx.commands,
+            capture: ctx.capture,
+            shutdown: ctx.shutdown,
+            reconnect_delay: ctx.reconnect_delay,
+            state: None,
+            framer: LineFramer::new(spec.line_framing.clone()),
+            packets: spec.packet_framing.map(PacketDecoder::new),
+            defmt: ctx.defmt.map(DefmtDecoder::new),
+            hex: spec.hex,
+            hex_chunk: Vec::new(),
+            offset: 0,
+            last_data: Instant::now(),
+            received: SystemTime::now(),
+        }
+    }
+
+    /// A second link for another stream of the same source, e.g. a child
+    /// process's stderr. It receives no commands.
+    pub fn fork(&self, source: SourceId) -> Self {
+        let (_, cmds) = mpsc::channel(1);
+        Self {
+            source,
+            kind: self.kind,
+            line_ending: self.line_ending,
+            tx: self.tx.clone(),
+            cmds,
+            capture: self.capture.clone(),
+            shutdown: self.shutdown.clone(),
+            reconnect_delay: self.reconnect_delay,
+            state: None,
+            framer: LineFramer::new(self.framer.framing().clone()),
+            packets: self
+                .packets
+                .as_ref()
+                .map(|p| PacketDecoder::new(p.framing())),
+            defmt: self.defmt.as_ref().map(|d| DefmtDecoder::new(d.table())),
+            hex: self.hex,
+            hex_chunk: Vec::new(),
+            offset: 0,
+            last_data: Instant::now(),
+            received: SystemTime::now(),
+        }
+    }
+
+    pub fn is_shutdown(&self) -> bool {
+        self.shutdown.is_triggered()
+    }
+
+    pub async fn system(&self, level: LogLevel, message: String) {
+        let _ = self.tx.send(AppEvent::System { level, message }).await;
+    }
+
+    pub async fn report_state(&mut self, state: PortState) {
+        if self.state == Some(state) {
+            return;
+        }
+        self.state = Some(state);
+        let _ = self
+            .tx
+            .send(AppEvent::PortState {
+                source: self.source.clone(),
+                state,
+            })
+            .await;
+    }
+
+    /// Sleeps before the next connection attempt.
+    pub async fn wait_reconnect(&mut self) {
+        let _ = self.until(sleep(self.reconnect_delay)).await;
+    }
+
+    /// Waits for `fut` while not connected, rejecting commands typed
+    /// meanwhile so they are not replayed at an unexpected time after
+    /// reconnecting. Returns `None` on shutdown.
+    pub async fn until<F: Future>(&mut self, fut: F) -> Option<F::Output> {
+        tokio::pin!(fut);
+
+        loop {
+            if self.shutdown.is_triggered() {
+                return None;
+            }
+
+            tokio::select! {
+                out = &mut fut => return Some(out),
+                _ = self.shutdown.changed() => {}
+                Some(_) = self.cmds.recv() => {
+                    self.system(
+                        LogLevel::Warn,
+                        format!("{} is not connected: command dropped", self.source.label()),
+                    )
+                    .await;
+                }
+            }
+        }
+    }
+
+    /// Reads lines from `stream` and writes queued commands to it until the
+    /// stream fails or the session shuts down. Returns `true` when the link
+    /// dropped and should be reconnected.
+    pub async fn pump<S>(&mut self, stream: &mut S) -> bool
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let mut buf = [0u8; 2048];
+        self.framer.clear();
+        if let Some(packets) = &mut self.packets {
+            packets.clear();
+        }
+        if let Some(defmt) = &mut self.defmt {
+            defmt.clear();
+        }
+        self.hex_chunk.clear();
+        self.offset = 0;
+
+        loop {
+            if self.shutdown.is_triggered() {
+                return false;
+            }
+
+            let idle = self.idle_deadline();
+            tokio::select! {
+                _ = deadline(idle) => {
+                    if !self.flush().await {
+                        return false;
+                    }
+                }
+                _ = self.shutdown.changed() => {
+                    if self.shutdown.is_triggered() {
+                        return false;
+                    }
+                }
+                Some(cmd) = self.cmds.recv() => {
+                    if let Err(e) = write_command(stream, &cmd, self.line_ending).await {
+                        self.system(
+                            LogLevel::Error,
+                            format!("{} write failed: {e}", self.kind),
+                        )
+                        .await;
+                        return true;
+                    }
+
+                    if !self.echo(cmd).await {
+                        return false;
+                    }
+                }
+                res = stream.read(&mut buf) => {
+                    let n = match res {
+                        Ok(0) => {
+                            self.system(LogLevel::Warn, format!("{} EOF", self.kind)).await;
+                            return true;
+                        }
+                        Ok(n) => n,
+                        Err(e) if is_transient_read_error(&e) => {
+                            continue;
+                        }
+                        Err(e) => {
+                            self.system(
+                                LogLevel::Error,
+                                format!("{} read failed: {e}", self.kind),
+                            )
+                            .await;
+                            return true;
+                        }
+                    };
+
+                    if !self.feed(&buf[..n]).await {
+                        return false;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Records `bytes` and emits every line or frame they complete. Returns
+    /// `false` once the event bus is closed.
+    pub async fn feed(&mut self, bytes: &[u8]) -> bool {
+        self.feed_at(SystemTime::now(), bytes).await
+    }
+
+    /// [`feed`](Self::feed) for bytes read at `ts`, e.g. from a capture.
+    pub async fn feed_at(&mut self, ts: SystemTime, bytes: &[u8]) -> bool {
+        self.received = ts;
+        if let Some(capture) = &self.capture {
+            capture.record(&self.source, self.received, bytes).await;
+        }
+
+        self.last_data = Instant::now();
+        if self.hex {
+            return self.feed_hex(bytes).await;
+        }
+        if self.packets.is_some() {
+            return self.feed_packets(bytes).await;
+        }
+        if self.defmt.is_some() {
+            return self.feed_defmt(bytes).await;
+        }
+        self.framer.push(bytes);
+
+        while let Some(raw) = self.framer.next_line() {
+            if !self.emit(raw).await {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Emits decoded frames, and a warning with running error counts for
+    /// every frame that failed to decode or check.
+    async fn feed_packets(&mut self, bytes: &[u8]) -> bool {
+        let Some(packets) = &mut self.packets else {
+            return true;
+        };
+        packets.push(bytes);
+
+        let mut out = Vec::new();
+        while let Some(frame) = packets.next_frame() {
+            out.push(frame.map_err(|e| {
+                format!(
+                    "{}: {e} (CRC errors: {}, framing errors: {})",
+                    self.source.label(),
+                    packets.crc_errors,
+                    packets.framing_errors
+                )
+            }));
+        }
+
+        for frame in out {
+            match frame {
+                Ok(bytes) => {
+                    if !self.emit_frame(0, bytes).await {
+                        return false;
+                    }
+                }
+                Err(message) => self.system(LogLevel::Warn, message).await,
+            }
+        }
+        true
+    }
+
+    /// Emits decoded defmt statements as lines with their level, and a
+    /// warning with a running count for every frame that failed to decode.
+    async fn feed_defmt(&mut self, bytes: &[u8]) -> bool {
+        let Some(defmt) = &mut self.defmt else {
+            return true;
+        };
+        defmt.push(bytes);
+
+        let mut out = Vec::new();
+        while let Some(line) = defmt.next_line() {
+            out.push(line.map_err(|e| {
+                format!(
+                    "{}: defmt: {e} (decode errors: {})",
+                    self.source.label(),
+                    defmt.errors
+                )
+            }));
+        }
+
+        for line in out {
+            match line {
+                Ok((level, raw)) => {
+                    if !self.emit_line(level, raw).await {
+                        return false;
+                    }
+                }
+                Err(message) => self.system(LogLevel::Warn, message).await,
+            }
+        }
+        true
+    }
+
+    /// Emits each read as one hex chunk, or with an idle timeout, the bytes
+    /// between gaps, split at `max_line`.
+    async fn feed_hex(&mut self, bytes: &[u8]) -> bool {
+        let framing = self.framer.framing();
+        if framing.idle_flush.is_none() {
+            return self.emit_chunk(bytes.to_vec()).await;
+        }
+
+        let max = framing.max_line;
+        self.hex_chunk.extend_from_slice(bytes);
+        while self.hex_chunk.len() >= max {
+            let chunk = self.hex_chunk.drain(..max).collect();
+            if !self.emit_chunk(chunk).await {
+                return false;
+            }
+        }
+        true
+    }
+
+    async fn emit_chunk(&mut self, bytes: Vec<u8>) -> bool {
+        let offset = self.offset;
+        self.offset += bytes.len() as u64;
+        self.emit_frame(offset, bytes).await
+    }
+
+    async fn emit_frame(&self, offset: u64, bytes: Vec<u8>) -> bool {
+        let frame = AppEvent::Frame {
+            source: self.source.clone(),
+            ts: self.received,
+            offset,
+            bytes,
+        };
+        self.tx.send(frame).await.is_ok()
+    }
+
+    /// Emits a trailing partial line or hex chunk, e.g. at end of stream or
+    /// once the idle timeout passed; an incomplete frame is never emitted.
+    /// Returns `false` once the event bus is closed.
+    pub async fn flush(&mut self) -> bool {
+        if self.hex {
+            let chunk = std::mem::take(&mut self.hex_chunk);
+            return chunk.is_empty() || self.emit_chunk(chunk).await;
+        }
+        match self.framer.flush() {
+            Some(raw) => self.emit(raw).await,
+            None => true,
+        }
+    }
+
+    /// How long a partial line or hex chunk may wait for more bytes.
+    pub fn idle_flush(&self) -> Option<Duration> {
+        self.framer.framing().idle_flush
+    }
+
+    /// When a waiting partial line or hex chunk is due to be flushed, if
+    /// ever.
+    pub fn idle_deadline(&self) -> Option<Instant> {
+        let after = self.framer.framing().idle_flush?;
+        let partial = if self.hex {
+            !self.hex_chunk.is_empty()
+        } else {
+            self.framer.has_partial()
+        };
+        partial.then(|| self.last_data + after)
+    }
+
+    async fn emit(&self, raw: String) -> bool {
+        self.emit_line(None, raw).await
+    }
+
+    async fn emit_line(&self, level: Option<LogLevel>, raw: String) -> bool {
+        let line = AppEvent::LogLine {
+            source: self.source.clone(),
+            ts: self.received,
+            level,
+            raw,
+        };
+        self.tx.send(line).await.is_ok()
+    }
+
+    /// Reports a command that was sent. Returns `false` once the event bus
+    /// is closed.
+    pub async fn echo(&self, text: String) -> bool {
+        self.tx
+            .send(AppEvent::Echo {
+                source: self.source.clone(),
+                ts: SystemTime::now(),
+                text,
+            })
+            .await
+            .is_ok()
+    }
+
+    /// The command text followed by the port's line ending.
+    pub fn command_bytes(&self, cmd: &str) -> Vec<u8> {
+        [cmd.as_bytes(), self.line_ending.as_bytes()].concat()
+    }
+}
+
+async fn write_command<S: AsyncWrite + Unpin>(
+    stream: &mut S,
+    cmd: &str,
+    line_ending: LineEnding,
+) -> std::io::Result<()> {
+    stream.write_all(cmd.as_bytes()).await?;
+    stream.write_all(line_ending.as_bytes()).await?;
+    stream.flush().await
+}
+
+fn is_transient_read_error(e: &std::io::Error) -> bool {
+    matches!(
+        e.kind(),
+        std::io::ErrorKind::TimedOut
+            | std::io::ErrorKind::WouldBlock
+            | std::io::ErrorKind::Interrupted
+    )
+}
+
+/// Sleeps until `at`, or forever without one; for `select!` branches on
+/// `Link::idle_deadline`.
+pub(crate) async fn deadline(at: Option<Instant>) {
+    match at {
+        Some(at) => sleep_until(at).await,
+        None => std::future::pending().await,
+    }
+}
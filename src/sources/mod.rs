@@ -1 +1,83 @@
+pub mod capture;
+pub mod defmt;
+pub mod framing;
+pub mod link;
+pub mod net;
+pub mod packets;
+pub mod process;
+pub mod replay;
+pub mod rfc2217;
 pub mod serial;
+pub mod stdin;
+pub mod tail;
+
+use crate::core::{AppEvent, ResolvedPortSpec, SourceId, Transport};
+use crate::runtime::{CommandRouter, Shutdown};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::Duration;
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+pub type SourceFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// What a running source is handed: where to send events, the commands
+/// typed for it, and when to stop.
+pub struct SourceContext {
+    pub tx: mpsc::Sender<AppEvent>,
+    pub commands: mpsc::Receiver<String>,
+    pub capture: Option<CaptureWriter>,
+    /// The firmware's defmt table, for a port with `defmt=`.
+    pub defmt: Option<Arc<defmt::Table>>,
+    pub shutdown: Shutdown,
+    pub reconnect_delay: Duration,
+}
+
+pub trait EventSource: Send + 'static {
+    fn source_id(&self) -> SourceId;
+
+    fn run(self: Box<Self>, ctx: SourceContext) -> SourceFuture;
+}
+
+/// Picks the source implementation for a configured port.
+pub fn from_spec(spec: ResolvedPortSpec) -> Box<dyn EventSource> {
+    match spec.transport {
+        Transport::Serial => Box::new(serial::SerialSource::new(spec)),
+        Transport::Rfc2217 { .. } => Box::new(rfc2217::Rfc2217Source::new(spec)),
+        Transport::Command { .. } => Box::new(process::CommandSource::new(spec)),
+        Transport::File { .. } => Box::new(tail::FileSource::new(spec)),
+        Transport::Stdin => Box::new(stdin::StdinSource::new(spec)),
+        _ => Box::new(net::NetSource::new(spec)),
+    }
+}
+
+/// Registers the source for commands and runs it on its own task.
+pub fn spawn_source(
+    source: Box<dyn EventSource>,
+    tx: mpsc::Sender<AppEvent>,
+    commands: &CommandRouter,
+    capture: Option<CaptureWriter>,
+    defmt: Option<Arc<defmt::Table>>,
+    shutdown: Shutdown,
+) -> JoinHandle<()> {
+    let ctx = SourceContext {
+        tx,
+        commands: commands.register(source.source_id()),
+        capture,
+        defmt,
+        shutdown,
+        reconnect_delay: RECONNECT_DELAY,
+    };
+    tokio::spawn(source.run(ctx))
+}
+
+pub use capture::CaptureWriter;
+pub use net::NetSource;
+pub use process::CommandSource;
+pub use replay::{ReplaySource, ReplaySpeed};
+pub use rfc2217::Rfc2217Source;
+pub use stdin::StdinSource;
+pub use tail::FileSource;
@@ -0,0 +1,240 @@
+use crate::core::{LogLevel, PortState, ResolvedPortSpec, SourceId, Transport};
+use crate::sources::link::{Link, deadline};
+use crate::sources::{EventSource, SourceContext, SourceFuture};
+
+use std::future::Future;
+use std::net::SocketAddr;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+
+/// A network port: TCP client or listener, UDP socket or Unix-domain socket.
+/// Lines are split and commands written exactly as for a serial port.
+pub struct NetSource {
+    spec: ResolvedPortSpec,
+}
+
+impl NetSource {
+    pub fn new(spec: ResolvedPortSpec) -> Self {
+        Self { spec }
+    }
+}
+
+impl EventSource for NetSource {
+    fn source_id(&self) -> SourceId {
+        self.spec.source_id()
+    }
+
+    fn run(self: Box<Self>, ctx: SourceContext) -> SourceFuture {
+        let spec = &self.spec;
+
+        match spec.transport.clone() {
+            Transport::TcpClient { addr } => {
+                let link = Link::new("tcp", spec, ctx);
+                let target = format!("tcp://{addr}");
+                Box::pin(run_connect_loop(link, target, move || {
+                    TcpStream::connect(addr.clone())
+                }))
+            }
+            Transport::TcpListen { addr } => {
+                Box::pin(run_tcp_listener(Link::new("tcp", spec, ctx), addr))
+            }
+            Transport::Udp { addr } => Box::pin(run_udp(Link::new("udp", spec, ctx), addr)),
+            Transport::Unix { path } => Box::pin(run_unix(Link::new("unix", spec, ctx), path)),
+            _ => {
+                let link = Link::new("net", spec, ctx);
+                Box::pin(async move {
+                    link.system(
+                        LogLevel::Error,
+                        format!("{} is not a network port", link.source.label()),
+                    )
+                    .await;
+                })
+            }
+        }
+    }
+}
+
+/// Connects with `connect`, pumps lines until the stream drops, and tries
+/// again after the reconnect delay.
+async fn run_connect_loop<F, Fut, S>(mut link: Link, target: String, connect: F)
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = std::io::Result<S>>,
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    loop {
+        if link.is_shutdown() {
+            break;
+        }
+
+        let mut stream = match link.until(connect()).await {
+            None => break,
+            Some(Ok(s)) => s,
+            Some(Err(e)) => {
+                link.system(
+                    LogLevel::Warn,
+                    format!("connect failed ({}): {}", target, e),
+                )
+                .await;
+                link.report_state(PortState::Disconnected).await;
+                link.wait_reconnect().await;
+                continue;
+            }
+        };
+
+        link.system(LogLevel::Info, format!("connected: {}", target))
+            .await;
+        link.report_state(PortState::Connected).await;
+
+        let disconnected = link.pump(&mut stream).await;
+
+        link.report_state(PortState::Disconnected).await;
+
+        if link.is_shutdown() {
+            break;
+        }
+
+        if disconnected {
+            link.wait_reconnect().await;
+        }
+    }
+}
+
+/// Accepts one client at a time; others wait in the listen backlog until
+/// the current one disconnects.
+async fn run_tcp_listener(mut link: Link, addr: String) {
+    let listener = loop {
+        match link.until(TcpListener::bind(&addr)).await {
+            None => return,
+            Some(Ok(l)) => break l,
+            Some(Err(e)) => {
+                link.system(LogLevel::Warn, format!("listen failed ({}): {}", addr, e))
+                    .await;
+                link.wait_reconnect().await;
+            }
+        }
+    };
+
+    link.system(LogLevel::Info, format!("listening on tcp://{}", addr))
+        .await;
+    link.report_state(PortState::Disconnected).await;
+
+    loop {
+        let (mut stream, peer) = match link.until(listener.accept()).await {
+            None => break,
+            Some(Ok(c)) => c,
+            Some(Err(e)) => {
+                link.system(LogLevel::Warn, format!("accept failed ({}): {}", addr, e))
+                    .await;
+                link.wait_reconnect().await;
+                continue;
+            }
+        };
+
+        link.system(LogLevel::Info, format!("client connected: {}", peer))
+            .await;
+        link.report_state(PortState::Connected).await;
+
+        link.pump(&mut stream).await;
+
+        link.report_state(PortState::Disconnected).await;
+
+        if link.is_shutdown() {
+            break;
+        }
+        link.system(LogLevel::Info, format!("client disconnected: {}", peer))
+            .await;
+    }
+}
+
+/// Reads datagrams on a bound socket. Commands go to whoever sent the most
+/// recent datagram.
+async fn run_udp(mut link: Link, addr: String) {
+    let socket = loop {
+        match link.until(UdpSocket::bind(&addr)).await {
+            None => return,
+            Some(Ok(s)) => break s,
+            Some(Err(e)) => {
+                link.system(LogLevel::Warn, format!("bind failed ({}): {}", addr, e))
+                    .await;
+                link.wait_reconnect().await;
+            }
+        }
+    };
+
+    link.system(LogLevel::Info, format!("listening on udp://{}", addr))
+        .await;
+    link.report_state(PortState::Connected).await;
+
+    let mut buf = vec![0u8; 64 * 1024];
+    let mut peer: Option<SocketAddr> = None;
+
+    loop {
+        if link.is_shutdown() {
+            break;
+        }
+
+        let idle = link.idle_deadline();
+        tokio::select! {
+            _ = deadline(idle) => {
+                if !link.flush().await {
+                    break;
+                }
+            }
+            _ = link.shutdown.changed() => {}
+            res = socket.recv_from(&mut buf) => match res {
+                Ok((n, from)) => {
+                    peer = Some(from);
+                    // A datagram is a whole record: its last line does not
+                    // wait for the next datagram's newline.
+                    if !link.feed(&buf[..n]).await || !link.flush().await {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    link.system(LogLevel::Warn, format!("udp receive failed: {e}")).await;
+                }
+            },
+            Some(cmd) = link.cmds.recv() => {
+                let Some(to) = peer else {
+                    link.system(
+                        LogLevel::Warn,
+                        format!("{}: no peer yet: command dropped", link.source.label()),
+                    )
+                    .await;
+                    continue;
+                };
+                match socket.send_to(&link.command_bytes(&cmd), to).await {
+                    Ok(_) => {
+                        if !link.echo(cmd).await {
+                            break;
+                        }
+                    }
+                    Err(e) => {
+                        link.system(LogLevel::Error, format!("udp write failed: {e}")).await;
+                    }
+                }
+            }
+        }
+    }
+
+    link.report_state(PortState::Disconnected).await;
+}
+
+#[cfg(unix)]
+async fn run_unix(link: Link, path: String) {
+    let target = format!("unix://{path}");
+    run_connect_loop(link, target, move || {
+        tokio::net::UnixStream::connect(path.clone())
+    })
+    .await;
+}
+
+#[cfg(not(unix))]
+async fn run_unix(link: Link, path: String) {
+    link.system(
+        LogLevel::Error,
+        format!("unix://{path}: Unix-domain sockets are not supported on this platform"),
+    )
+    .await;
+}
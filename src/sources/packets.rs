@@ -0,0 +1,476 @@
+use crate::core::{Crc, PacketFraming, PacketKind};
+use std::fmt;
+
+/// Longest frame accepted; anything larger is treated as line noise.
+const MAX_FRAME: usize = 64 * 1024;
+
+const SLIP_END: u8 = 0xC0;
+const SLIP_ESC: u8 = 0xDB;
+const SLIP_ESC_END: u8 = 0xDC;
+const SLIP_ESC_ESC: u8 = 0xDD;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum FrameError {
+    Crc {
+        len: usize,
+        expected: u32,
+        actual: u32,
+    },
+    Cobs {
+        len: usize,
+    },
+    Slip {
+        len: usize,
+    },
+    TooShort {
+        len: usize,
+    },
+    Oversize {
+        len: usize,
+    },
+}
+
+impl FrameError {
+    fn is_crc(&self) -> bool {
+        matches!(self, Self::Crc { .. })
+    }
+}
+
+impl fmt::Display for FrameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Crc {
+                len,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "CRC mismatch in {len}-byte frame (stored {expected:#x}, computed {actual:#x})"
+            ),
+            Self::Cobs { len } => write!(f, "malformed COBS frame ({len} bytes)"),
+            Self::Slip { len } => write!(f, "bad SLIP escape in {len}-byte frame"),
+            Self::TooShort { len } => write!(f, "{len}-byte frame is too short for its CRC"),
+            Self::Oversize { len } => {
+                write!(f, "no frame boundary within {len} bytes, discarded")
+            }
+        }
+    }
+}
+
+/// Cuts a byte stream into binary frames according to a port's
+/// `PacketFraming`, checking each frame's CRC.
+pub(crate) struct PacketDecoder {
+    framing: PacketFraming,
+    acc: Vec<u8>,
+    pub crc_errors: u64,
+    pub framing_errors: u64,
+}
+
+impl PacketDecoder {
+    pub fn new(framing: PacketFraming) -> Self {
+        Self {
+            framing,
+            acc: Vec::with_capacity(4096),
+            crc_errors: 0,
+            framing_errors: 0,
+        }
+    }
+
+    pub fn framing(&self) -> PacketFraming {
+        self.framing
+    }
+
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.acc.extend_from_slice(bytes);
+    }
+
+    pub fn clear(&mut self) {
+        self.acc.clear();
+    }
+
+    /// The next complete frame without its CRC, or why the bytes that made
+    /// it up were dropped.
+    pub fn next_frame(&mut self) -> Option<Result<Vec<u8>, FrameError>> {
+        let res = loop {
+            let frame = match self.framing.kind {
+                PacketKind::Cobs => self.delimited(0x00).map(|raw| {
+                    raw.and_then(|raw| cobs_decode(&raw).ok_or(FrameError::Cobs { len: raw.len() }))
+                }),
+                PacketKind::Slip => self.delimited(SLIP_END).map(|raw| {
+                    raw.and_then(|raw| slip_decode(&raw).ok_or(FrameError::Slip { len: raw.len() }))
+                }),
+                PacketKind::Length { width, big_endian } => {
+                    self.length_prefixed(width as usize, big_endian)
+                }
+            }?;
+
+            match frame {
+                // Back-to-back delimiters carry nothing.
+                Ok(frame) if frame.is_empty() => continue,
+                Ok(frame) => break check_crc(self.framing.crc, frame),
+                Err(e) => break Err(e),
+            }
+        };
+
+        if let Err(e) = &res {
+            if e.is_crc() {
+                self.crc_errors += 1;
+            } else {
+                self.framing_errors += 1;
+            }
+        }
+        Some(res)
+    }
+
+    /// The bytes up to the next `end` marker, still encoded.
+    fn delimited(&mut self, end: u8) -> Option<Result<Vec<u8>, FrameError>> {
+        match self.acc.iter().position(|&b| b == end) {
+            Some(pos) => {
+                let raw = self.acc[..pos].to_vec();
+                self.acc.drain(..=pos);
+                Some(Ok(raw))
+            }
+            None if self.acc.len() > MAX_FRAME => {
+                let len = self.acc.len();
+                self.acc.clear();
+                Some(Err(FrameError::Oversize { len }))
+            }
+            None => None,
+        }
+    }
+
+    fn length_prefixed(
+        &mut self,
+        width: usize,
+        big_endian: bool,
+    ) -> Option<Result<Vec<u8>, FrameError>> {
+        let header = self.framing.header;
+        if self.acc.len() < header {
+            return None;
+        }
+
+        let field = &self.acc[..width];
+        let len = if big_endian {
+            field.iter().fold(0usize, |n, &b| n << 8 | b as usize)
+        } else {
+            field.iter().rev().fold(0usize, |n, &b| n << 8 | b as usize)
+        };
+        let total = header + len;
+
+        // A length this large means we are out of step with the sender:
+        // drop one byte and look for a plausible header after it.
+        if total > MAX_FRAME {
+            self.acc.remove(0);
+            return Some(Err(FrameError::Oversize { len: total }));
+        }
+        if self.acc.len() < total {
+            return None;
+        }
+        Some(Ok(self.acc.drain(..total).collect()))
+    }
+}
+
+fn check_crc(crc: Crc, mut frame: Vec<u8>) -> Result<Vec<u8>, FrameError> {
+    let size = crc_len(crc);
+    if size == 0 {
+        return Ok(frame);
+    }
+    if frame.len() <= size {
+        return Err(FrameError::TooShort { len: frame.len() });
+    }
+
+    let at = frame.len() - size;
+    let stored = &frame[at..];
+    let expected = match crc {
+        // CRC-16/CCITT-FALSE is conventionally sent high byte first.
+        Crc::Crc8 | Crc::Crc16 => stored.iter().fold(0u32, |n, &b| n << 8 | b as u32),
+        _ => stored.iter().rev().fold(0u32, |n, &b| n << 8 | b as u32),
+    };
+    let actual = checksum(crc, &frame[..at]);
+    if expected != actual {
+        return Err(FrameError::Crc {
+            len: frame.len(),
+            expected,
+            actual,
+        });
+    }
+    frame.truncate(at);
+    Ok(frame)
+}
+
+fn crc_len(crc: Crc) -> usize {
+    match crc {
+        Crc::None => 0,
+        Crc::Crc8 => 1,
+        Crc::Crc16 | Crc::Crc16Modbus => 2,
+        Crc::Crc32 => 4,
+    }
+}
+
+/// The checksum of `data` with the given algorithm.
+fn checksum(crc: Crc, data: &[u8]) -> u32 {
+    match crc {
+        Crc::None => 0,
+        Crc::Crc8 => data.iter().fold(0u8, |mut c, &b| {
+            c ^= b;
+            for _ in 0..8 {
+                c = if c & 0x80 != 0 { c << 1 ^ 0x07 } else { c << 1 };
+            }
+            c
+        }) as u32,
+        Crc::Crc16 => data.iter().fold(0xFFFFu16, |mut c, &b| {
+            c ^= (b as u16) << 8;
+            for _ in 0..8 {
+                c = if c & 0x8000 != 0 {
+                    c << 1 ^ 0x1021
+                } else {
+                    c << 1
+                };
+            }
+            c
+        }) as u32,
+        Crc::Crc16Modbus => data.iter().fold(0xFFFFu16, |mut c, &b| {
+            c ^= b as u16;
+            for _ in 0..8 {
+                c = if c & 1 != 0 { c >> 1 ^ 0xA001 } else { c >> 1 };
+            }
+            c
+        }) as u32,
+        Crc::Crc32 => !data.iter().fold(!0u32, |mut c, &b| {
+            c ^= b as u32;
+            for _ in 0..8 {
+                c = if c & 1 != 0 {
+                    c >> 1 ^ 0xEDB8_8320
+                } else {
+                    c >> 1
+                };
+            }
+            c
+        }),
+    }
+}
+
+fn cobs_decode(src: &[u8]) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(src.len());
+    let mut i = 0;
+    while i < src.len() {
+        let code = src[i] as usize;
+        if code == 0 {
+            return None;
+        }
+        let end = i + code;
+        if end > src.len() {
+            return None;
+        }
+        out.extend_from_slice(&src[i + 1..end]);
+        i = end;
+        if code < 0xFF && i < src.len() {
+            out.push(0);
+        }
+    }
+    Some(out)
+}
+
+fn slip_decode(src: &[u8]) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(src.len());
+    let mut bytes = src.iter();
+    while let Some(&b) = bytes.next() {
+        if b != SLIP_ESC {
+            out.push(b);
+            continue;
+        }
+        match bytes.next() {
+            Some(&SLIP_ESC_END) => out.push(SLIP_END),
+            Some(&SLIP_ESC_ESC) => out.push(SLIP_ESC),
+            _ => return None,
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHECK: &[u8] = b"123456789";
+
+    fn decoder(kind: PacketKind, header: usize, crc: Crc) -> PacketDecoder {
+        PacketDecoder::new(PacketFraming { kind, header, crc })
+    }
+
+    fn frames(d: &mut PacketDecoder, bytes: &[u8]) -> Vec<Result<Vec<u8>, FrameError>> {
+        d.push(bytes);
+        std::iter::from_fn(|| d.next_frame()).collect()
+    }
+
+    fn cobs_encode(data: &[u8]) -> Vec<u8> {
+        let mut out = vec![0];
+        let (mut code_at, mut code) = (0, 1u8);
+        for &b in data {
+            if b != 0 {
+                out.push(b);
+                code += 1;
+            }
+            if b == 0 || code == 0xFF {
+                out[code_at] = code;
+                code_at = out.len();
+                out.push(0);
+                code = 1;
+            }
+        }
+        out[code_at] = code;
+        out
+    }
+
+    #[test]
+    fn check_values() {
+        assert_eq!(checksum(Crc::Crc8, CHECK), 0xF4);
+        assert_eq!(checksum(Crc::Crc16, CHECK), 0x29B1);
+        assert_eq!(checksum(Crc::Crc16Modbus, CHECK), 0x4B37);
+        assert_eq!(checksum(Crc::Crc32, CHECK), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn crc_byte_order() {
+        let cases: [(Crc, &[u8]); 4] = [
+            (Crc::Crc8, &[0xF4]),
+            (Crc::Crc16, &[0x29, 0xB1]),
+            (Crc::Crc16Modbus, &[0x37, 0x4B]),
+            (Crc::Crc32, &[0x26, 0x39, 0xF4, 0xCB]),
+        ];
+        for (crc, stored) in cases {
+            let frame = [CHECK, stored].concat();
+            assert_eq!(check_crc(crc, frame), Ok(CHECK.to_vec()), "{crc:?}");
+
+            if stored.len() > 1 {
+                let swapped = stored.iter().rev().copied().collect::<Vec<_>>();
+                let frame = [CHECK, &swapped].concat();
+                assert!(matches!(check_crc(crc, frame), Err(FrameError::Crc { .. })));
+            }
+        }
+        assert_eq!(
+            check_crc(Crc::Crc16, vec![0x29, 0xB1]),
+            Err(FrameError::TooShort { len: 2 })
+        );
+    }
+
+    #[test]
+    fn cobs_round_trip() {
+        let run = vec![0x11; 254];
+        let inputs: [Vec<u8>; 5] = [
+            vec![0x01],
+            vec![0x00],
+            vec![0x11, 0x00, 0x00, 0x22],
+            run.clone(),
+            [&run[..], &[0x00, 0x22], &run[..]].concat(),
+        ];
+        for input in inputs {
+            let encoded = cobs_encode(&input);
+            assert!(!encoded.contains(&0));
+            assert_eq!(cobs_decode(&encoded), Some(input.clone()));
+
+            let mut d = decoder(PacketKind::Cobs, 0, Crc::None);
+            let mut stream = encoded.clone();
+            stream.push(0x00);
+            assert_eq!(frames(&mut d, &stream), vec![Ok(input)]);
+        }
+        assert_eq!(cobs_decode(&[0x05, 0x11]), None);
+    }
+
+    #[test]
+    fn cobs_stream_with_crc() {
+        let mut d = decoder(PacketKind::Cobs, 0, Crc::Crc16);
+        let good = cobs_encode(&[CHECK, &[0x29, 0xB1]].concat());
+        let bad = cobs_encode(&[CHECK, &[0x29, 0xB2]].concat());
+        let stream = [&[0x00][..], &good, &[0x00], &bad, &[0x00]].concat();
+
+        let out = frames(&mut d, &stream[..5]);
+        assert!(out.is_empty());
+        let out = frames(&mut d, &stream[5..]);
+        assert_eq!(out.len(), 2);
+        assert_eq!(out[0], Ok(CHECK.to_vec()));
+        assert!(matches!(out[1], Err(FrameError::Crc { .. })));
+        assert_eq!((d.crc_errors, d.framing_errors), (1, 0));
+    }
+
+    #[test]
+    fn slip() {
+        let mut d = decoder(PacketKind::Slip, 0, Crc::None);
+        let stream = [
+            0xC0, 0x01, 0xDB, 0xDC, 0x02, 0xDB, 0xDD, 0xC0, 0x03, 0xDB, 0x04, 0xC0,
+        ];
+        assert_eq!(
+            frames(&mut d, &stream),
+            vec![
+                Ok(vec![0x01, 0xC0, 0x02, 0xDB]),
+                Err(FrameError::Slip { len: 3 }),
+            ]
+        );
+        assert_eq!(d.framing_errors, 1);
+    }
+
+    #[test]
+    fn length_prefixed() {
+        let len16be = PacketKind::Length {
+            width: 2,
+            big_endian: true,
+        };
+        let len16le = PacketKind::Length {
+            width: 2,
+            big_endian: false,
+        };
+
+        // The header is kept and the length counts what follows it.
+        let mut d = decoder(len16be, 2, Crc::None);
+        assert!(frames(&mut d, &[0x00, 0x03, 0xAA]).is_empty());
+        assert_eq!(
+            frames(&mut d, &[0xBB, 0xCC, 0x00, 0x01]),
+            vec![Ok(vec![0x00, 0x03, 0xAA, 0xBB, 0xCC])]
+        );
+        assert_eq!(frames(&mut d, &[0xDD]), vec![Ok(vec![0x00, 0x01, 0xDD])]);
+
+        // A wider header: the length field, then a type byte.
+        let mut d = decoder(len16le, 3, Crc::None);
+        assert_eq!(
+            frames(&mut d, &[0x01, 0x00, 0x7F, 0xEE]),
+            vec![Ok(vec![0x01, 0x00, 0x7F, 0xEE])]
+        );
+
+        // The CRC covers the header too.
+        let frame = [&[0x0B][..], CHECK].concat();
+        let crc = checksum(Crc::Crc16Modbus, &frame) as u16;
+        let mut d = decoder(
+            PacketKind::Length {
+                width: 1,
+                big_endian: false,
+            },
+            1,
+            Crc::Crc16Modbus,
+        );
+        let stream = [&frame[..], &crc.to_le_bytes()].concat();
+        assert_eq!(frames(&mut d, &stream), vec![Ok(frame)]);
+    }
+
+    #[test]
+    fn length_resyncs_after_oversize() {
+        let mut d = decoder(
+            PacketKind::Length {
+                width: 4,
+                big_endian: true,
+            },
+            4,
+            Crc::None,
+        );
+        let out = frames(&mut d, &[0x01, 0x00, 0x00, 0x00, 0x01, 0xAB]);
+        assert_eq!(
+            out,
+            vec![
+                Err(FrameError::Oversize {
+                    len: 4 + 0x0100_0000
+                }),
+                Ok(vec![0x00, 0x00, 0x00, 0x01, 0xAB]),
+            ]
+        );
+        assert_eq!(d.framing_errors, 1);
+    }
+}
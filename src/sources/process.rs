@@ -0,0 +1,226 @@
+use crate::core::{LogLevel, PortState, ResolvedPortSpec, SourceId, Transport};
+use crate::sources::link::{Link, deadline};
+use crate::sources::{EventSource, SourceContext, SourceFuture};
+
+use std::process::{ExitStatus, Stdio};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::process::{Child, ChildStdin, Command};
+
+/// A child process run through the shell. Stdout lines carry the port's own
+/// `SourceId`, stderr lines a sibling one labelled `<label>:stderr`, and
+/// commands typed for the port are written to its stdin.
+pub struct CommandSource {
+    spec: ResolvedPortSpec,
+}
+
+impl CommandSource {
+    pub fn new(spec: ResolvedPortSpec) -> Self {
+        Self { spec }
+    }
+}
+
+impl EventSource for CommandSource {
+    fn source_id(&self) -> SourceId {
+        self.spec.source_id()
+    }
+
+    fn run(self: Box<Self>, ctx: SourceContext) -> SourceFuture {
+        let (command, restart) = match &self.spec.transport {
+            Transport::Command { command, restart } => (command.clone(), *restart),
+            _ => (self.spec.path.clone(), false),
+        };
+        let link = Link::new("cmd", &self.spec, ctx);
+        Box::pin(run_command(command, restart, link))
+    }
+}
+
+/// The `SourceId` stderr lines are reported under.
+pub fn stderr_id(source: &SourceId) -> SourceId {
+    SourceId {
+        port: format!("{}:stderr", source.port),
+        alias: Some(format!("{}:stderr", source.label())),
+    }
+}
+
+async fn run_command(command: String, restart: bool, mut out: Link) {
+    let mut err = out.fork(stderr_id(&out.source));
+    let target = format!("cmd:{command}");
+
+    loop {
+        if out.is_shutdown() {
+            break;
+        }
+
+        let mut child = match shell(&command).spawn() {
+            Ok(c) => c,
+            Err(e) => {
+                out.system(LogLevel::Warn, format!("spawn failed ({target}): {e}"))
+                    .await;
+                out.report_state(PortState::Disconnected).await;
+                if !restart {
+                    break;
+                }
+                out.wait_reconnect().await;
+                continue;
+            }
+        };
+
+        let pid = child
+            .id()
+            .map(|p| format!(" (pid {p})"))
+            .unwrap_or_default();
+        out.system(LogLevel::Info, format!("started: {target}{pid}"))
+            .await;
+        out.report_state(PortState::Connected).await;
+
+        let status = pump(&mut child, &mut out, &mut err).await;
+
+        out.report_state(PortState::Disconnected).await;
+
+        match status {
+            None => {
+                let _ = child.start_kill();
+                let _ = child.wait().await;
+                break;
+            }
+            Some(Ok(status)) => {
+                let level = if status.success() {
+                    LogLevel::Info
+                } else {
+                    LogLevel::Warn
+                };
+                let how = match status.code() {
+                    Some(code) => format!("exited with code {code}"),
+                    None => format!("terminated ({status})"),
+                };
+                out.system(level, format!("{target}: {how}")).await;
+            }
+            Some(Err(e)) => {
+                out.system(LogLevel::Error, format!("{target}: wait failed: {e}"))
+                    .await;
+            }
+        }
+
+        if !restart || out.is_shutdown() {
+            break;
+        }
+        out.wait_reconnect().await;
+    }
+}
+
+fn shell(command: &str) -> Command {
+    #[cfg(windows)]
+    let mut cmd = {
+        let mut c = Command::new("cmd");
+        c.arg("/C").arg(command);
+        c
+    };
+    #[cfg(not(windows))]
+    let mut cmd = {
+        let mut c = Command::new("sh");
+        c.arg("-c").arg(command);
+        c
+    };
+    cmd.stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    cmd
+}
+
+/// Forwards output and commands until both output streams close, then
+/// reaps the child. Returns `None` on shutdown.
+async fn pump(
+    child: &mut Child,
+    out: &mut Link,
+    err: &mut Link,
+) -> Option<std::io::Result<ExitStatus>> {
+    let mut stdin = child.stdin.take();
+    let mut stdout = child.stdout.take();
+    let mut stderr = child.stderr.take();
+    let mut out_buf = [0u8; 2048];
+    let mut err_buf = [0u8; 2048];
+
+    loop {
+        if out.is_shutdown() {
+            return None;
+        }
+
+        let out_idle = out.idle_deadline();
+        let err_idle = err.idle_deadline();
+        tokio::select! {
+            _ = deadline(out_idle) => {
+                if !out.flush().await {
+                    return None;
+                }
+            }
+            _ = deadline(err_idle) => {
+                if !err.flush().await {
+                    return None;
+                }
+            }
+            _ = out.shutdown.changed() => {}
+            Some(cmd) = out.cmds.recv() => {
+                if write_stdin(&mut stdin, &out.command_bytes(&cmd)).await {
+                    if !out.echo(cmd).await {
+                        return None;
+                    }
+                } else {
+                    stdin = None;
+                    out.system(
+                        LogLevel::Warn,
+                        format!("{}: stdin closed: command dropped", out.source.label()),
+                    )
+                    .await;
+                }
+            }
+            n = read_some(&mut stdout, &mut out_buf), if stdout.is_some() => {
+                if !forward(n, &mut stdout, &out_buf, out).await {
+                    return None;
+                }
+            }
+            n = read_some(&mut stderr, &mut err_buf), if stderr.is_some() => {
+                if !forward(n, &mut stderr, &err_buf, err).await {
+                    return None;
+                }
+            }
+            status = child.wait(), if stdout.is_none() && stderr.is_none() => {
+                return Some(status);
+            }
+        }
+    }
+}
+
+async fn read_some<R: AsyncRead + Unpin>(
+    stream: &mut Option<R>,
+    buf: &mut [u8],
+) -> std::io::Result<usize> {
+    match stream {
+        Some(s) => s.read(buf).await,
+        None => Ok(0),
+    }
+}
+
+/// Feeds what one output stream produced, closing it at end of stream.
+/// Returns `false` once the event bus is closed.
+async fn forward<R>(
+    n: std::io::Result<usize>,
+    stream: &mut Option<R>,
+    buf: &[u8],
+    link: &mut Link,
+) -> bool {
+    match n {
+        Ok(n) if n > 0 => link.feed(&buf[..n]).await,
+        _ => {
+            *stream = None;
+            link.flush().await
+        }
+    }
+}
+
+async fn write_stdin(stdin: &mut Option<ChildStdin>, bytes: &[u8]) -> bool {
+    let Some(pipe) = stdin else {
+        return false;
+    };
+    pipe.write_all(bytes).await.is_ok() && pipe.flush().await.is_ok()
+}
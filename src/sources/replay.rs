@@ -0,0 +1,262 @@
+use crate::core::{
+    AppEvent, LogLevel, PortDefaults, PortSpec, PortState, ResolvedPortSpec, SourceId,
+};
+use crate::runtime::{Shutdown, ShutdownHandle};
+use crate::sources::capture::CaptureReader;
+use crate::sources::link::Link;
+use crate::sources::process::stderr_id;
+use crate::sources::{SourceContext, defmt};
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::SystemTime;
+use tokio::io::BufReader;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::{Duration, Instant, sleep_until};
+
+/// How fast a capture is played back.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReplaySpeed {
+    /// Multiple of the original pace; `1.0` is real time.
+    Factor(f64),
+    /// No delays at all.
+    Max,
+}
+
+impl Default for ReplaySpeed {
+    fn default() -> Self {
+        Self::Factor(1.0)
+    }
+}
+
+impl FromStr for ReplaySpeed {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let t = s.trim().to_ascii_lowercase();
+        if t == "max" {
+            return Ok(Self::Max);
+        }
+        match t.trim_end_matches('x').parse::<f64>() {
+            Ok(f) if f.is_finite() && f > 0.0 => Ok(Self::Factor(f)),
+            _ => Err(format!(
+                "invalid replay speed '{s}' (expected e.g. 1, 10x, 0.5 or max)"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ReplaySpeed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Factor(x) => write!(f, "{x}x"),
+            Self::Max => f.write_str("max speed"),
+        }
+    }
+}
+
+/// Feeds a raw capture back through the event pipeline, framing and decoding
+/// each recorded port as its configured port would live. Lines keep their
+/// original timestamps.
+pub struct ReplaySource {
+    path: PathBuf,
+    speed: ReplaySpeed,
+    tx: mpsc::Sender<AppEvent>,
+    shutdown: Shutdown,
+    exit_when_done: Option<ShutdownHandle>,
+    ports: Vec<ResolvedPortSpec>,
+    defaults: PortDefaults,
+    tables: HashMap<PathBuf, Arc<defmt::Table>>,
+}
+
+impl ReplaySource {
+    /// Recorded sources are framed like the port in `ports` they came from,
+    /// or with `defaults` when none matches.
+    pub fn new(
+        path: PathBuf,
+        speed: ReplaySpeed,
+        defaults: PortDefaults,
+        ports: Vec<ResolvedPortSpec>,
+        tx: mpsc::Sender<AppEvent>,
+        shutdown: Shutdown,
+    ) -> Self {
+        Self {
+            path,
+            speed,
+            tx,
+            shutdown,
+            exit_when_done: None,
+            ports,
+            defaults,
+            tables: HashMap::new(),
+        }
+    }
+
+    /// The parsed defmt tables, by the ELF path a port names.
+    pub fn with_defmt(mut self, tables: HashMap<PathBuf, Arc<defmt::Table>>) -> Self {
+        self.tables = tables;
+        self
+    }
+
+    /// Ends the session once the whole capture has been replayed.
+    pub fn exit_when_done(mut self, handle: ShutdownHandle) -> Self {
+        self.exit_when_done = Some(handle);
+        self
+    }
+
+    pub fn spawn(self) -> JoinHandle<()> {
+        tokio::spawn(self.run())
+    }
+
+    async fn run(mut self) {
+        if let Err(e) = self.replay().await {
+            self.system(
+                LogLevel::Error,
+                format!("replay failed ({}): {e}", self.path.display()),
+            )
+            .await;
+        }
+
+        if let Some(handle) = &self.exit_when_done {
+            handle.trigger();
+        }
+    }
+
+    async fn replay(&mut self) -> std::io::Result<()> {
+        let file = tokio::fs::File::open(&self.path).await?;
+        let mut reader = CaptureReader::new(BufReader::new(file)).await?;
+
+        self.system(
+            LogLevel::Info,
+            format!("replaying {} at {}", self.path.display(), self.speed),
+        )
+        .await;
+
+        // Each port's link, and when its latest bytes were read.
+        let mut links: HashMap<SourceId, (Link, SystemTime)> = HashMap::new();
+        let mut clock: Option<(SystemTime, Instant)> = None;
+        let mut chunks = 0usize;
+
+        let result = loop {
+            if self.shutdown.is_triggered() {
+                break Ok(());
+            }
+
+            let chunk = match reader.next_chunk().await {
+                Ok(Some(c)) => c,
+                Ok(None) => break Ok(()),
+                Err(e) => break Err(e),
+            };
+            chunks += 1;
+
+            if let ReplaySpeed::Factor(factor) = self.speed {
+                let (first_ts, started) = *clock.get_or_insert((chunk.ts, Instant::now()));
+                let offset = chunk.ts.duration_since(first_ts).unwrap_or_default();
+                let due = started + Duration::from_secs_f64(offset.as_secs_f64() / factor);
+
+                tokio::select! {
+                    _ = self.shutdown.changed() => continue,
+                    _ = sleep_until(due) => {}
+                }
+            }
+
+            if !links.contains_key(&chunk.source) {
+                let mut link = self.link(&chunk.source);
+                link.report_state(PortState::Connected).await;
+                links.insert(chunk.source.clone(), (link, chunk.ts));
+            }
+            let Some((link, last)) = links.get_mut(&chunk.source) else {
+                continue;
+            };
+
+            // The recorded gap stands in for the live idle timeout.
+            let gap = chunk.ts.duration_since(*last).unwrap_or_default();
+            if link.idle_flush().is_some_and(|after| gap >= after) && !link.flush().await {
+                return Ok(());
+            }
+            *last = chunk.ts;
+            if !link.feed_at(chunk.ts, &chunk.bytes).await {
+                return Ok(());
+            }
+        };
+
+        for (link, _) in links.values_mut() {
+            if !link.flush().await {
+                return Ok(());
+            }
+            link.report_state(PortState::Disconnected).await;
+        }
+
+        if reader.truncated() {
+            self.system(
+                LogLevel::Info,
+                "capture ends in a partial record, the recording was cut short".to_string(),
+            )
+            .await;
+        }
+
+        if result.is_ok() && !self.shutdown.is_triggered() {
+            self.system(
+                LogLevel::Info,
+                format!(
+                    "replay finished: {chunks} reads from {} port(s)",
+                    links.len()
+                ),
+            )
+            .await;
+        }
+        result
+    }
+
+    /// A link set up like the configured port `source` was recorded from:
+    /// the same id, a command port's stderr, or the same alias. Other
+    /// sources get the port defaults.
+    fn link(&self, source: &SourceId) -> Link {
+        let spec = self
+            .ports
+            .iter()
+            .find(|p| {
+                let id = p.source_id();
+                id == *source
+                    || stderr_id(&id) == *source
+                    || (p.alias.is_some() && p.alias == source.alias)
+            })
+            .cloned()
+            .unwrap_or_else(|| {
+                PortSpec {
+                    path: source.port.clone(),
+                    alias: source.alias.clone(),
+                    ..PortSpec::default()
+                }
+                .resolve(&self.defaults)
+            });
+
+        let (_, commands) = mpsc::channel(1);
+        let ctx = SourceContext {
+            tx: self.tx.clone(),
+            commands,
+            capture: None,
+            defmt: spec
+                .defmt
+                .as_ref()
+                .and_then(|p| self.tables.get(p))
+                .cloned(),
+            shutdown: self.shutdown.clone(),
+            reconnect_delay: Duration::ZERO,
+        };
+        let link = Link::new("replay", &spec, ctx);
+        if link.source == *source {
+            link
+        } else {
+            link.fork(source.clone())
+        }
+    }
+
+    async fn system(&self, level: LogLevel, message: String) {
+        let _ = self.tx.send(AppEvent::System { level, message }).await;
+    }
+}
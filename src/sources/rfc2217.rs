@@ -0,0 +1,515 @@
+use crate::core::{
+    FlowControl, Framing, LinePolicy, LogLevel, Parity, PortState, ResolvedPortSpec, SourceId,
+    Transport,
+};
+use crate::sources::link::{Link, deadline};
+use crate::sources::{EventSource, SourceContext, SourceFuture};
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::{Duration, Instant, sleep_until};
+
+// Telnet (RFC 854) commands.
+pub const IAC: u8 = 255;
+pub const DONT: u8 = 254;
+pub const DO: u8 = 253;
+pub const WONT: u8 = 252;
+pub const WILL: u8 = 251;
+pub const SB: u8 = 250;
+pub const SE: u8 = 240;
+
+// Telnet options.
+pub const OPT_BINARY: u8 = 0;
+pub const OPT_SGA: u8 = 3;
+pub const OPT_COM_PORT: u8 = 44;
+
+// COM-PORT-OPTION (RFC 2217) client commands; the server answers with the
+// same code plus 100.
+pub const SET_BAUDRATE: u8 = 1;
+pub const SET_DATASIZE: u8 = 2;
+pub const SET_PARITY: u8 = 3;
+pub const SET_STOPSIZE: u8 = 4;
+pub const SET_CONTROL: u8 = 5;
+pub const SERVER_OFFSET: u8 = 100;
+
+const CONTROL_NO_FLOW: u8 = 1;
+const CONTROL_XON_XOFF: u8 = 2;
+const CONTROL_HARDWARE: u8 = 3;
+const CONTROL_DTR_ON: u8 = 8;
+const CONTROL_DTR_OFF: u8 = 9;
+const CONTROL_RTS_ON: u8 = 11;
+const CONTROL_RTS_OFF: u8 = 12;
+
+/// How long the server gets to accept the COM-PORT option.
+const NEGOTIATION_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A serial port exported by an RFC 2217 server (ser2net, `socat`, pyserial
+/// `rfc2217_server`, ...). Baudrate, framing, flow control and DTR/RTS are
+/// applied over Telnet the same way `open_serial` applies them locally.
+pub struct Rfc2217Source {
+    spec: ResolvedPortSpec,
+    addr: String,
+}
+
+impl Rfc2217Source {
+    pub fn new(spec: ResolvedPortSpec) -> Self {
+        let addr = match &spec.transport {
+            Transport::Rfc2217 { addr } => addr.clone(),
+            _ => spec.path.clone(),
+        };
+        Self { spec, addr }
+    }
+}
+
+impl EventSource for Rfc2217Source {
+    fn source_id(&self) -> SourceId {
+        self.spec.source_id()
+    }
+
+    fn run(self: Box<Self>, ctx: SourceContext) -> SourceFuture {
+        let link = Link::new("rfc2217", &self.spec, ctx);
+        Box::pin(run_rfc2217(self.spec, self.addr, link))
+    }
+}
+
+async fn run_rfc2217(spec: ResolvedPortSpec, addr: String, mut link: Link) {
+    loop {
+        if link.is_shutdown() {
+            break;
+        }
+
+        let mut stream = match link.until(TcpStream::connect(&addr)).await {
+            None => break,
+            Some(Ok(s)) => s,
+            Some(Err(e)) => {
+                link.system(
+                    LogLevel::Warn,
+                    format!("connect failed ({}): {}", spec.path, e),
+                )
+                .await;
+                link.report_state(PortState::Disconnected).await;
+                link.wait_reconnect().await;
+                continue;
+            }
+        };
+
+        link.system(
+            LogLevel::Info,
+            format!("connected: {} @ {} {}", spec.path, spec.baud, spec.framing),
+        )
+        .await;
+        link.report_state(PortState::Connected).await;
+
+        let disconnected = pump(&mut stream, &spec, &mut link).await;
+
+        link.report_state(PortState::Disconnected).await;
+
+        if link.is_shutdown() {
+            break;
+        }
+
+        if disconnected {
+            link.wait_reconnect().await;
+        }
+    }
+}
+
+/// Negotiates the COM-PORT option and then carries data both ways. Returns
+/// `true` when the connection dropped and should be retried.
+async fn pump(stream: &mut TcpStream, spec: &ResolvedPortSpec, link: &mut Link) -> bool {
+    let mut session = Session::new(spec);
+    let mut buf = [0u8; 2048];
+    let mut data = Vec::with_capacity(2048);
+    let negotiation_deadline = sleep_until(Instant::now() + NEGOTIATION_TIMEOUT);
+    tokio::pin!(negotiation_deadline);
+
+    if let Err(e) = stream.write_all(&session.hello()).await {
+        link.system(LogLevel::Error, format!("rfc2217 write failed: {e}"))
+            .await;
+        return true;
+    }
+
+    loop {
+        if link.is_shutdown() {
+            return false;
+        }
+
+        let idle = link.idle_deadline();
+        tokio::select! {
+            _ = deadline(idle) => {
+                if !link.flush().await {
+                    return false;
+                }
+            }
+            _ = link.shutdown.changed() => {}
+            _ = &mut negotiation_deadline, if session.com_port == OptionState::Requested => {
+                session.com_port = OptionState::Refused;
+                link.system(
+                    LogLevel::Warn,
+                    format!(
+                        "{}: server did not answer the COM-PORT option; port settings not applied",
+                        spec.path
+                    ),
+                )
+                .await;
+            }
+            Some(cmd) = link.cmds.recv() => {
+                let bytes = escape_iac(&link.command_bytes(&cmd));
+                if let Err(e) = stream.write_all(&bytes).await {
+                    link.system(LogLevel::Error, format!("rfc2217 write failed: {e}")).await;
+                    return true;
+                }
+                if !link.echo(cmd).await {
+                    return false;
+                }
+            }
+            res = stream.read(&mut buf) => {
+                let n = match res {
+                    Ok(0) => {
+                        link.system(LogLevel::Warn, "rfc2217 EOF".to_string()).await;
+                        return true;
+                    }
+                    Ok(n) => n,
+                    Err(e) => {
+                        link.system(LogLevel::Error, format!("rfc2217 read failed: {e}")).await;
+                        return true;
+                    }
+                };
+
+                data.clear();
+                let mut notes = Vec::new();
+                let reply = session.receive(&buf[..n], &mut data, &mut notes);
+
+                if !reply.is_empty()
+                    && let Err(e) = stream.write_all(&reply).await
+                {
+                    link.system(LogLevel::Error, format!("rfc2217 write failed: {e}")).await;
+                    return true;
+                }
+                for (level, note) in notes {
+                    link.system(level, format!("{}: {note}", spec.path)).await;
+                }
+                if !data.is_empty() && !link.feed(&data).await {
+                    return false;
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OptionState {
+    Requested,
+    Accepted,
+    Refused,
+}
+
+/// Client side of the Telnet session: option negotiation, COM-PORT
+/// settings and their acknowledgements.
+struct Session {
+    decoder: TelnetDecoder,
+    settings: Vec<(u8, Vec<u8>)>,
+    com_port: OptionState,
+    local_binary: bool,
+    remote_binary: bool,
+    remote_sga: bool,
+}
+
+impl Session {
+    fn new(spec: &ResolvedPortSpec) -> Self {
+        Self {
+            decoder: TelnetDecoder::default(),
+            settings: port_settings(spec),
+            com_port: OptionState::Requested,
+            local_binary: true,
+            remote_binary: true,
+            remote_sga: true,
+        }
+    }
+
+    /// Initial offers: binary both ways, no go-ahead, and the COM-PORT option.
+    fn hello(&self) -> Vec<u8> {
+        [
+            [IAC, WILL, OPT_BINARY],
+            [IAC, DO, OPT_BINARY],
+            [IAC, DO, OPT_SGA],
+            [IAC, WILL, OPT_COM_PORT],
+        ]
+        .concat()
+    }
+
+    /// Decodes bytes from the server. Port data is appended to `data`,
+    /// anything worth reporting to `notes`; returns the bytes to send back.
+    fn receive(
+        &mut self,
+        input: &[u8],
+        data: &mut Vec<u8>,
+        notes: &mut Vec<(LogLevel, String)>,
+    ) -> Vec<u8> {
+        let mut reply = Vec::new();
+        let mut messages = Vec::new();
+        self.decoder.feed(input, data, &mut messages);
+
+        for msg in messages {
+            match msg {
+                TelnetMessage::Negotiate(cmd, opt) => {
+                    self.negotiate(cmd, opt, &mut reply, notes);
+                }
+                TelnetMessage::Sub(payload) => {
+                    if let Some((&OPT_COM_PORT, rest)) = payload.split_first() {
+                        self.com_port_reply(rest, notes);
+                    }
+                }
+            }
+        }
+        reply
+    }
+
+    fn negotiate(
+        &mut self,
+        cmd: u8,
+        opt: u8,
+        reply: &mut Vec<u8>,
+        notes: &mut Vec<(LogLevel, String)>,
+    ) {
+        match (cmd, opt) {
+            (DO, OPT_COM_PORT) if self.com_port != OptionState::Accepted => {
+                self.com_port = OptionState::Accepted;
+                for (code, value) in &self.settings {
+                    reply.extend(subnegotiation(*code, value));
+                }
+            }
+            (DONT, OPT_COM_PORT) if self.com_port != OptionState::Refused => {
+                self.com_port = OptionState::Refused;
+                notes.push((
+                    LogLevel::Warn,
+                    "server refused the COM-PORT option; port settings not applied".into(),
+                ));
+            }
+            (DO | DONT, OPT_COM_PORT) => {}
+            (DO | DONT, OPT_BINARY) => {
+                let on = cmd == DO;
+                if self.local_binary != on {
+                    self.local_binary = on;
+                    reply.extend([IAC, if on { WILL } else { WONT }, opt]);
+                }
+            }
+            (WILL | WONT, OPT_BINARY | OPT_SGA) => {
+                let on = cmd == WILL;
+                let enabled = if opt == OPT_BINARY {
+                    &mut self.remote_binary
+                } else {
+                    &mut self.remote_sga
+                };
+                if *enabled != on {
+                    *enabled = on;
+                    reply.extend([IAC, if on { DO } else { DONT }, opt]);
+                }
+            }
+            // Refuse everything else; never answer a refusal.
+            (DO, _) => reply.extend([IAC, WONT, opt]),
+            (WILL, _) => reply.extend([IAC, DONT, opt]),
+            _ => {}
+        }
+    }
+
+    /// Checks a server acknowledgement against what was asked for.
+    fn com_port_reply(&self, payload: &[u8], notes: &mut Vec<(LogLevel, String)>) {
+        let Some((&code, value)) = payload.split_first() else {
+            return;
+        };
+        let Some(request) = code.checked_sub(SERVER_OFFSET) else {
+            return;
+        };
+        // SET-CONTROL answers report line states and are not compared.
+        if request == SET_CONTROL {
+            return;
+        }
+        let Some((_, wanted)) = self.settings.iter().find(|(c, _)| *c == request) else {
+            return;
+        };
+        if value != wanted.as_slice() {
+            notes.push((
+                LogLevel::Warn,
+                format!(
+                    "server set {} to {} instead of {}",
+                    setting_name(request),
+                    setting_value(request, value),
+                    setting_value(request, wanted)
+                ),
+            ));
+        }
+    }
+}
+
+/// The COM-PORT commands that reproduce the spec's local port setup.
+fn port_settings(spec: &ResolvedPortSpec) -> Vec<(u8, Vec<u8>)> {
+    let Framing {
+        data_bits,
+        parity,
+        stop_bits,
+    } = spec.framing;
+
+    let mut settings = vec![
+        (SET_BAUDRATE, spec.baud.to_be_bytes().to_vec()),
+        (SET_DATASIZE, vec![data_bits]),
+        (
+            SET_PARITY,
+            vec![match parity {
+                Parity::None => 1,
+                Parity::Odd => 2,
+                Parity::Even => 3,
+            }],
+        ),
+        (SET_STOPSIZE, vec![stop_bits]),
+        (
+            SET_CONTROL,
+            vec![match spec.flow_control {
+                FlowControl::None => CONTROL_NO_FLOW,
+                FlowControl::Software => CONTROL_XON_XOFF,
+                FlowControl::Hardware => CONTROL_HARDWARE,
+            }],
+        ),
+    ];
+
+    match spec.dtr {
+        LinePolicy::On => settings.push((SET_CONTROL, vec![CONTROL_DTR_ON])),
+        LinePolicy::Off => settings.push((SET_CONTROL, vec![CONTROL_DTR_OFF])),
+        LinePolicy::Keep => {}
+    }
+    // With RTS/CTS handshaking the server's driver owns RTS.
+    if spec.flow_control != FlowControl::Hardware {
+        match spec.rts {
+            LinePolicy::On => settings.push((SET_CONTROL, vec![CONTROL_RTS_ON])),
+            LinePolicy::Off => settings.push((SET_CONTROL, vec![CONTROL_RTS_OFF])),
+            LinePolicy::Keep => {}
+        }
+    }
+
+    settings
+}
+
+fn setting_name(code: u8) -> &'static str {
+    match code {
+        SET_BAUDRATE => "baudrate",
+        SET_DATASIZE => "data bits",
+        SET_PARITY => "parity",
+        SET_STOPSIZE => "stop bits",
+        _ => "control",
+    }
+}
+
+fn setting_value(code: u8, value: &[u8]) -> String {
+    match (code, value) {
+        (SET_BAUDRATE, &[a, b, c, d]) => u32::from_be_bytes([a, b, c, d]).to_string(),
+        (SET_PARITY, &[p]) => match p {
+            1 => "none".to_string(),
+            2 => "odd".to_string(),
+            3 => "even".to_string(),
+            4 => "mark".to_string(),
+            5 => "space".to_string(),
+            other => other.to_string(),
+        },
+        (SET_STOPSIZE, &[3]) => "1.5".to_string(),
+        (_, &[v]) => v.to_string(),
+        _ => format!("{value:02x?}"),
+    }
+}
+
+/// `IAC SB COM-PORT-OPTION <code> <value> IAC SE`, with IAC bytes in the
+/// value doubled.
+pub fn subnegotiation(code: u8, value: &[u8]) -> Vec<u8> {
+    let mut out = vec![IAC, SB, OPT_COM_PORT, code];
+    out.extend(escape_iac(value));
+    out.extend([IAC, SE]);
+    out
+}
+
+/// Doubles every 0xFF byte so it is sent as data rather than a command.
+pub fn escape_iac(bytes: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(bytes.len());
+    for &b in bytes {
+        out.push(b);
+        if b == IAC {
+            out.push(IAC);
+        }
+    }
+    out
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TelnetMessage {
+    /// `IAC <WILL|WONT|DO|DONT> <option>`
+    Negotiate(u8, u8),
+    /// Subnegotiation payload, starting with the option code.
+    Sub(Vec<u8>),
+}
+
+#[derive(Debug, Default)]
+enum DecodeState {
+    #[default]
+    Data,
+    Iac,
+    Negotiate(u8),
+    Sub,
+    SubIac,
+}
+
+/// Splits a Telnet byte stream into data and protocol messages. Sequences
+/// may be cut anywhere between two reads.
+#[derive(Debug, Default)]
+pub struct TelnetDecoder {
+    state: DecodeState,
+    sub: Vec<u8>,
+}
+
+impl TelnetDecoder {
+    pub fn feed(&mut self, input: &[u8], data: &mut Vec<u8>, messages: &mut Vec<TelnetMessage>) {
+        for &b in input {
+            self.state = match std::mem::take(&mut self.state) {
+                DecodeState::Data if b == IAC => DecodeState::Iac,
+                DecodeState::Data => {
+                    data.push(b);
+                    DecodeState::Data
+                }
+                DecodeState::Iac => match b {
+                    IAC => {
+                        data.push(IAC);
+                        DecodeState::Data
+                    }
+                    WILL | WONT | DO | DONT => DecodeState::Negotiate(b),
+                    SB => {
+                        self.sub.clear();
+                        DecodeState::Sub
+                    }
+                    // NOP, GA, and the other single-byte commands.
+                    _ => DecodeState::Data,
+                },
+                DecodeState::Negotiate(cmd) => {
+                    messages.push(TelnetMessage::Negotiate(cmd, b));
+                    DecodeState::Data
+                }
+                DecodeState::Sub if b == IAC => DecodeState::SubIac,
+                DecodeState::Sub => {
+                    self.sub.push(b);
+                    DecodeState::Sub
+                }
+                DecodeState::SubIac => match b {
+                    IAC => {
+                        self.sub.push(IAC);
+                        DecodeState::Sub
+                    }
+                    SE => {
+                        messages.push(TelnetMessage::Sub(std::mem::take(&mut self.sub)));
+                        DecodeState::Data
+                    }
+                    // Malformed: drop the subnegotiation.
+                    _ => {
+                        self.sub.clear();
+                        DecodeState::Data
+                    }
+                },
+            };
+        }
+    }
+}
@@ -0,0 +1,272 @@
+use crate::config::DiscoveryConfig;
+use crate::core::{
+    AppEvent, LogLevel, PortDefaults, PortSpec, ResolvedPortSpec, SourceId, UsbMatch,
+};
+use crate::runtime::{CommandRouter, Shutdown, ShutdownHandle, shutdown_channel};
+use crate::sources::serial::SerialSource;
+use crate::sources::serial::scan::{self, PortInfo};
+use crate::sources::{CaptureWriter, spawn_source};
+
+use std::collections::{HashMap, HashSet};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
+
+const MAX_ALIAS_LEN: usize = 24;
+
+/// Periodically rescans serial ports and runs a source task for every
+/// matching device while it is present.
+pub struct HotplugWatcher {
+    rule: DiscoveryConfig,
+    defaults: PortDefaults,
+    claimed: Vec<ResolvedPortSpec>,
+    tx: mpsc::Sender<AppEvent>,
+    shutdown: Shutdown,
+    commands: CommandRouter,
+    capture: Option<CaptureWriter>,
+}
+
+struct Attached {
+    source: SourceId,
+    stop: ShutdownHandle,
+    task: JoinHandle<()>,
+}
+
+impl HotplugWatcher {
+    /// `claimed` are the statically configured ports; devices they select
+    /// are left alone.
+    pub fn new(
+        rule: DiscoveryConfig,
+        defaults: PortDefaults,
+        claimed: Vec<ResolvedPortSpec>,
+        tx: mpsc::Sender<AppEvent>,
+        shutdown: Shutdown,
+    ) -> Self {
+        Self {
+            rule,
+            defaults,
+            claimed,
+            tx,
+            shutdown,
+            commands: CommandRouter::new(),
+            capture: None,
+        }
+    }
+
+    pub fn with_commands(mut self, commands: CommandRouter) -> Self {
+        self.commands = commands;
+        self
+    }
+
+    pub fn with_capture(mut self, capture: Option<CaptureWriter>) -> Self {
+        self.capture = capture;
+        self
+    }
+
+    pub fn spawn(self) -> JoinHandle<()> {
+        tokio::spawn(self.run())
+    }
+
+    async fn run(mut self) {
+        let mut attached: HashMap<String, Attached> = HashMap::new();
+        let mut ticker = tokio::time::interval(self.rule.interval);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            if self.shutdown.is_triggered() {
+                break;
+            }
+
+            tokio::select! {
+                _ = self.shutdown.changed() => continue,
+                _ = ticker.tick() => {}
+            }
+
+            let (ports, resolved) = match self.scan().await {
+                Ok(scan) => scan,
+                Err(e) => {
+                    self.system(LogLevel::Warn, format!("hotplug scan failed: {e}"))
+                        .await;
+                    continue;
+                }
+            };
+
+            let present = ports
+                .iter()
+                .map(|p| p.path.as_str())
+                .collect::<HashSet<_>>();
+            let gone = attached
+                .keys()
+                .filter(|path| !present.contains(path.as_str()))
+                .cloned()
+                .collect::<Vec<_>>();
+            for path in gone {
+                if let Some(a) = attached.remove(&path) {
+                    self.detach(&path, a).await;
+                }
+            }
+
+            for info in ports {
+                if attached.contains_key(&info.path)
+                    || !self.matches(&info)
+                    || self.is_claimed(&info, &resolved)
+                {
+                    continue;
+                }
+                let a = self.attach(&info, &attached).await;
+                attached.insert(info.path, a);
+            }
+        }
+
+        for (_, a) in attached {
+            a.stop.trigger();
+            let _ = a.task.await;
+        }
+    }
+
+    /// Lists the ports, and resolves the claimed device paths to the nodes
+    /// they link to, off the async runtime: both touch sysfs or IOKit.
+    async fn scan(&self) -> Result<(Vec<PortInfo>, HashSet<String>), String> {
+        let claimed = self
+            .claimed
+            .iter()
+            .filter(|spec| spec.usb.is_none())
+            .map(|spec| spec.path.clone())
+            .collect::<Vec<_>>();
+
+        tokio::task::spawn_blocking(move || {
+            let ports = scan::list_available_ports(None, true).map_err(|e| e.to_string())?;
+            let resolved = claimed
+                .iter()
+                .filter_map(|path| std::fs::canonicalize(path).ok())
+                .map(|p| p.to_string_lossy().into_owned())
+                .collect();
+            Ok((ports, resolved))
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+
+    fn matches(&self, info: &PortInfo) -> bool {
+        let path_ok = match &self.rule.pattern {
+            Some(re) => {
+                re.is_match(&info.path) || info.by_id.as_deref().is_some_and(|n| re.is_match(n))
+            }
+            None => self.rule.usb.is_some() || scan::is_system_port(&info.path),
+        };
+
+        let usb_ok = match &self.rule.usb {
+            Some(m) => usb_matches(m, info),
+            None => true,
+        };
+
+        path_ok && usb_ok
+    }
+
+    /// `resolved` holds the claimed paths after following symlinks.
+    fn is_claimed(&self, info: &PortInfo, resolved: &HashSet<String>) -> bool {
+        resolved.contains(&info.path)
+            || self.claimed.iter().any(|spec| match &spec.usb {
+                Some(m) => usb_matches(m, info),
+                None => spec.path == info.path,
+            })
+    }
+
+    async fn attach(&self, info: &PortInfo, attached: &HashMap<String, Attached>) -> Attached {
+        let taken = self
+            .claimed
+            .iter()
+            .filter_map(|s| s.alias.clone())
+            .chain(attached.values().filter_map(|a| a.source.alias.clone()))
+            .collect::<HashSet<_>>();
+
+        let spec = PortSpec {
+            path: info.path.clone(),
+            alias: auto_alias(info, &taken),
+            ..PortSpec::default()
+        }
+        .resolve(&self.defaults);
+        let source = spec.source_id();
+
+        let msg = match &spec.alias {
+            Some(alias) => format!("attached: {} as {}", info.path, alias),
+            None => format!("attached: {}", info.path),
+        };
+        self.system(LogLevel::Info, msg).await;
+
+        let (stop_rx, stop) = shutdown_channel();
+        let task = spawn_source(
+            Box::new(SerialSource::new(spec)),
+            self.tx.clone(),
+            &self.commands,
+            self.capture.clone(),
+            None,
+            stop_rx,
+        );
+
+        Attached { source, stop, task }
+    }
+
+    async fn detach(&self, path: &str, a: Attached) {
+        a.stop.trigger();
+        let _ = a.task.await;
+        self.commands.unregister(&a.source);
+
+        let msg = match &a.source.alias {
+            Some(alias) => format!("detached: {} ({})", alias, path),
+            None => format!("detached: {}", path),
+        };
+        self.system(LogLevel::Info, msg).await;
+    }
+
+    async fn system(&self, level: LogLevel, message: String) {
+        let _ = self.tx.send(AppEvent::System { level, message }).await;
+    }
+}
+
+fn usb_matches(m: &UsbMatch, info: &PortInfo) -> bool {
+    match (info.vid, info.pid) {
+        (Some(vid), Some(pid)) => m.is_match(
+            vid,
+            pid,
+            info.serial.as_deref(),
+            info.manufacturer.as_deref(),
+            info.product.as_deref(),
+        ),
+        _ => false,
+    }
+}
+
+/// Builds a readable alias from USB descriptors, e.g. `CP2102-USB-UART-1A2B`
+/// from the product name and the tail of the serial number. Ports without
+/// descriptors keep their device path as label.
+fn auto_alias(info: &PortInfo, taken: &HashSet<String>) -> Option<String> {
+    let name = info.product.as_deref().or(info.manufacturer.as_deref())?;
+
+    let mut base = name
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    base.truncate(MAX_ALIAS_LEN);
+    let base = base.trim_end_matches('-').to_string();
+    if base.is_empty() {
+        return None;
+    }
+
+    let base = match info.serial.as_deref().filter(|s| !s.is_empty()) {
+        Some(serial) => {
+            let tail = serial.chars().rev().take(4).collect::<Vec<_>>();
+            let tail = tail.into_iter().rev().collect::<String>();
+            format!("{base}-{tail}")
+        }
+        None => base,
+    };
+
+    if !taken.contains(&base) {
+        return Some(base);
+    }
+    (2..)
+        .map(|n| format!("{base}-{n}"))
+        .find(|a| !taken.contains(a))
+}
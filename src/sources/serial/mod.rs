@@ -1,4 +1,6 @@
+pub mod hotplug;
 pub mod port;
 pub mod scan;
 
+pub use hotplug::HotplugWatcher;
 pub use port::SerialSource;
@@ -1,229 +1,207 @@
-use crate::core::{AppEvent, LogLevel, ResolvedPortSpec, SourceId};
-use crate::runtime::Shutdown;
-use std::time::SystemTime;
-use tokio::io::AsyncReadExt;
-use tokio::sync::mpsc;
-use tokio::task::JoinHandle;
-use tokio::time::{Duration, sleep};
-use tokio_serial::{SerialPort, SerialPortBuilderExt, SerialStream};
+use crate::core::{
+    AppError, AppResult, FlowControl, Framing, LogLevel, Parity, PortState, ResolvedPortSpec,
+    SourceId,
+};
+use crate::sources::link::Link;
+use crate::sources::serial::scan;
+use crate::sources::{EventSource, SourceContext, SourceFuture};
+use tokio::time::Duration;
+use tokio_serial::{DataBits, SerialPort, SerialPortBuilderExt, SerialStream, StopBits};
 
 #[cfg(target_os = "macos")]
-use nix::sys::termios::{SetArg, cfmakeraw, tcgetattr, tcsetattr};
+use nix::sys::termios::{InputFlags, LocalFlags, OutputFlags, SetArg, tcgetattr, tcsetattr};
 #[cfg(target_os = "macos")]
 use std::os::fd::{AsRawFd, BorrowedFd};
 
-const MAX_ACC_BYTES: usize = 64 * 1024;
-
+/// One serial port, reopened whenever it disappears.
 pub struct SerialSource {
-    ports: Vec<ResolvedPortSpec>,
-    tx: mpsc::Sender<AppEvent>,
-    shutdown: Shutdown,
-    reconnect_delay: Duration,
+    spec: ResolvedPortSpec,
 }
 
 impl SerialSource {
-    pub fn new(
-        ports: Vec<ResolvedPortSpec>,
-        tx: mpsc::Sender<AppEvent>,
-        shutdown: Shutdown,
-    ) -> Self {
-        Self {
-            ports,
-            tx,
-            shutdown,
-            reconnect_delay: Duration::from_secs(1),
-        }
+    pub fn new(spec: ResolvedPortSpec) -> Self {
+        Self { spec }
     }
+}
 
-    pub fn spawn(self) -> Vec<JoinHandle<()>> {
-        let mut handles = Vec::with_capacity(self.ports.len());
-
-        for spec in self.ports {
-            let tx = self.tx.clone();
-            let shutdown = self.shutdown.clone();
-            let reconnect_delay = self.reconnect_delay;
-
-            handles.push(tokio::spawn(async move {
-                run_port_loop(spec, tx, shutdown, reconnect_delay).await;
-            }));
-        }
+impl EventSource for SerialSource {
+    fn source_id(&self) -> SourceId {
+        self.spec.source_id()
+    }
 
-        handles
+    fn run(self: Box<Self>, ctx: SourceContext) -> SourceFuture {
+        let link = Link::new("serial", &self.spec, ctx);
+        Box::pin(run_port_loop(self.spec, link))
     }
 }
 
-async fn run_port_loop(
-    spec: ResolvedPortSpec,
-    tx: mpsc::Sender<AppEvent>,
-    mut shutdown: Shutdown,
-    reconnect_delay: Duration,
-) {
-    let source = SourceId {
-        port: spec.path.clone(),
-        alias: spec.alias.clone(),
-    };
-
+async fn run_port_loop(spec: ResolvedPortSpec, mut link: Link) {
     loop {
-        if shutdown.is_triggered() {
+        if link.is_shutdown() {
             break;
         }
 
-        let mut port = match open_serial(&spec).await {
+        let path = match resolve_device(&spec) {
+            Ok(p) => p,
+            Err(e) => {
+                link.system(
+                    LogLevel::Warn,
+                    format!("serial port unavailable ({}): {}", spec.path, e),
+                )
+                .await;
+                link.report_state(PortState::Disconnected).await;
+                link.wait_reconnect().await;
+                continue;
+            }
+        };
+
+        let mut port = match open_serial(&spec, &path).await {
             Ok(p) => p,
             Err(e) => {
-                let _ = tx
-                    .send(AppEvent::System {
-                        level: LogLevel::Warn,
-                        message: format!(
-                            "serial open failed ({} @ {}): {}",
-                            spec.path, spec.baud, e
-                        ),
-                    })
-                    .await;
-                sleep(reconnect_delay).await;
+                link.system(
+                    LogLevel::Warn,
+                    format!("serial open failed ({} @ {}): {}", path, spec.baud, e),
+                )
+                .await;
+                link.report_state(PortState::Disconnected).await;
+                link.wait_reconnect().await;
                 continue;
             }
         };
 
-        let _ = tx
-            .send(AppEvent::System {
-                level: LogLevel::Info,
-                message: format!("connected: {} @ {}", spec.path, spec.baud),
-            })
-            .await;
+        let target = if path == spec.path {
+            path
+        } else {
+            format!("{} ({})", path, spec.path)
+        };
+        link.system(
+            LogLevel::Info,
+            format!("connected: {} @ {} {}", target, spec.baud, spec.framing),
+        )
+        .await;
+        link.report_state(PortState::Connected).await;
 
-        let disconnected = read_lines(&mut port, &source, &tx, &mut shutdown).await;
+        let disconnected = link.pump(&mut port).await;
 
-        if shutdown.is_triggered() {
+        link.report_state(PortState::Disconnected).await;
+
+        if link.is_shutdown() {
             break;
         }
 
         if disconnected {
-            sleep(reconnect_delay).await;
+            link.wait_reconnect().await;
         }
     }
 }
 
-async fn open_serial(spec: &ResolvedPortSpec) -> Result<SerialStream, tokio_serial::Error> {
-    let mut port = tokio_serial::new(&spec.path, spec.baud)
+/// Maps the spec to a device path. Called before every open attempt, so a
+/// board that comes back under a different name is still found.
+fn resolve_device(spec: &ResolvedPortSpec) -> AppResult<String> {
+    if let Some(usb) = &spec.usb {
+        return scan::find_usb_port(usb);
+    }
+
+    // Follow stable symlinks such as /dev/serial/by-id/* to the real node.
+    let is_link = std::fs::symlink_metadata(&spec.path).is_ok_and(|m| m.file_type().is_symlink());
+    if is_link {
+        return std::fs::canonicalize(&spec.path)
+            .map(|p| p.to_string_lossy().into_owned())
+            .map_err(|e| AppError::Scan(e.to_string()));
+    }
+
+    Ok(spec.path.clone())
+}
+
+async fn open_serial(
+    spec: &ResolvedPortSpec,
+    path: &str,
+) -> Result<SerialStream, tokio_serial::Error> {
+    let mut builder = tokio_serial::new(path, spec.baud)
         .timeout(Duration::from_millis(100))
-        .dtr_on_open(true)
-        .open_native_async()?;
+        .data_bits(data_bits(spec.framing))
+        .parity(parity(spec.framing.parity))
+        .stop_bits(stop_bits(spec.framing))
+        .flow_control(flow_control(spec.flow_control));
+
+    // `Keep` leaves DTR to the driver; most OSes still raise it on open.
+    if let Some(level) = spec.dtr.level() {
+        builder = builder.dtr_on_open(level);
+    }
+
+    let mut port = builder.open_native_async()?;
 
     #[cfg(target_os = "macos")]
     {
         let raw_fd = port.as_raw_fd();
         let fd = unsafe { BorrowedFd::borrow_raw(raw_fd) };
 
+        // Raw mode without `cfmakeraw`, which would also reset the data
+        // bits, parity and XON/XOFF flow control set above.
         if let Ok(mut t) = tcgetattr(fd) {
-            cfmakeraw(&mut t);
+            t.input_flags.remove(
+                InputFlags::IGNBRK
+                    | InputFlags::BRKINT
+                    | InputFlags::PARMRK
+                    | InputFlags::ISTRIP
+                    | InputFlags::INLCR
+                    | InputFlags::IGNCR
+                    | InputFlags::ICRNL,
+            );
+            t.output_flags.remove(OutputFlags::OPOST);
+            t.local_flags.remove(
+                LocalFlags::ECHO
+                    | LocalFlags::ECHONL
+                    | LocalFlags::ICANON
+                    | LocalFlags::ISIG
+                    | LocalFlags::IEXTEN,
+            );
             let _ = tcsetattr(fd, SetArg::TCSANOW, &t);
         }
     }
 
-    let _ = port.write_data_terminal_ready(true);
-    let _ = port.write_request_to_send(true);
+    if let Some(level) = spec.dtr.level() {
+        let _ = port.write_data_terminal_ready(level);
+    }
+
+    // With RTS/CTS handshaking the driver owns RTS.
+    if spec.flow_control != FlowControl::Hardware
+        && let Some(level) = spec.rts.level()
+    {
+        let _ = port.write_request_to_send(level);
+    }
 
     Ok(port)
 }
 
-async fn read_lines(
-    port: &mut SerialStream,
-    source: &SourceId,
-    tx: &mpsc::Sender<AppEvent>,
-    shutdown: &mut Shutdown,
-) -> bool {
-    let mut buf = [0u8; 2048];
-    let mut acc: Vec<u8> = Vec::with_capacity(4096);
-
-    loop {
-        if shutdown.is_triggered() {
-            return false;
-        }
-
-        tokio::select! {
-            _ = shutdown.changed() => {
-                if shutdown.is_triggered() {
-                    return false;
-                }
-            }
-            res = port.read(&mut buf) => {
-                let n = match res {
-                    Ok(0) => {
-                        let _ = tx.send(AppEvent::System {
-                            level: LogLevel::Warn,
-                            message: "serial EOF".to_string(),
-                        }).await;
-                        return true;
-                    }
-                    Ok(n) => n,
-                    Err(e) if is_transient_read_error(&e) => {
-                        continue;
-                    }
-                    Err(e) => {
-                        let _ = tx.send(AppEvent::System {
-                            level: LogLevel::Error,
-                            message: format!("serial read failed: {e}"),
-                        }).await;
-                        return true;
-                    }
-                };
-
-                acc.extend_from_slice(&buf[..n]);
-
-                if acc.len() > MAX_ACC_BYTES {
-                    acc.clear();
-                    let _ = tx.send(AppEvent::System {
-                        level: LogLevel::Warn,
-                        message: format!(
-                            "serial buffer overflow on {} (>{} bytes): dropping partial line",
-                            source.label(),
-                            MAX_ACC_BYTES
-                        ),
-                    }).await;
-                }
-
-                while let Some(raw) = try_pop_line(&mut acc) {
-                    if raw.is_empty() {
-                        continue;
-                    }
-
-                    if tx.send(AppEvent::LogLine {
-                        source: source.clone(),
-                        ts: SystemTime::now(),
-                        raw,
-                    }).await.is_err() {
-                        return false;
-                    }
-                }
-            }
-        }
+fn data_bits(framing: Framing) -> DataBits {
+    match framing.data_bits {
+        5 => DataBits::Five,
+        6 => DataBits::Six,
+        7 => DataBits::Seven,
+        _ => DataBits::Eight,
     }
 }
 
-fn is_transient_read_error(e: &std::io::Error) -> bool {
-    matches!(
-        e.kind(),
-        std::io::ErrorKind::TimedOut
-            | std::io::ErrorKind::WouldBlock
-            | std::io::ErrorKind::Interrupted
-    )
+fn parity(parity: Parity) -> tokio_serial::Parity {
+    match parity {
+        Parity::None => tokio_serial::Parity::None,
+        Parity::Odd => tokio_serial::Parity::Odd,
+        Parity::Even => tokio_serial::Parity::Even,
+    }
 }
 
-fn try_pop_line(acc: &mut Vec<u8>) -> Option<String> {
-    let pos = acc.iter().position(|&b| b == b'\n' || b == b'\r')?;
-
-    let raw = String::from_utf8_lossy(&acc[..pos]).trim().to_string();
-
-    acc.drain(..=pos);
-
-    let mut k = 0usize;
-    while k < acc.len() && (acc[k] == b'\n' || acc[k] == b'\r') {
-        k += 1;
-    }
-    if k > 0 {
-        acc.drain(..k);
+fn stop_bits(framing: Framing) -> StopBits {
+    match framing.stop_bits {
+        2 => StopBits::Two,
+        _ => StopBits::One,
     }
+}
 
-    Some(raw)
+fn flow_control(flow: FlowControl) -> tokio_serial::FlowControl {
+    match flow {
+        FlowControl::None => tokio_serial::FlowControl::None,
+        FlowControl::Software => tokio_serial::FlowControl::Software,
+        FlowControl::Hardware => tokio_serial::FlowControl::Hardware,
+    }
 }
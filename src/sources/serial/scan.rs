@@ -1,8 +1,52 @@
 use regex::Regex;
+use serde::{Serialize, Serializer};
 
-use crate::core::{AppError, AppResult};
+use crate::core::port_spec::BY_ID_DIR;
+use crate::core::{AppError, AppResult, UsbMatch};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use tokio_serial::SerialPortType;
 
-pub fn list_available_ports(regex: Option<String>) -> AppResult<Vec<String>> {
+#[derive(Debug, Clone, Serialize)]
+pub struct PortInfo {
+    pub path: String,
+    #[serde(rename = "type")]
+    pub kind: PortKind,
+    #[serde(serialize_with = "hex_id")]
+    pub vid: Option<u16>,
+    #[serde(serialize_with = "hex_id")]
+    pub pid: Option<u16>,
+    pub serial: Option<String>,
+    pub manufacturer: Option<String>,
+    pub product: Option<String>,
+    pub by_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PortKind {
+    Usb,
+    Pci,
+    Bluetooth,
+    Unknown,
+}
+
+impl PortKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Usb => "usb",
+            Self::Pci => "pci",
+            Self::Bluetooth => "bluetooth",
+            Self::Unknown => "unknown",
+        }
+    }
+}
+
+/// Lists serial ports with their USB metadata.
+///
+/// With `regex`, ports whose path matches it are kept; otherwise `all`
+/// decides between every port and just the usual USB/ACM/COM devices.
+pub fn list_available_ports(regex: Option<String>, all: bool) -> AppResult<Vec<PortInfo>> {
     let all_ports = tokio_serial::available_ports().map_err(|e| AppError::Scan(e.to_string()))?;
     let mut matching_ports = Vec::new();
 
@@ -12,25 +56,118 @@ pub fn list_available_ports(regex: Option<String>) -> AppResult<Vec<String>> {
         None
     };
 
+    let by_id = by_id_links();
+
     for p in all_ports {
         let name = p.port_name;
 
         let keep = match &user_re {
             Some(re) => re.is_match(&name),
-            None => is_system_port(&name),
+            None => all || is_system_port(&name),
         };
 
-        if keep {
-            matching_ports.push(name);
+        if !keep {
+            continue;
+        }
+
+        let mut info = PortInfo {
+            by_id: by_id.get(&name).cloned(),
+            path: name,
+            kind: PortKind::Unknown,
+            vid: None,
+            pid: None,
+            serial: None,
+            manufacturer: None,
+            product: None,
+        };
+        match p.port_type {
+            SerialPortType::UsbPort(usb) => {
+                info.kind = PortKind::Usb;
+                info.vid = Some(usb.vid);
+                info.pid = Some(usb.pid);
+                info.serial = usb.serial_number;
+                info.manufacturer = usb.manufacturer;
+                info.product = usb.product;
+            }
+            SerialPortType::PciPort => info.kind = PortKind::Pci,
+            SerialPortType::BluetoothPort => info.kind = PortKind::Bluetooth,
+            SerialPortType::Unknown => {}
         }
+        matching_ports.push(info);
     }
 
-    matching_ports.sort();
+    matching_ports.sort_by(|a, b| a.path.cmp(&b.path));
     Ok(matching_ports)
 }
 
+/// Maps real device paths to their `/dev/serial/by-id` names.
+fn by_id_links() -> HashMap<String, String> {
+    let Ok(entries) = std::fs::read_dir(BY_ID_DIR) else {
+        return HashMap::new();
+    };
+
+    entries
+        .flatten()
+        .filter_map(|e| {
+            let target: PathBuf = std::fs::canonicalize(e.path()).ok()?;
+            Some((
+                target.to_string_lossy().into_owned(),
+                e.file_name().to_string_lossy().into_owned(),
+            ))
+        })
+        .collect()
+}
+
+fn hex_id<S: Serializer>(id: &Option<u16>, s: S) -> Result<S::Ok, S::Error> {
+    match id {
+        Some(id) => s.serialize_str(&format!("{id:04x}")),
+        None => s.serialize_none(),
+    }
+}
+
+/// Finds the device path of the single USB port matching `m`.
+pub fn find_usb_port(m: &UsbMatch) -> AppResult<String> {
+    let all_ports = tokio_serial::available_ports().map_err(|e| AppError::Scan(e.to_string()))?;
+
+    let mut found = all_ports
+        .into_iter()
+        .filter(|p| match &p.port_type {
+            SerialPortType::UsbPort(info) => m.is_match(
+                info.vid,
+                info.pid,
+                info.serial_number.as_deref(),
+                info.manufacturer.as_deref(),
+                info.product.as_deref(),
+            ),
+            _ => false,
+        })
+        .map(|p| p.port_name)
+        .collect::<Vec<_>>();
+
+    prefer_callout_devices(&mut found);
+    found.sort();
+
+    match found.as_slice() {
+        [] => Err(AppError::Scan(format!("no USB serial port matches {m}"))),
+        [path] => Ok(path.clone()),
+        _ => Err(AppError::Scan(format!(
+            "{m} is ambiguous: {} (add serial= to narrow it down)",
+            found.join(", ")
+        ))),
+    }
+}
+
+/// macOS lists every device twice; keep the `/dev/cu.*` node.
+#[cfg(target_os = "macos")]
+fn prefer_callout_devices(paths: &mut Vec<String>) {
+    paths.retain(|p| !p.starts_with("/dev/tty."));
+}
+
+#[cfg(not(target_os = "macos"))]
+fn prefer_callout_devices(_paths: &mut Vec<String>) {}
+
 #[cfg(target_os = "macos")]
-fn is_system_port(name: &str) -> bool {
+pub(crate) fn is_system_port(name: &str) -> bool {
     if !name.starts_with("/dev/tty.") && !name.starts_with("/dev/cu.") {
         return false;
     }
@@ -39,18 +176,18 @@ fn is_system_port(name: &str) -> bool {
 }
 
 #[cfg(target_os = "linux")]
-fn is_system_port(name: &str) -> bool {
+pub(crate) fn is_system_port(name: &str) -> bool {
     let re = Regex::new(r"/dev/(ttyACM\d+|ttyUSB\d+)").unwrap();
     re.is_match(name)
 }
 
 #[cfg(target_os = "windows")]
-fn is_system_port(name: &str) -> bool {
+pub(crate) fn is_system_port(name: &str) -> bool {
     let re = Regex::new(r"(?i)^COM\d+$").unwrap();
     re.is_match(name)
 }
 
 #[cfg(not(any(target_os = "macos", target_os = "linux", target_os = "windows")))]
-fn is_system_port(_name: &str) -> bool {
+pub(crate) fn is_system_port(_name: &str) -> bool {
     true
 }
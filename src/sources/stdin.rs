@@ -0,0 +1,119 @@
+use crate::core::{LogLevel, PortState, ResolvedPortSpec, SourceId};
+use crate::runtime::ShutdownHandle;
+use crate::sources::link::{Link, deadline};
+use crate::sources::{EventSource, SourceContext, SourceFuture};
+
+use std::io::Read;
+use tokio::sync::mpsc;
+
+/// Lines piped into Octolog, e.g. from `adb logcat` or `journalctl -f`.
+pub struct StdinSource {
+    spec: ResolvedPortSpec,
+    exit_when_done: Option<ShutdownHandle>,
+}
+
+impl StdinSource {
+    pub fn new(spec: ResolvedPortSpec) -> Self {
+        Self {
+            spec,
+            exit_when_done: None,
+        }
+    }
+
+    /// Ends the session once stdin reaches end of file.
+    pub fn exit_when_done(mut self, handle: ShutdownHandle) -> Self {
+        self.exit_when_done = Some(handle);
+        self
+    }
+}
+
+impl EventSource for StdinSource {
+    fn source_id(&self) -> SourceId {
+        self.spec.source_id()
+    }
+
+    fn run(self: Box<Self>, ctx: SourceContext) -> SourceFuture {
+        let link = Link::new("stdin", &self.spec, ctx);
+        Box::pin(run_stdin(link, self.exit_when_done))
+    }
+}
+
+async fn run_stdin(mut link: Link, exit_when_done: Option<ShutdownHandle>) {
+    let mut chunks = spawn_reader();
+
+    link.report_state(PortState::Connected).await;
+
+    loop {
+        if link.is_shutdown() {
+            break;
+        }
+
+        let idle = link.idle_deadline();
+        tokio::select! {
+            _ = deadline(idle) => {
+                if !link.flush().await {
+                    break;
+                }
+            }
+            _ = link.shutdown.changed() => {}
+            chunk = chunks.recv() => match chunk {
+                Some(Ok(bytes)) => {
+                    if !link.feed(&bytes).await {
+                        break;
+                    }
+                }
+                Some(Err(e)) => {
+                    link.flush().await;
+                    link.system(LogLevel::Error, format!("stdin read failed: {e}"))
+                        .await;
+                    break;
+                }
+                None => {
+                    link.flush().await;
+                    link.system(LogLevel::Info, "stdin closed".to_string())
+                        .await;
+                    break;
+                }
+            },
+            Some(_) = link.cmds.recv() => {
+                link.system(
+                    LogLevel::Warn,
+                    format!("{} is read-only: command dropped", link.source.label()),
+                )
+                .await;
+            }
+        }
+    }
+
+    link.report_state(PortState::Disconnected).await;
+
+    if let Some(handle) = exit_when_done
+        && !link.is_shutdown()
+    {
+        handle.trigger();
+    }
+}
+
+/// Reads stdin on a plain thread rather than the runtime's blocking pool,
+/// since a pending read would otherwise keep the runtime from shutting down.
+/// The channel closes at end of file.
+fn spawn_reader() -> mpsc::Receiver<std::io::Result<Vec<u8>>> {
+    let (tx, rx) = mpsc::channel(16);
+    std::thread::spawn(move || {
+        let mut stdin = std::io::stdin().lock();
+        let mut buf = [0u8; 8192];
+        loop {
+            let chunk = match stdin.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => Ok(buf[..n].to_vec()),
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => Err(e),
+            };
+            let failed = chunk.is_err();
+            if tx.blocking_send(chunk).is_err() || failed {
+                break;
+            }
+        }
+    });
+    rx
+}
@@ -0,0 +1,229 @@
+use crate::core::{LogLevel, PortState, ResolvedPortSpec, SourceId, Transport};
+use crate::sources::link::Link;
+use crate::sources::{EventSource, SourceContext, SourceFuture};
+
+use std::fs::Metadata;
+use std::io::SeekFrom;
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio::time::{Duration, Instant, sleep};
+
+/// How often a followed file is checked for new data, rotation and
+/// truncation.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Follows a file like `tail -F`: starts at its current end, picks up the
+/// new file when the path is rotated or recreated, and starts over when it
+/// is truncated.
+pub struct FileSource {
+    spec: ResolvedPortSpec,
+}
+
+impl FileSource {
+    pub fn new(spec: ResolvedPortSpec) -> Self {
+        Self { spec }
+    }
+}
+
+impl EventSource for FileSource {
+    fn source_id(&self) -> SourceId {
+        self.spec.source_id()
+    }
+
+    fn run(self: Box<Self>, ctx: SourceContext) -> SourceFuture {
+        let path = match &self.spec.transport {
+            Transport::File { path } => path.clone(),
+            _ => self.spec.path.clone(),
+        };
+        let link = Link::new("file", &self.spec, ctx);
+        Box::pin(run_tail(path, link))
+    }
+}
+
+async fn run_tail(path: String, mut link: Link) {
+    let target = format!("file:{path}");
+    // Only the file found at startup is skipped to its end; files that
+    // appear later are read from the start.
+    let mut from_end = true;
+    let mut missing = false;
+
+    loop {
+        if link.is_shutdown() {
+            break;
+        }
+
+        let mut file = match File::open(&path).await {
+            Ok(f) => f,
+            Err(e) => {
+                if !missing {
+                    missing = true;
+                    link.system(LogLevel::Warn, format!("waiting for {target}: {e}"))
+                        .await;
+                    link.report_state(PortState::Disconnected).await;
+                }
+                from_end = false;
+                if !idle(&mut link).await {
+                    break;
+                }
+                continue;
+            }
+        };
+        missing = false;
+
+        let meta = file.metadata().await.ok();
+        if meta.as_ref().is_some_and(Metadata::is_dir) {
+            link.system(
+                LogLevel::Error,
+                format!("{target}: is a directory, not following it"),
+            )
+            .await;
+            break;
+        }
+
+        let mut pos = 0;
+        if from_end {
+            pos = file.seek(SeekFrom::End(0)).await.unwrap_or(0);
+            from_end = false;
+        }
+        let identity = meta.as_ref().and_then(file_identity);
+
+        link.system(LogLevel::Info, format!("following {target}"))
+            .await;
+        link.report_state(PortState::Connected).await;
+
+        match follow(&path, &mut file, pos, identity, &mut link).await {
+            Followed::Shutdown => break,
+            Followed::Rotated => {
+                link.system(LogLevel::Info, format!("{target}: rotated, reopening"))
+                    .await;
+            }
+            Followed::Gone => {}
+        }
+    }
+
+    link.report_state(PortState::Disconnected).await;
+}
+
+/// Why `follow` stopped reading a file.
+enum Followed {
+    Shutdown,
+    /// The path now names a different file.
+    Rotated,
+    /// The path was removed or moved away.
+    Gone,
+}
+
+/// Reads new data from `file` until the path points at a different file or
+/// none. Read errors are retried at the same position every poll interval.
+async fn follow(
+    path: &str,
+    file: &mut File,
+    mut pos: u64,
+    identity: Option<(u64, u64)>,
+    link: &mut Link,
+) -> Followed {
+    let mut buf = vec![0u8; 8192];
+    let mut failing = false;
+
+    loop {
+        match file.read(&mut buf).await {
+            Ok(0) => {
+                failing = false;
+                if link.idle_deadline().is_some_and(|at| at <= Instant::now())
+                    && !link.flush().await
+                {
+                    return Followed::Shutdown;
+                }
+            }
+            Ok(n) => {
+                failing = false;
+                pos += n as u64;
+                if !link.feed(&buf[..n]).await {
+                    return Followed::Shutdown;
+                }
+                continue;
+            }
+            Err(e) => {
+                if !failing {
+                    failing = true;
+                    link.system(
+                        LogLevel::Error,
+                        format!("file:{path}: read failed: {e}, retrying"),
+                    )
+                    .await;
+                }
+                if !idle(link).await {
+                    return Followed::Shutdown;
+                }
+                pos = file.seek(SeekFrom::Start(pos)).await.unwrap_or(pos);
+                continue;
+            }
+        }
+
+        // At the end of the data: anything left of the old file has been
+        // read, so a rotation can be followed without losing lines.
+        match tokio::fs::metadata(path).await {
+            Ok(meta) if identity.is_some() && file_identity(&meta) != identity => {
+                link.flush().await;
+                return Followed::Rotated;
+            }
+            Ok(meta) if meta.len() < pos => {
+                link.flush().await;
+                link.system(
+                    LogLevel::Warn,
+                    format!("file:{path}: truncated, reading from the start"),
+                )
+                .await;
+                pos = file.seek(SeekFrom::Start(0)).await.unwrap_or(0);
+                continue;
+            }
+            Ok(_) => {}
+            // Moved away and not recreated yet.
+            Err(_) => {
+                link.flush().await;
+                return Followed::Gone;
+            }
+        }
+
+        if !idle(link).await {
+            return Followed::Shutdown;
+        }
+    }
+}
+
+/// Waits one poll interval, rejecting commands: a file cannot be written
+/// to. Returns `false` on shutdown.
+async fn idle(link: &mut Link) -> bool {
+    let delay = sleep(POLL_INTERVAL);
+    tokio::pin!(delay);
+
+    loop {
+        if link.is_shutdown() {
+            return false;
+        }
+
+        tokio::select! {
+            _ = &mut delay => return true,
+            _ = link.shutdown.changed() => {}
+            Some(_) = link.cmds.recv() => {
+                link.system(
+                    LogLevel::Warn,
+                    format!("{} is read-only: command dropped", link.source.label()),
+                )
+                .await;
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+fn file_identity(meta: &Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((meta.dev(), meta.ino()))
+}
+
+/// Without inode numbers only truncation is detected.
+#[cfg(not(unix))]
+fn file_identity(_meta: &Metadata) -> Option<(u64, u64)> {
+    None
+}